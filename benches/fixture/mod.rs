@@ -0,0 +1,116 @@
+//! Minimal writer for the CZDB file layout shared by the benches, kept separate from the
+//! crate's own test fixtures.
+
+use aes::{
+    Aes128,
+    cipher::{BlockEncryptMut, KeyInit, block_padding::Pkcs7},
+};
+use base64::{Engine, engine::general_purpose};
+use czdb::DbType;
+use std::{collections::HashMap, net::IpAddr, path::PathBuf};
+
+const KEY: [u8; 16] = *b"0123456789abcdef";
+const CLIENT_ID: u32 = 42;
+const EXPIRY: u32 = 991231;
+const PADDING: usize = 8;
+const SUPER_PART_LENGTH: usize = 17;
+const HEADER_BLOCK_LENGTH: usize = 20;
+
+pub fn test_key() -> String {
+    general_purpose::STANDARD.encode(KEY)
+}
+
+fn ip_bytes(ip: IpAddr) -> [u8; 16] {
+    let mut bytes = [0u8; 16];
+    match ip {
+        IpAddr::V4(ip) => bytes[..4].copy_from_slice(&ip.octets()),
+        IpAddr::V6(ip) => bytes.copy_from_slice(&ip.octets()),
+    }
+    bytes
+}
+
+/// A complete database of sorted `(start, end, region)` blocks with inline regions and a
+/// header entry every `header_every` blocks.
+pub fn build(db_type: DbType, blocks: &[(IpAddr, IpAddr, String)], header_every: usize) -> Vec<u8> {
+    let (ip_len, blen) = match db_type {
+        DbType::Ipv4 => (4, 13),
+        DbType::Ipv6 => (16, 37),
+    };
+    let mut headers: Vec<usize> = (0..blocks.len()).step_by(header_every).collect();
+    if headers.last() != Some(&(blocks.len() - 1)) {
+        headers.push(blocks.len() - 1);
+    }
+    let header_size = headers.len() * HEADER_BLOCK_LENGTH;
+    let start_index = SUPER_PART_LENGTH + header_size;
+    let end_index = start_index + (blocks.len() - 1) * blen;
+    // Index, then a zero column selection, then the region records.
+    let mut region_ptr = end_index + blen + 4;
+
+    let mut bindata = vec![if db_type == DbType::Ipv4 { 0 } else { 1 }];
+    bindata.extend_from_slice(&0u32.to_le_bytes());
+    bindata.extend_from_slice(&(start_index as u32).to_le_bytes());
+    bindata.extend_from_slice(&(header_size as u32).to_le_bytes());
+    bindata.extend_from_slice(&(end_index as u32).to_le_bytes());
+    for &i in &headers {
+        bindata.extend_from_slice(&ip_bytes(blocks[i].0));
+        bindata.extend_from_slice(&((start_index + i * blen) as u32).to_le_bytes());
+    }
+    let mut regions = Vec::new();
+    let mut shared = HashMap::new();
+    for (start, end, region) in blocks {
+        let (ptr, len) = *shared.entry(region).or_insert_with(|| {
+            let mut payload = Vec::new();
+            rmp::encode::write_uint(&mut payload, 0).unwrap();
+            rmp::encode::write_str(&mut payload, region).unwrap();
+            let ptr = region_ptr;
+            region_ptr += payload.len();
+            regions.extend_from_slice(&payload);
+            (ptr, payload.len())
+        });
+        bindata.extend_from_slice(&ip_bytes(*start)[..ip_len]);
+        bindata.extend_from_slice(&ip_bytes(*end)[..ip_len]);
+        bindata.extend_from_slice(&(ptr as u32).to_le_bytes());
+        bindata.push(len as u8);
+    }
+    bindata.extend_from_slice(&0u32.to_le_bytes());
+    bindata.extend_from_slice(&regions);
+    let file_size = bindata.len() as u32;
+    bindata[1..5].copy_from_slice(&file_size.to_le_bytes());
+
+    let mut plain = [0u8; 16];
+    plain[..4].copy_from_slice(&((CLIENT_ID << 20) | EXPIRY).to_le_bytes());
+    plain[4..8].copy_from_slice(&(PADDING as u32).to_le_bytes());
+    let encrypted = Aes128::new(&KEY.into())
+        .encrypt_padded_mut::<Pkcs7>(&mut plain, 8)
+        .unwrap()
+        .to_vec();
+    let mut file = Vec::new();
+    file.extend_from_slice(&1u32.to_le_bytes());
+    file.extend_from_slice(&CLIENT_ID.to_le_bytes());
+    file.extend_from_slice(&(encrypted.len() as u32).to_le_bytes());
+    file.extend_from_slice(&encrypted);
+    file.extend_from_slice(&[0u8; PADDING]);
+    file.extend_from_slice(&bindata);
+    file
+}
+
+/// A database written to a temporary file, removed on drop.
+pub struct TempDb(PathBuf);
+
+impl TempDb {
+    pub fn new(bytes: &[u8]) -> Self {
+        let path = std::env::temp_dir().join(format!("czdb-bench-{}.czdb", std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+        Self(path)
+    }
+
+    pub fn path_str(&self) -> &str {
+        self.0.to_str().unwrap()
+    }
+}
+
+impl Drop for TempDb {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
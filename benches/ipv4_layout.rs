@@ -0,0 +1,107 @@
+//! Compares `CzdbMemory`'s IPv4 lookup, a binary search over a flat array of start addresses
+//! that only touches the matched entry, with the layout it replaced: a binary search over an
+//! array of `(start, end, region)` entries.
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use czdb::{CzdbMemory, DbType};
+use fixture::{TempDb, test_key};
+use std::{
+    hint::black_box,
+    net::{IpAddr, Ipv4Addr},
+};
+
+mod fixture;
+
+struct Entry {
+    start_ip: u32,
+    end_ip: u32,
+    region_id: u32,
+}
+
+/// Contiguous ranges of pseudo-random width with a gap after every eighth one.
+fn entries(count: usize) -> Vec<Entry> {
+    let mut state = 0x2545_f491_u32;
+    let mut next = 0u32;
+    (0..count as u32)
+        .map(|i| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            let start_ip = next;
+            let end_ip = start_ip + state % 4096;
+            next = end_ip + if i % 8 == 7 { 256 } else { 1 };
+            Entry {
+                start_ip,
+                end_ip,
+                region_id: i % 1024,
+            }
+        })
+        .collect()
+}
+
+fn region(region_id: u32) -> String {
+    format!("region{region_id}")
+}
+
+/// The replaced layout: binary search directly over the entries.
+fn find_in_entries(entries: &[Entry], ip: u32) -> Option<u32> {
+    let (mut l, mut h) = (0usize, entries.len().checked_sub(1)?);
+    while l <= h {
+        let m = (l + h) >> 1;
+        let entry = &entries[m];
+        if ip >= entry.start_ip && ip <= entry.end_ip {
+            return Some(entry.region_id);
+        } else if ip < entry.start_ip {
+            h = m.checked_sub(1)?;
+        } else {
+            l = m + 1;
+        }
+    }
+    None
+}
+
+fn ipv4_layout(c: &mut Criterion) {
+    let mut group = c.benchmark_group("ipv4_layout");
+    for count in [10_000, 1_000_000] {
+        let entries = entries(count);
+        let blocks: Vec<_> = entries
+            .iter()
+            .map(|e| {
+                let (start, end) = (Ipv4Addr::from(e.start_ip), Ipv4Addr::from(e.end_ip));
+                (IpAddr::V4(start), IpAddr::V4(end), region(e.region_id))
+            })
+            .collect();
+        let file = TempDb::new(&fixture::build(DbType::Ipv4, &blocks, 1024));
+        let db = CzdbMemory::open(file.path_str(), &test_key()).unwrap();
+
+        let last = entries.last().map_or(0, |e| e.end_ip);
+        let ips: Vec<u32> = (0..4096u32)
+            .map(|i| i.wrapping_mul(0x9e37_79b9) % last)
+            .collect();
+        for &ip in &ips {
+            assert_eq!(
+                find_in_entries(&entries, ip).map(region).as_deref(),
+                db.search_v4(Ipv4Addr::from(ip))
+            );
+        }
+
+        group.bench_with_input(BenchmarkId::new("entries", count), &ips, |b, ips| {
+            b.iter(|| {
+                for &ip in ips {
+                    black_box(find_in_entries(&entries, black_box(ip)));
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("czdb", count), &ips, |b, ips| {
+            b.iter(|| {
+                for &ip in ips {
+                    black_box(db.search_v4(black_box(Ipv4Addr::from(ip))));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, ipv4_layout);
+criterion_main!(benches);
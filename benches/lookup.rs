@@ -0,0 +1,108 @@
+//! Single and batch lookups on every backend, over generated IPv4 and IPv6 databases.
+
+use criterion::{
+    BenchmarkGroup, Criterion, criterion_group, criterion_main, measurement::WallTime,
+};
+use czdb::{CzdbDisk, CzdbMemory, DbType};
+use fixture::{TempDb, test_key};
+use std::{
+    hint::black_box,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+};
+
+mod fixture;
+
+const BLOCKS: u128 = 4096;
+const LOOKUPS: usize = 1024;
+
+/// `BLOCKS` equal ranges covering the whole address space, sharing 256 distinct regions.
+fn database(db_type: DbType) -> TempDb {
+    let (bits, to_ip): (u32, fn(u128) -> IpAddr) = match db_type {
+        DbType::Ipv4 => (32, |n| Ipv4Addr::from(n as u32).into()),
+        DbType::Ipv6 => (128, |n| Ipv6Addr::from(n).into()),
+    };
+    let width = 1u128 << (bits - BLOCKS.trailing_zeros());
+    let blocks: Vec<_> = (0..BLOCKS)
+        .map(|i| {
+            let region = format!("省份{}\t城市{}", i % 16, i % 256);
+            (to_ip(i * width), to_ip(i * width + (width - 1)), region)
+        })
+        .collect();
+    TempDb::new(&fixture::build(db_type, &blocks, 64))
+}
+
+/// Addresses spread pseudo-randomly over the whole address space.
+fn addresses(db_type: DbType) -> Vec<IpAddr> {
+    let mut state = 0x853c_49e6_748f_ea9b_u64;
+    (0..LOOKUPS)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            match db_type {
+                DbType::Ipv4 => Ipv4Addr::from(state as u32).into(),
+                DbType::Ipv6 => Ipv6Addr::from(u128::from(state) << 64 | u128::from(state)).into(),
+            }
+        })
+        .collect()
+}
+
+fn bench_backend(
+    group: &mut BenchmarkGroup<'_, WallTime>,
+    backend: &str,
+    ips: &[IpAddr],
+    search: impl Fn(IpAddr) -> Option<String>,
+    search_many: impl Fn(&[IpAddr]) -> Vec<Option<String>>,
+) {
+    assert!(ips.iter().all(|&ip| search(ip).is_some()));
+    group.bench_function(format!("{backend}/single"), |b| {
+        let mut next = ips.iter().cycle();
+        b.iter(|| search(black_box(*next.next().unwrap())))
+    });
+    group.bench_function(format!("{backend}/batch_{LOOKUPS}"), |b| {
+        b.iter(|| search_many(black_box(ips)))
+    });
+}
+
+fn lookup(c: &mut Criterion) {
+    for (name, db_type) in [("ipv4", DbType::Ipv4), ("ipv6", DbType::Ipv6)] {
+        let db = database(db_type);
+        let ips = addresses(db_type);
+        let mut group = c.benchmark_group(name);
+
+        let disk = CzdbDisk::open(db.path_str(), &test_key()).unwrap();
+        bench_backend(
+            &mut group,
+            "disk",
+            &ips,
+            |ip| disk.search(ip),
+            |ips| disk.search_many(ips),
+        );
+
+        #[cfg(feature = "mmap")]
+        {
+            let mmap = czdb::CzdbMmap::open(db.path_str(), &test_key()).unwrap();
+            bench_backend(
+                &mut group,
+                "mmap",
+                &ips,
+                |ip| mmap.search(ip),
+                |ips| mmap.search_many(ips),
+            );
+        }
+
+        let memory = CzdbMemory::open(db.path_str(), &test_key()).unwrap();
+        bench_backend(
+            &mut group,
+            "memory",
+            &ips,
+            |ip| memory.search(ip),
+            |ips| memory.search_many(ips),
+        );
+
+        group.finish();
+    }
+}
+
+criterion_group!(benches, lookup);
+criterion_main!(benches);
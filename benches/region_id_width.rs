@@ -0,0 +1,86 @@
+//! Binary search over IPv4 index entries whose region id is a `u32`, as `CzdbMemory` stores
+//! them (12 bytes per entry), against the same entries with a `usize` region id (16 bytes on
+//! 64-bit targets).
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use std::{hint::black_box, mem::size_of};
+
+struct Entry<R> {
+    start_ip: u32,
+    end_ip: u32,
+    region_id: R,
+}
+
+/// Contiguous ranges of pseudo-random width with a gap after every eighth one.
+fn entries<R: TryFrom<u32>>(count: usize) -> Vec<Entry<R>> {
+    let mut state = 0x2545_f491_u32;
+    let mut next = 0u32;
+    (0..count as u32)
+        .map(|i| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            let start_ip = next;
+            let end_ip = start_ip + state % 4096;
+            next = end_ip + if i % 8 == 7 { 256 } else { 1 };
+            Entry {
+                start_ip,
+                end_ip,
+                region_id: R::try_from(i % 1024).ok().unwrap(),
+            }
+        })
+        .collect()
+}
+
+fn find<R: Copy>(entries: &[Entry<R>], ip: u32) -> Option<R> {
+    let (mut l, mut h) = (0usize, entries.len().checked_sub(1)?);
+    while l <= h {
+        let m = (l + h) >> 1;
+        let entry = &entries[m];
+        if ip >= entry.start_ip && ip <= entry.end_ip {
+            return Some(entry.region_id);
+        } else if ip < entry.start_ip {
+            h = m.checked_sub(1)?;
+        } else {
+            l = m + 1;
+        }
+    }
+    None
+}
+
+fn region_id_width(c: &mut Criterion) {
+    assert_eq!(size_of::<Entry<u32>>(), 12);
+
+    let mut group = c.benchmark_group("region_id_width");
+    for count in [10_000, 1_000_000] {
+        let narrow = entries::<u32>(count);
+        let wide = entries::<usize>(count);
+
+        let last = narrow.last().map_or(0, |e| e.end_ip);
+        let ips: Vec<u32> = (0..4096u32)
+            .map(|i| i.wrapping_mul(0x9e37_79b9) % last)
+            .collect();
+        for &ip in &ips {
+            assert_eq!(find(&narrow, ip).map(|id| id as usize), find(&wide, ip));
+        }
+
+        group.bench_with_input(BenchmarkId::new("u32", count), &ips, |b, ips| {
+            b.iter(|| {
+                for &ip in ips {
+                    black_box(find(&narrow, black_box(ip)));
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("usize", count), &ips, |b, ips| {
+            b.iter(|| {
+                for &ip in ips {
+                    black_box(find(&wide, black_box(ip)));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, region_id_width);
+criterion_main!(benches);
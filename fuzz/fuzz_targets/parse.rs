@@ -0,0 +1,20 @@
+#![no_main]
+
+//! Feed arbitrary bytes to `CzdbMemory::from_bytes`; it must return, never panic.
+//!
+//! A leading `0` byte feeds the rest as a whole file, exercising the hyper header. Any other
+//! input is wrapped in a valid, decryptable hyper header (see `czdb_fuzz::database`) so that
+//! it reaches the data-section parser.
+
+use czdb::CzdbMemory;
+use czdb_fuzz::{KEY, database};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let bytes = match data.split_first() {
+        Some((0, file)) => file.to_vec(),
+        Some((_, section)) => database(section),
+        None => return,
+    };
+    let _ = CzdbMemory::from_bytes(bytes, KEY);
+});
@@ -0,0 +1,39 @@
+#![no_main]
+
+//! Search databases that parse successfully; lookups must never panic.
+//!
+//! The last 16 bytes of the input are the queried address (IPv4 uses the first 4) and
+//! the rest is a data section, wrapped in a valid hyper header by `czdb_fuzz::database`.
+
+use czdb::{CzdbDisk, CzdbMemory, DbType};
+use czdb_fuzz::{KEY, database};
+use libfuzzer_sys::fuzz_target;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 16 {
+        return;
+    }
+    let (section, ip_bytes) = data.split_at(data.len() - 16);
+    let db_bytes = database(section);
+    let Ok(db) = CzdbMemory::from_bytes(db_bytes.clone(), KEY) else {
+        return;
+    };
+    let octets: [u8; 16] = ip_bytes.try_into().unwrap();
+    let ip = match db.supported_version() {
+        DbType::Ipv4 => IpAddr::V4(Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3])),
+        DbType::Ipv6 => IpAddr::V6(Ipv6Addr::from(octets)),
+    };
+    let _ = db.search(ip);
+    let _ = db.lookup_entry(ip);
+
+    // The disk backend reads the same bytes through its own bounds checks.
+    let path = std::env::temp_dir().join(format!("czdb-fuzz-{}.czdb", std::process::id()));
+    if std::fs::write(&path, &db_bytes).is_ok() {
+        if let Ok(disk) = CzdbDisk::open(path.to_str().unwrap(), KEY) {
+            let _ = disk.search(ip);
+            let _ = disk.lookup_entry(ip);
+        }
+        let _ = std::fs::remove_file(&path);
+    }
+});
@@ -0,0 +1,40 @@
+//! Helpers shared by the fuzz targets.
+
+use aes::Aes128;
+use cipher::{BlockEncryptMut, KeyInit, block_padding::Pkcs7};
+
+/// Base64 of the key every wrapped database is encrypted with.
+pub const KEY: &str = "MDEyMzQ1Njc4OWFiY2RlZg==";
+
+const KEY_BYTES: [u8; 16] = *b"0123456789abcdef";
+const CLIENT_ID: u32 = 1;
+const EXPIRY: u32 = 991231;
+const PADDING: u32 = 8;
+
+/// Wrap `data_section` in a valid hyper header encrypted with `KEY`.
+///
+/// Random bytes almost never decrypt, so without this the targets would only exercise the
+/// header checks. The declared data-section size is also patched to match, which lets the
+/// input reach the super part, header index, index and region parsing.
+pub fn database(data_section: &[u8]) -> Vec<u8> {
+    let mut section = data_section.to_vec();
+    if let Some(size) = section.get_mut(1..5) {
+        size.copy_from_slice(&(data_section.len() as u32).to_le_bytes());
+    }
+
+    let mut plain = [0u8; 16];
+    plain[..4].copy_from_slice(&((CLIENT_ID << 20) | EXPIRY).to_le_bytes());
+    plain[4..8].copy_from_slice(&PADDING.to_le_bytes());
+    let encrypted = Aes128::new(&KEY_BYTES.into())
+        .encrypt_padded_mut::<Pkcs7>(&mut plain, 8)
+        .expect("block fits");
+
+    let mut file = Vec::with_capacity(12 + encrypted.len() + PADDING as usize + section.len());
+    file.extend_from_slice(&1u32.to_le_bytes());
+    file.extend_from_slice(&CLIENT_ID.to_le_bytes());
+    file.extend_from_slice(&(encrypted.len() as u32).to_le_bytes());
+    file.extend_from_slice(encrypted);
+    file.resize(file.len() + PADDING as usize, 0);
+    file.extend_from_slice(&section);
+    file
+}
@@ -0,0 +1,289 @@
+use crate::{
+    CzError, CzdbOptions,
+    common::{
+        BackendKind, DbInfo, DbMeta, DbType, IndexBlock, Iterations, MatchedEntry, RecordSite,
+        Searcher, bytes_to_ip, check_index_order, decode_aes_key, decode_region_at,
+        decode_region_from_bytes, decode_region_ref, find_index_block, ip_to_bytes,
+        parse_meta_from_bytes, read_hyper_header, report_lookup_error,
+    },
+};
+use std::{borrow::Cow, io::Cursor, net::IpAddr};
+
+/// Zero-copy CZDB searcher over database bytes the caller already holds.
+///
+/// Runs the same search as `CzdbMmap` against a borrowed slice, for databases received over
+/// the network or embedded with `include_bytes!`, without writing them to a file first. Only
+/// the header partitions and the geo map are copied out; index and region bytes are read in
+/// place. Like the other backends it is `Send + Sync` and every query takes `&self`.
+///
+/// 针对调用方已持有的数据库字节的零拷贝 CZDB 查询器。与 `CzdbMmap` 使用相同的查询逻辑，
+/// 但作用于借用的切片，适用于通过网络接收或以 `include_bytes!` 嵌入的数据库，无需先写入文件。
+/// 仅复制头部分区与地理映射，索引与区域数据均原地读取。与其他后端一样实现了 `Send + Sync`，
+/// 且所有查询都只需 `&self`。
+#[derive(Debug)]
+pub struct CzdbBorrowed<'a> {
+    bindata: &'a [u8],
+    meta: DbMeta,
+}
+
+impl<'a> CzdbBorrowed<'a> {
+    /// Parse the header of a complete database file held in `data`.
+    ///
+    /// 解析 `data` 中完整数据库文件的头部。
+    pub fn from_bytes(data: &'a [u8], key: &str) -> Result<Self, CzError> {
+        Self::from_bytes_with_options(data, key, CzdbOptions::default())
+    }
+
+    /// `from_bytes` with custom options.
+    ///
+    /// 使用自定义选项的 `from_bytes`。
+    pub fn from_bytes_with_options(
+        data: &'a [u8],
+        key: &str,
+        options: CzdbOptions,
+    ) -> Result<Self, CzError> {
+        let (meta, data_offset) = load_meta(data, &decode_aes_key(key)?, options)?;
+        Ok(Self {
+            bindata: &data[data_offset..],
+            meta,
+        })
+    }
+
+    /// Search a single IP address.
+    ///
+    /// Consults the configured `ResultCache` first, if any. Regions listed in
+    /// `CzdbOptions::treat_as_miss` yield `None`.
+    ///
+    /// 查询指定 IP 地址；若配置了 `ResultCache`，会先查询缓存。
+    /// `CzdbOptions::treat_as_miss` 中列出的区域返回 `None`。
+    pub fn search(&self, ip: IpAddr) -> Option<String> {
+        self.try_search(ip).ok().flatten()
+    }
+
+    /// Search a single IP address, reporting corrupt records as errors; see
+    /// `CzdbMmap::try_search`.
+    ///
+    /// 查询指定 IP 地址，并将已损坏的记录作为错误返回，参见 `CzdbMmap::try_search`。
+    pub fn try_search(&self, ip: IpAddr) -> Result<Option<String>, CzError> {
+        try_search(self.bindata, &self.meta, ip)
+    }
+
+    /// Search a single IP address, borrowing from the input bytes for inline regions.
+    ///
+    /// Regions built from geo-map columns are allocated.
+    ///
+    /// 查询指定 IP；内联区域直接借用输入字节，由地理映射列组成的区域会分配新字符串。
+    pub fn search_ref(&self, ip: IpAddr) -> Option<Cow<'a, str>> {
+        let block = locate(self.bindata, &self.meta, ip, &mut ()).ok()??;
+        let region = decode_region_ref(
+            region_bytes(self.bindata, &block)?,
+            &self.meta,
+            RecordSite::lookup(ip, &block),
+        );
+        self.meta.filter_miss(region)
+    }
+
+    /// Search a batch of IP addresses.
+    ///
+    /// 批量查询 IP。
+    pub fn search_many(&self, ips: &[IpAddr]) -> Vec<Option<String>> {
+        ips.iter().map(|ip| self.search(*ip)).collect()
+    }
+
+    /// Look up the entry covering `ip`, returning its range, region and database type.
+    ///
+    /// 查询覆盖 `ip` 的条目，返回其地址范围、区域及数据库类型。
+    pub fn lookup_entry(&self, ip: IpAddr) -> Option<MatchedEntry> {
+        let block = locate(self.bindata, &self.meta, ip, &mut ()).ok()??;
+        let db_type = self.meta.db_type;
+        Some(MatchedEntry {
+            start: bytes_to_ip(&block.start_ip, db_type),
+            end: bytes_to_ip(&block.end_ip, db_type),
+            region: self.meta.filter_miss(decode_region_from_bytes(
+                region_bytes(self.bindata, &block)?,
+                &self.meta,
+                RecordSite::lookup(ip, &block),
+            ))?,
+            db_type,
+        })
+    }
+
+    /// Number of IP ranges (index blocks) in the database.
+    ///
+    /// 数据库中 IP 段（索引块）的数量。
+    pub fn entry_count(&self) -> usize {
+        self.meta.entry_count()
+    }
+
+    /// The IP version this database answers for.
+    ///
+    /// 数据库支持查询的 IP 版本。
+    pub fn supported_version(&self) -> DbType {
+        self.meta.db_type
+    }
+
+    /// Format version, client id and expiry date read from the hyper header.
+    ///
+    /// 从超头读取的格式版本、客户端 ID 与到期日期。
+    pub fn info(&self) -> DbInfo {
+        self.meta.info()
+    }
+
+    /// Whether the database was past its expiry date when parsed.
+    ///
+    /// Only ever true when parsed with `CzdbOptions::allow_expired`.
+    ///
+    /// 解析时数据库是否已过期；仅在使用 `CzdbOptions::allow_expired` 解析时可能为 true。
+    pub fn is_expired(&self) -> bool {
+        self.meta.expired
+    }
+
+    /// Returns the storage backend of this searcher.
+    ///
+    /// 返回该查询器的存储模式。
+    pub fn backend_kind(&self) -> BackendKind {
+        BackendKind::Borrowed
+    }
+}
+
+impl Searcher for CzdbBorrowed<'_> {
+    fn lookup(&self, ip: IpAddr) -> Option<String> {
+        self.search(ip)
+    }
+
+    fn db_type(&self) -> DbType {
+        self.meta.db_type
+    }
+
+    fn backend_kind(&self) -> BackendKind {
+        BackendKind::Borrowed
+    }
+}
+
+/// Parse the hyper header and metadata of a whole database file held in memory.
+///
+/// Returns the metadata and the offset of the data section within `data`. With
+/// `CzdbOptions::strict` the index ordering is checked as well.
+pub(crate) fn load_meta(
+    data: &[u8],
+    key_bytes: &[u8],
+    options: CzdbOptions,
+) -> Result<(DbMeta, usize), CzError> {
+    let header = read_hyper_header(
+        &mut Cursor::new(data),
+        data.len() as u64,
+        key_bytes,
+        &options,
+    )?;
+    let data_offset = header.data_offset();
+    if data_offset >= data.len() as u64 {
+        return Err(CzError::NoDataSection);
+    }
+    let data_offset = data_offset as usize;
+    let bindata = &data[data_offset..];
+    let meta = parse_meta_from_bytes(bindata, data.len() as u64, &header, key_bytes, options)?;
+    if meta.options.strict {
+        let index = bindata
+            .get(meta.index_range())
+            .ok_or(CzError::DatabaseFileCorrupted)?;
+        check_index_order(index, meta.db_type)?;
+    }
+    Ok((meta, data_offset))
+}
+
+/// Search `ip` in the data section `bindata`, reporting corrupt records as errors.
+pub(crate) fn try_search(
+    bindata: &[u8],
+    meta: &DbMeta,
+    ip: IpAddr,
+) -> Result<Option<String>, CzError> {
+    if let Some(region) = meta.cached_region(ip) {
+        return Ok(Some(region));
+    }
+    let block = locate(bindata, meta, ip, &mut ()).map_err(|e| report_lookup_error(ip, None, e))?;
+    let region = match block {
+        Some(block) if block.data_ptr != 0 && block.data_len != 0 => {
+            let region_bytes = region_bytes(bindata, &block).ok_or_else(|| {
+                report_lookup_error(ip, Some(&block), CzError::DatabaseFileCorrupted)
+            })?;
+            let site = RecordSite::lookup(ip, &block);
+            let region = decode_region_at(region_bytes, meta, meta.column_selection, site)?;
+            meta.filter_miss(Some(region))
+        }
+        _ => None,
+    };
+    meta.cache_region(ip, &region);
+    Ok(region)
+}
+
+/// Find the index block covering `ip` in the data section `bindata`.
+///
+/// Index bytes lying past the end of `bindata` yield `DatabaseFileCorrupted`.
+pub(crate) fn locate(
+    bindata: &[u8],
+    meta: &DbMeta,
+    ip: IpAddr,
+    iterations: &mut impl Iterations,
+) -> Result<Option<IndexBlock>, CzError> {
+    let ip = meta.normalize_ip(ip);
+    if !meta.db_type.compare(&ip) {
+        return Ok(None);
+    }
+    let ip_bytes = ip_to_bytes(ip);
+
+    let Some((sptr, eptr)) = meta.search_in_header(&ip_bytes, iterations) else {
+        return Ok(None);
+    };
+    let sptr = sptr as usize;
+    // The last partition is reported one block past its end; keep reads inside the index.
+    let eptr = (eptr as usize).min(meta.end_index as usize).max(sptr);
+
+    let blen = meta.db_type.index_block_len();
+    let max_len = eptr.saturating_add(blen);
+    if max_len > bindata.len() {
+        return Err(CzError::DatabaseFileCorrupted);
+    }
+
+    let index = &bindata[sptr..max_len];
+    let db_type = meta.db_type;
+    Ok(find_index_block(index, &ip_bytes, db_type, iterations)
+        .map(|p| IndexBlock::read(index, p, db_type)))
+}
+
+/// Region bytes referenced by `block`, or `None` if they lie past the end of `bindata`.
+pub(crate) fn region_bytes<'a>(bindata: &'a [u8], block: &IndexBlock) -> Option<&'a [u8]> {
+    bindata.get(block.data_ptr..block.data_ptr.checked_add(block.data_len)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::{FixtureBuilder, test_key};
+
+    #[test]
+    fn borrowed_bytes_search_like_a_file() {
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.255.255.255", "beijing")
+            .block("3.0.0.0", "3.255.255.255", "shanghai")
+            .build();
+        let db = CzdbBorrowed::from_bytes(&bytes, &test_key()).unwrap();
+
+        assert_eq!(db.entry_count(), 2);
+        assert_eq!(
+            db.search("1.2.3.4".parse().unwrap()).as_deref(),
+            Some("beijing")
+        );
+        assert_eq!(db.search("2.2.3.4".parse().unwrap()), None);
+        let region = db.search_ref("3.0.0.1".parse().unwrap()).unwrap();
+        assert!(matches!(region, Cow::Borrowed("shanghai")));
+        assert_eq!(
+            db.lookup_entry("3.0.0.1".parse().unwrap()).unwrap().end,
+            "3.255.255.255".parse::<IpAddr>().unwrap()
+        );
+
+        assert!(matches!(
+            CzdbBorrowed::from_bytes(&bytes[..bytes.len() - 1], &test_key()),
+            Err(CzError::DatabaseFileCorrupted)
+        ));
+    }
+}
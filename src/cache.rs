@@ -0,0 +1,44 @@
+use std::{fmt::Debug, net::IpAddr};
+
+/// Key of a cached lookup: the fingerprint of the database and the queried address.
+///
+/// The fingerprint is derived from the header metadata, layout, header index and a hash of
+/// the whole data section, so searchers on different database files use different keys while
+/// every backend opened on the same file shares them. The data section is hashed once, when
+/// a searcher with a `ResultCache` attached is opened.
+///
+/// 缓存查询的键：数据库指纹与查询地址。指纹由头部元数据、布局、头部索引及整个数据区的哈希计算得出，
+/// 因此不同数据库文件的查询器使用不同的键，而同一文件上打开的各后端共享相同的键。
+/// 数据区仅在打开挂载了 `ResultCache` 的查询器时哈希一次。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    /// Fingerprint of the database that produced the result.
+    ///
+    /// 产生该结果的数据库指纹。
+    pub fingerprint: u64,
+    /// Queried address.
+    ///
+    /// 查询的地址。
+    pub ip: IpAddr,
+}
+
+/// Query result cache shared by any number of searchers.
+///
+/// Attach one with `CzdbOptions::result_cache`; `search` then consults it before running
+/// the lookup and stores the regions it finds. Misses are not cached. Searchers sharing a
+/// cache should use the same decoding options, since those are not part of the key.
+///
+/// 可由多个查询器共享的查询结果缓存。通过 `CzdbOptions::result_cache` 挂载后，
+/// `search` 会先查询缓存，并写入命中的区域；未命中结果不缓存。
+/// 解析选项不属于缓存键，共享缓存的查询器应使用相同的解析选项。
+pub trait ResultCache: Debug + Send + Sync {
+    /// Cached region for `key`, if any.
+    ///
+    /// 返回 `key` 对应的缓存区域（若存在）。
+    fn get(&self, key: &CacheKey) -> Option<String>;
+
+    /// Store `region` for `key`.
+    ///
+    /// 为 `key` 写入 `region`。
+    fn put(&self, key: CacheKey, region: String);
+}
@@ -0,0 +1,37 @@
+use chrono::NaiveDate;
+use std::fmt::Debug;
+
+/// Source of the current date used by the expiry check.
+///
+/// Implement this to derive "today" from a trusted time source instead of the host clock,
+/// or to pin the date in tests. Register it with `CzdbOptions::clock`.
+///
+/// 过期校验所用的当前日期来源。可实现该 trait 以从可信时间源而非主机时钟获取日期，
+/// 或在测试中固定日期；通过 `CzdbOptions::clock` 注册。
+pub trait Clock: Debug + Send + Sync {
+    /// The current date.
+    ///
+    /// 当前日期。
+    fn today(&self) -> NaiveDate;
+}
+
+/// Clock reading the host's local date. Used when no clock is configured.
+///
+/// 读取主机本地日期的时钟，未配置时钟时默认使用。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn today(&self) -> NaiveDate {
+        chrono::Local::now().date_naive()
+    }
+}
+
+/// A fixed date acts as a clock that always reports that date.
+///
+/// 固定日期可作为始终返回该日期的时钟。
+impl Clock for NaiveDate {
+    fn today(&self) -> NaiveDate {
+        *self
+    }
+}
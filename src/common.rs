@@ -4,65 +4,498 @@ use aes::{
 };
 use base64::{Engine, engine::general_purpose};
 use byteorder::{LittleEndian, ReadBytesExt};
+use chrono::NaiveDate;
 use cipher::{BlockDecryptMut, block_padding::Pkcs7};
-use rmpv::{Value, decode::read_value};
+use rmpv::{Utf8String, Value, decode::read_value};
 use std::{
+    borrow::Cow,
     cmp::Ordering,
-    io::{Cursor, Read, Seek, SeekFrom},
-    net::IpAddr,
+    fmt,
+    fs::File,
+    hash::{DefaultHasher, Hasher},
+    io::{self, Cursor, Read, Seek, SeekFrom, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    sync::{
+        OnceLock,
+        atomic::{AtomicUsize, Ordering as AtomicOrdering},
+    },
 };
 
-use crate::CzError;
-
-const SUPER_PART_LENGTH: usize = 17;
+pub use crate::section::{
+    BoundaryKind, DbType, IndexBlock, Iterations, bytes_to_ip, compare_bytes, find_index_block,
+    ip_to_bytes, split_geo_pos_mix,
+};
+use crate::{
+    CacheKey, CzError, CzdbOptions,
+    decoder::{DefaultRegionDecoder, RegionDecoder},
+    section::{self, SUPER_PART_LENGTH},
+};
 const HEADER_BLOCK_LENGTH: usize = 20;
 
-/// Database IP version type.
+/// Storage backend used by a searcher.
+///
+/// New backends may be added in minor releases, so matches need a wildcard arm.
+///
+/// 查询器使用的存储模式。后续小版本可能新增后端，匹配时需保留通配分支。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[non_exhaustive]
+pub enum BackendKind {
+    /// `CzdbMemory`: prebuilt in-memory index.
+    ///
+    /// `CzdbMemory`：预构建的内存索引。
+    Memory,
+    /// `CzdbMmap`: memory-mapped file.
+    ///
+    /// `CzdbMmap`：内存映射文件。
+    Mmap,
+    /// `CzdbDisk`: reads from the file per query.
+    ///
+    /// `CzdbDisk`：每次查询从文件读取。
+    Disk,
+    /// `CzdbBorrowed`: searches bytes borrowed from the caller.
+    ///
+    /// `CzdbBorrowed`：在调用方借出的字节上查询。
+    Borrowed,
+}
+
+/// Lookup interface shared by every backend, for code generic over the storage mode.
+///
+/// Implemented by `CzdbDisk`, `CzdbMemory`, `CzdbMmap` and `CzdbBorrowed`, so a
+/// `Box<dyn Searcher>` can hold whichever backend the configuration selects.
+///
+/// 各存储模式共用的查询接口，便于编写与存储模式无关的代码。`CzdbDisk`、`CzdbMemory`、
+/// `CzdbMmap` 与 `CzdbBorrowed` 均实现了该 trait，因此可用 `Box<dyn Searcher>` 按配置切换后端。
+pub trait Searcher {
+    /// Look up the region for `ip`; equivalent to the backend's `search`.
+    ///
+    /// 查询 `ip` 的区域，等价于各后端的 `search`。
+    fn lookup(&self, ip: IpAddr) -> Option<String>;
+
+    /// IP version of the database.
+    ///
+    /// 数据库的 IP 版本。
+    fn db_type(&self) -> DbType;
+
+    /// Storage backend answering the lookups.
+    ///
+    /// 响应查询的存储模式。
+    fn backend_kind(&self) -> BackendKind;
+}
+
+/// Part of a region record that failed to decode.
 ///
-/// 数据库 IP 版本类型。
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum DbType {
-    Ipv4,
-    Ipv6,
+/// 区域记录中解析失败的部分。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum RegionErrorKind {
+    /// The leading geo-position integer is missing, so the bytes are likely not a region.
+    ///
+    /// 缺少开头的地理位置整数，数据很可能不是区域记录。
+    #[error("missing geo position; not a region record")]
+    MissingGeoPosition,
+    /// The geo position was read but the `other_data` string is truncated or malformed.
+    ///
+    /// 已读取地理位置，但 `other_data` 字符串被截断或格式错误。
+    #[error("truncated or malformed other_data")]
+    MalformedOtherData,
+    /// The geo-map reference is out of range or does not point at a column array.
+    ///
+    /// 地理映射引用越界或未指向列数组。
+    #[error("invalid geo-map column reference")]
+    InvalidGeoColumns,
 }
 
-impl DbType {
-    /// Returns true if the IP matches this database type.
+/// Why a key could not be loaded from the environment or a file.
+///
+/// 无法从环境变量或文件加载密钥的原因。
+#[derive(Debug, thiserror::Error)]
+pub enum KeySourceErrorKind {
+    /// The environment variable is not set or is not valid Unicode.
+    ///
+    /// 环境变量未设置或不是有效的 Unicode。
+    #[error("environment variable {name} is not set or not valid unicode")]
+    MissingEnvVar { name: String },
+    /// The key file could not be read.
     ///
-    /// 判断给定 IP 是否与数据库类型一致。
-    pub fn compare(&self, ip: &IpAddr) -> bool {
-        match self {
-            DbType::Ipv4 => ip.is_ipv4(),
-            DbType::Ipv6 => ip.is_ipv6(),
+    /// 无法读取密钥文件。
+    #[error("cannot read key file {path}: {source}")]
+    UnreadableFile { path: String, source: io::Error },
+}
+
+/// Read a base64 key from the environment variable `name`.
+///
+/// 从环境变量 `name` 读取 base64 密钥。
+pub fn key_from_env(name: &str) -> Result<String, CzError> {
+    key_from_env_with(name, |name| std::env::var(name).ok())
+}
+
+/// `key_from_env` with the variable looked up through `lookup` instead of the process
+/// environment, so tests need not mutate it.
+///
+/// 通过 `lookup` 而非进程环境查找变量的 `key_from_env`，测试时无需修改环境变量。
+pub(crate) fn key_from_env_with(
+    name: &str,
+    lookup: impl FnOnce(&str) -> Option<String>,
+) -> Result<String, CzError> {
+    lookup(name).ok_or_else(|| {
+        CzError::KeySourceError(KeySourceErrorKind::MissingEnvVar {
+            name: name.to_string(),
+        })
+    })
+}
+
+/// Read a base64 key from the file at `path`.
+///
+/// 从 `path` 处的文件读取 base64 密钥。
+pub fn key_from_file(path: &str) -> Result<String, CzError> {
+    std::fs::read_to_string(path).map_err(|source| {
+        CzError::KeySourceError(KeySourceErrorKind::UnreadableFile {
+            path: path.to_string(),
+            source,
+        })
+    })
+}
+
+/// Open a database with the first of `keys` for which `open` succeeds.
+///
+/// Each candidate goes through the full `open`, so a key only fits if the whole header
+/// parses. `DecryptionError`, `InvalidClientId` and short reads are what a wrong key
+/// produces and move on to the next key silently. Other parse errors may also come from a
+/// corrupt file, so they move on too but are kept. `DatabaseExpired`, which means the key
+/// fitted, other read errors, which no key can fix, and malformed keys are returned as they
+/// occur. If no key fits, returns `NoMatchingKey` with the number of keys tried and the
+/// last kept error, or `DecryptionError` when there is none.
+///
+/// 使用 `keys` 中第一个能让 `open` 成功的密钥打开数据库。每个候选密钥都会完整执行 `open`，
+/// 只有整个头部解析成功才视为适用。`DecryptionError`、`InvalidClientId` 与读取长度不足
+/// 是错误密钥的典型结果，直接尝试下一个密钥；其他解析错误也可能源于文件损坏，同样尝试下一个
+/// 密钥但会被保留。`DatabaseExpired`（表示密钥适用）、其他读取错误（更换密钥也无法解决）
+/// 以及格式错误的密钥会直接返回。全部不适用时返回 `NoMatchingKey`，其中包含尝试的密钥数量
+/// 与最后保留的错误，没有保留的错误时为 `DecryptionError`。
+pub(crate) fn open_with_first_key<T>(
+    keys: &[&str],
+    mut open: impl FnMut(&str) -> Result<T, CzError>,
+) -> Result<T, CzError> {
+    let mut last = None;
+    for key in keys {
+        decode_aes_key(key)?;
+        match open(key) {
+            Ok(db) => return Ok(db),
+            Err(CzError::DecryptionError | CzError::InvalidClientId) => {}
+            Err(CzError::DatabaseFileReadError(err))
+                if err.kind() == io::ErrorKind::UnexpectedEof => {}
+            Err(err @ (CzError::DatabaseFileReadError(_) | CzError::DatabaseExpired)) => {
+                return Err(err);
+            }
+            Err(err) => last = Some(err),
         }
     }
+    Err(CzError::NoMatchingKey {
+        tried: keys.len(),
+        last: Box::new(last.unwrap_or(CzError::DecryptionError)),
+    })
+}
+
+/// Outcome of a lookup, distinguishing the cases `search` folds into `None`.
+///
+/// 查询结果，区分 `search` 统一返回 `None` 的各种情况。
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum QueryResult {
+    /// The IP's version differs from the database's.
+    ///
+    /// IP 版本与数据库类型不一致。
+    WrongVersion,
+    /// No block covers the IP.
+    ///
+    /// 没有索引块覆盖该 IP。
+    NotCovered,
+    /// A block covers the IP but has no usable region: it is blank, cannot be decoded, or is
+    /// listed in `CzdbOptions::treat_as_miss`. Every backend reports these cases alike.
+    ///
+    /// 有索引块覆盖该 IP，但区域为空白、无法解析或列于 `CzdbOptions::treat_as_miss` 中。
+    /// 所有后端对这些情况的报告一致。
+    CoveredEmpty,
+    /// The decoded region.
+    ///
+    /// 解析后的区域。
+    Found(String),
+}
 
-    /// Returns the index block length for this database type.
+impl QueryResult {
+    /// Classify a decoded region for a covering block.
     ///
-    /// 返回该数据库类型的索引块长度。
-    pub fn index_block_len(&self) -> usize {
-        match self {
-            DbType::Ipv4 => 13,
-            DbType::Ipv6 => 37,
+    /// 对覆盖块解析出的区域进行分类。
+    pub(crate) fn covered(region: Option<String>) -> Self {
+        match region {
+            Some(region) if !region.trim().is_empty() => QueryResult::Found(region),
+            _ => QueryResult::CoveredEmpty,
         }
     }
+}
 
-    /// Returns the IP byte length for this database type.
+/// A decoded region split into named fields.
+///
+/// Each selected geo-map column is placed by its CZDB column index: columns 0, 1 and 2 fill
+/// `country`, `province` and `city`, and any other selected column goes to `columns` under
+/// its name from `selected_columns`. Fields whose column is not selected stay `None`. The
+/// inline `other_data` becomes `isp`. Records stored inline, without geo-map columns, only
+/// fill `raw`. `raw` is always the string `search` returns, and is what `Display` prints.
+///
+/// 拆分为具名字段的区域。每个选中的地理映射列按其 CZDB 列序号归位：第 0、1、2 列分别填入
+/// `country`、`province`、`city`，其余选中列以 `selected_columns` 中的名称放入 `columns`；
+/// 未选中的列对应字段为 `None`。内联的 `other_data` 作为 `isp`。不含地理映射列的内联记录仅填充
+/// `raw`。`raw` 始终等于 `search` 返回的字符串，也是 `Display` 输出的内容。
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Region {
+    /// Geo-map column 0.
     ///
-    /// 返回该数据库类型的 IP 字节长度。
-    pub fn bytes_len(&self) -> usize {
-        match self {
-            DbType::Ipv4 => 4,
-            DbType::Ipv6 => 16,
+    /// 地理映射第 0 列。
+    pub country: Option<String>,
+    /// Geo-map column 1.
+    ///
+    /// 地理映射第 1 列。
+    pub province: Option<String>,
+    /// Geo-map column 2.
+    ///
+    /// 地理映射第 2 列。
+    pub city: Option<String>,
+    /// The record's `other_data`, when it follows geo-map columns and is not empty.
+    ///
+    /// 记录的 `other_data`（跟随地理映射列且非空时）。
+    pub isp: Option<String>,
+    /// Further selected columns as `(name, value)` pairs.
+    ///
+    /// 其余选中列，形如 `(名称, 值)`。
+    pub columns: Vec<(String, String)>,
+    /// The full region string, as returned by `search`.
+    ///
+    /// 完整的区域字符串，与 `search` 的返回值相同。
+    pub raw: String,
+}
+
+impl Region {
+    /// Split a joined region string whose first fields are the geo-map `columns`.
+    ///
+    /// 拆分前若干字段为地理映射列 `columns` 的拼接区域字符串。
+    pub(crate) fn from_joined(raw: String, columns: &[usize]) -> Self {
+        let mut region = Region::default();
+        if !columns.is_empty() {
+            let mut fields = raw.splitn(columns.len() + 1, '\t');
+            for &column in columns {
+                let Some(value) = fields.next() else {
+                    break;
+                };
+                let value = value.to_string();
+                match column {
+                    0 => region.country = Some(value),
+                    1 => region.province = Some(value),
+                    2 => region.city = Some(value),
+                    _ => region
+                        .columns
+                        .push((geo_column_name(column).to_string(), value)),
+                }
+            }
+            region.isp = fields.next().filter(|s| !s.is_empty()).map(str::to_string);
         }
+        region.raw = raw;
+        region
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+/// Names of the geo-map columns in CZDB order; positions past the end are `"unknown"`.
+const GEO_COLUMN_NAMES: [&str; 5] = ["country", "province", "city", "district", "isp"];
+
+/// Name of geo-map column `column`, or `"unknown"` past the known columns.
+fn geo_column_name(column: usize) -> &'static str {
+    GEO_COLUMN_NAMES.get(column).copied().unwrap_or("unknown")
+}
+
+/// Geo-map column positions selected by `column_selection`, in order.
+///
+/// 按顺序返回 `column_selection` 选中的地理映射列位置。
+pub fn selected_column_positions(column_selection: u64) -> Vec<usize> {
+    // Bit 0 is reserved; column `n` is selected by bit `n + 1`.
+    (0..63)
+        .filter(|n| (column_selection >> (n + 1)) & 1 == 1)
+        .collect()
+}
+
+/// A matched index entry: its address range, decoded region, and database type.
+///
+/// `start` and `end` are rebuilt from the index octets, so their `Display` output is the
+/// canonical form: RFC 5952 compressed lower case for IPv6.
+///
+/// 命中的索引条目：地址范围、解析后的区域及数据库类型。`start` 与 `end` 由索引字节重建，
+/// 其 `Display` 输出为规范形式，IPv6 为 RFC 5952 规定的小写压缩格式。
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MatchedEntry {
+    /// First address of the matched range.
+    ///
+    /// 命中范围的起始地址。
+    pub start: IpAddr,
+    /// Last address of the matched range (inclusive).
+    ///
+    /// 命中范围的结束地址（包含）。
+    pub end: IpAddr,
+    /// Decoded region string.
+    ///
+    /// 解析后的区域字符串。
+    pub region: String,
+    /// Type of the database that produced the match.
+    ///
+    /// 产生该结果的数据库类型。
+    pub db_type: DbType,
+}
+
+impl MatchedEntry {
+    /// Prefix length of the smallest CIDR network containing the matched range.
+    ///
+    /// See `covering_prefix_len`.
+    ///
+    /// 包含命中范围的最小 CIDR 网络的前缀长度，参见 `covering_prefix_len`。
+    pub fn prefix_len(&self) -> u8 {
+        covering_prefix_len(self.start, self.end)
+    }
+}
+
+/// Prefix length of the smallest CIDR network containing `[start, end]`.
+///
+/// This is the number of leading bits the two boundaries share. A CIDR-aligned range
+/// yields its exact prefix; any other range yields the prefix of the network that
+/// contains it, which also covers addresses outside the range. Mixed address families
+/// yield 0.
+///
+/// 包含 `[start, end]` 的最小 CIDR 网络的前缀长度，即两端地址相同的前导位数。
+/// 与 CIDR 对齐的范围返回其精确前缀；其它范围返回包含它的网络前缀，该网络也覆盖范围之外的地址。
+/// 地址族不一致时返回 0。
+pub fn covering_prefix_len(start: IpAddr, end: IpAddr) -> u8 {
+    match (start, end) {
+        (IpAddr::V4(s), IpAddr::V4(e)) => (ipv4_to_u32(s) ^ ipv4_to_u32(e)).leading_zeros() as u8,
+        (IpAddr::V6(s), IpAddr::V6(e)) => (u128::from(s) ^ u128::from(e)).leading_zeros() as u8,
+        _ => 0,
+    }
+}
+
+/// Returns false for addresses no public geolocation database covers.
+///
+/// This excludes unspecified, loopback, private, shared (100.64.0.0/10), link-local,
+/// broadcast, documentation, benchmarking (198.18.0.0/15), reserved (240.0.0.0/4) and
+/// multicast IPv4 addresses, and unspecified, loopback, unique-local, link-local,
+/// documentation (2001:db8::/32) and multicast IPv6 addresses. IPv4-mapped IPv6
+/// addresses are judged by their IPv4 address.
+///
+/// 对任何公共地理位置数据库都不会收录的地址返回 false。排除未指定、环回、私有、
+/// 共享（100.64.0.0/10）、链路本地、广播、文档、基准测试（198.18.0.0/15）、
+/// 保留（240.0.0.0/4）及组播 IPv4 地址，以及未指定、环回、唯一本地、链路本地、
+/// 文档（2001:db8::/32）及组播 IPv6 地址。IPv4 映射的 IPv6 地址按其 IPv4 地址判断。
+pub fn is_geolocatable(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            !(v4.is_unspecified()
+                || v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_broadcast()
+                || v4.is_documentation()
+                || v4.is_multicast()
+                || (a == 100 && b & 0xc0 == 64)
+                || (a == 198 && b & 0xfe == 18)
+                || a >= 240)
+        }
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_geolocatable(IpAddr::V4(v4)),
+            None => {
+                let segments = v6.segments();
+                !(v6.is_unspecified()
+                    || v6.is_loopback()
+                    || v6.is_unique_local()
+                    || v6.is_unicast_link_local()
+                    || v6.is_multicast()
+                    || (segments[0] == 0x2001 && segments[1] == 0x0db8))
+            }
+        },
+    }
+}
+
+/// Check that the index blocks in `index` are well formed and strictly ascending: each
+/// block has `start <= end` and starts after the previous block ends.
+///
+/// 校验 `index` 中的索引块格式正确且严格递增：每个块满足 `start <= end`，
+/// 且起始地址大于前一个块的结束地址。
+pub fn check_index_order(index: &[u8], db_type: DbType) -> Result<(), CzError> {
+    let ip_len = db_type.bytes_len();
+    let blen = db_type.index_block_len();
+    if !index.len().is_multiple_of(blen) {
+        return Err(CzError::DatabaseFileCorrupted);
+    }
+    let mut previous_end: Option<&[u8]> = None;
+    for block in index.chunks_exact(blen) {
+        let (start, end) = (&block[..ip_len], &block[ip_len..ip_len * 2]);
+        if compare_bytes(start, end, ip_len) == Ordering::Greater
+            || previous_end.is_some_and(|prev| compare_bytes(prev, start, ip_len) != Ordering::Less)
+        {
+            return Err(CzError::DatabaseFileCorrupted);
+        }
+        previous_end = Some(end);
+    }
+    Ok(())
+}
+
+/// Convert an IPv4 address to the big-endian `u32` stored in memory index entries.
+///
+/// 将 IPv4 地址转换为内存索引条目中存储的大端 `u32`。
+pub fn ipv4_to_u32(ip: Ipv4Addr) -> u32 {
+    u32::from_be_bytes(ip.octets())
+}
+
+/// Convert a stored big-endian `u32` back to an IPv4 address; inverse of `ipv4_to_u32`.
+///
+/// 将存储的大端 `u32` 还原为 IPv4 地址，是 `ipv4_to_u32` 的逆运算。
+pub(crate) fn u32_to_ipv4(n: u32) -> Ipv4Addr {
+    Ipv4Addr::from(n.to_be_bytes())
+}
+
+/// Convert stored IPv6 bytes (network order) back to an IPv6 address.
+///
+/// 将存储的 IPv6 字节（网络字节序）还原为 IPv6 地址。
+pub(crate) fn bytes_to_ipv6(bytes: [u8; 16]) -> Ipv6Addr {
+    Ipv6Addr::from(bytes)
+}
+
+/// Convert a numeric address of `db_type` (IPv4 in the low 32 bits) to an `IpAddr`.
+///
+/// 将 `db_type` 类型的数值地址（IPv4 位于低 32 位）转换为 `IpAddr`。
+pub fn numeric_to_ip(n: u128, db_type: DbType) -> IpAddr {
+    match db_type {
+        DbType::Ipv4 => IpAddr::V4(u32_to_ipv4(n as u32)),
+        DbType::Ipv6 => IpAddr::V6(bytes_to_ipv6(n.to_be_bytes())),
     }
 }
 
-#[derive(Debug)]
 struct GeoDataDecryptor {
     key_bytes: Vec<u8>,
 }
 
+impl fmt::Debug for GeoDataDecryptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GeoDataDecryptor")
+            .field("key_bytes", &"<redacted>")
+            .finish()
+    }
+}
+
 impl GeoDataDecryptor {
     fn new(key_bytes: Vec<u8>) -> Result<Self, CzError> {
         if key_bytes.is_empty() {
@@ -80,6 +513,44 @@ impl GeoDataDecryptor {
     }
 }
 
+/// Geo-map data, decrypted either at open or on first use.
+///
+/// 地理映射数据，在打开时或首次使用时解密。
+#[derive(Debug)]
+pub struct GeoMap {
+    encrypted: Vec<u8>,
+    decryptor: GeoDataDecryptor,
+    decrypted: OnceLock<Vec<u8>>,
+}
+
+impl GeoMap {
+    fn new(encrypted: Vec<u8>, key_bytes: &[u8], lazy: bool) -> Result<Self, CzError> {
+        let decryptor = GeoDataDecryptor::new(key_bytes.to_vec())?;
+        let geo_map = if lazy {
+            Self {
+                encrypted,
+                decryptor,
+                decrypted: OnceLock::new(),
+            }
+        } else {
+            Self {
+                decrypted: OnceLock::from(decryptor.decrypt(&encrypted)),
+                encrypted: Vec::new(),
+                decryptor,
+            }
+        };
+        Ok(geo_map)
+    }
+
+    /// Returns the decrypted geo-map bytes, decrypting them on first access.
+    ///
+    /// 返回解密后的地理映射数据，首次访问时解密。
+    pub fn data(&self) -> &[u8] {
+        self.decrypted
+            .get_or_init(|| self.decryptor.decrypt(&self.encrypted))
+    }
+}
+
 /// Parsed metadata used for searching.
 ///
 /// 查询所需的解析元数据。
@@ -88,74 +559,185 @@ pub struct DbMeta {
     pub db_type: DbType,
     pub header_sip: Vec<[u8; 16]>,
     pub header_ptr: Vec<u32>,
+    /// Geo-map column mask. Bit `n + 1` selects column `n`; bit 0 is reserved and never
+    /// selects a column. It only makes the mask nonzero, which marks that a geo map follows.
+    ///
+    /// 地理映射列掩码。第 `n + 1` 位选择第 `n` 列；第 0 位保留，不选择任何列，
+    /// 仅用于使掩码非零以表明其后存在地理映射数据。
     pub column_selection: u64,
-    pub geo_map_data: Option<Vec<u8>>,
+    pub geo_map_data: Option<GeoMap>,
     pub start_index: u32,
     pub end_index: u32,
+    pub options: CzdbOptions,
+    pub invalid_utf8: AtomicUsize,
+    pub padding_size: u32,
+    pub encrypted_block_size: u32,
+    pub file_size: u32,
+    pub label: Option<String>,
+    pub fingerprint: u64,
+    pub info: DbInfo,
+    pub expired: bool,
+}
+
+/// Where a searcher was opened from, kept so it can be reloaded in place.
+///
+/// 记录查询器的打开来源，用于原地重新加载。
+#[derive(Clone)]
+pub struct Source {
+    pub path: String,
+    pub key: Vec<u8>,
+}
+
+/// Searchers derive `Debug`; keep the decryption key out of their output.
+impl fmt::Debug for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Source")
+            .field("path", &self.path)
+            .field("key", &"<redacted>")
+            .finish()
+    }
 }
 
 /// Hyper header info parsed from the encrypted header block.
 ///
 /// 从加密头部解析出来的超头信息。
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct HyperHeaderInfo {
     pub padding_size: u32,
     pub encrypted_block_size: u32,
+    pub info: DbInfo,
+    pub expired: bool,
+}
+
+impl HyperHeaderInfo {
+    /// Offset of the data section: the plain header, the encrypted block and the padding.
+    ///
+    /// 数据区的偏移：明文头、加密块与填充之后。
+    pub fn data_offset(&self) -> u64 {
+        12 + self.padding_size as u64 + self.encrypted_block_size as u64
+    }
+}
+
+/// Database metadata from the hyper header.
+///
+/// 超头中的数据库元数据。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DbInfo {
+    /// Header format version.
+    ///
+    /// 头部格式版本。
+    pub version: u32,
+    /// Client id the database was issued to.
+    ///
+    /// 数据库所属的客户端 ID。
+    pub client_id: u32,
+    /// Expiry date embedded in the header, or `None` if it is not a valid date.
+    ///
+    /// The database is rejected as expired after this day.
+    ///
+    /// 头部内嵌的到期日期，不是有效日期时为 `None`。超过该日期后数据库会被判定为过期。
+    pub expiry_date: Option<NaiveDate>,
+}
+
+/// Parse a `yymmdd` header date as a day in the 2000s.
+fn parse_header_date(yymmdd: u32) -> Option<NaiveDate> {
+    NaiveDate::from_ymd_opt(
+        2000 + (yymmdd / 10000) as i32,
+        (yymmdd / 100) % 100,
+        yymmdd % 100,
+    )
+}
+
+/// Check that `key` is a usable database key without opening a database.
+///
+/// Surrounding whitespace is ignored, as it is by every `open` function. Returns
+/// `KeyDecodingError` if the key is not valid base64 and `InvalidAesKeyLength` if it
+/// does not decode to 16 bytes.
+///
+/// 在不打开数据库的情况下校验密钥。与各 `open` 函数一致，忽略首尾空白；
+/// 非法 base64 返回 `KeyDecodingError`，解码后不是 16 字节返回 `InvalidAesKeyLength`。
+pub fn validate_key(key: &str) -> Result<(), CzError> {
+    decode_aes_key(key).map(|_| ())
+}
+
+/// Read only the IP version of a database file, without parsing its index.
+///
+/// Decrypts the hyper header to locate the data section, then reads the single type byte
+/// of the super part. Expired files are not rejected, since only the version is needed.
+///
+/// 仅读取数据库文件的 IP 版本而不解析索引。解密超头以定位数据区，随后只读取超级块中的类型字节；
+/// 由于只需要版本信息，过期文件不会被拒绝。
+pub fn peek_db_type(db_path: &str, key: &str) -> Result<DbType, CzError> {
+    let key_bytes = decode_aes_key(key)?;
+    let mut file = File::open(db_path)?;
+    let options = CzdbOptions::new().allow_expired(true);
+    let file_len = file.metadata()?.len();
+    let header = read_hyper_header(&mut file, file_len, &key_bytes, &options)?;
+    file.seek(SeekFrom::Start(header.data_offset()))?;
+    Ok(DbType::from_type_byte(file.read_u8()?))
 }
 
 /// Decode the base64 AES key and validate its length.
 ///
 /// 解码 base64 AES 密钥并校验长度。
 pub fn decode_aes_key(key: &str) -> Result<Vec<u8>, CzError> {
-    let key_bytes = general_purpose::STANDARD.decode(key)?;
-    if key_bytes.len() != 16 {
-        return Err(CzError::InvalidAesKeyLength(key_bytes.len()));
-    }
+    let key_bytes = general_purpose::STANDARD.decode(key.trim())?;
+    check_aes_key_len(&key_bytes)?;
     Ok(key_bytes)
 }
 
-/// Compare two byte slices for the first `length` bytes.
+/// Check that a raw AES key is 16 bytes long.
 ///
-/// 比较两个字节切片的前 `length` 字节。
-pub fn compare_bytes(a: &[u8], b: &[u8], length: usize) -> Ordering {
-    for i in 0..length {
-        if a[i] < b[i] {
-            return Ordering::Less;
-        } else if a[i] > b[i] {
-            return Ordering::Greater;
-        }
+/// 校验原始 AES 密钥长度为 16 字节。
+pub fn check_aes_key_len(key_bytes: &[u8]) -> Result<(), CzError> {
+    if key_bytes.len() != 16 {
+        return Err(CzError::InvalidAesKeyLength(key_bytes.len()));
     }
-    Ordering::Equal
+    Ok(())
 }
 
-/// Read and validate the encrypted hyper header.
+/// Read and validate the encrypted hyper header of a `file_len`-byte database.
+///
+/// An encrypted block longer than the rest of the file is reported as an unexpected end of
+/// file before anything is allocated for it.
 ///
-/// 读取并校验加密超头信息。
+/// 读取并校验长度为 `file_len` 字节的数据库的加密超头信息。加密块长度超出文件剩余部分时，
+/// 在为其分配内存之前即报告为意外的文件结尾。
 pub fn read_hyper_header<R: Read>(
     reader: &mut R,
+    file_len: u64,
     key_bytes: &[u8],
+    options: &CzdbOptions,
 ) -> Result<HyperHeaderInfo, CzError> {
-    let _version = reader.read_u32::<LittleEndian>()?;
-    let client_id = reader.read_u32::<LittleEndian>()?;
-    let encrypted_block_size = reader.read_u32::<LittleEndian>()?;
+    let mut plain_header = [0u8; 12];
+    reader.read_exact(&mut plain_header)?;
+    let mut fields = &plain_header[..];
+    let version = fields.read_u32::<LittleEndian>()?;
+    let client_id = fields.read_u32::<LittleEndian>()?;
+    let encrypted_block_size = fields.read_u32::<LittleEndian>()?;
+    if 12 + encrypted_block_size as u64 > file_len {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
 
     let mut encrypted_bytes = vec![0; encrypted_block_size as usize];
     reader.read_exact(&mut encrypted_bytes)?;
-    let cipher = Aes128::new(Key::<Aes128>::from_slice(key_bytes));
-    let mut decrypted_bytes = cipher
-        .decrypt_padded_mut::<Pkcs7>(&mut encrypted_bytes)
-        .map_err(|_| CzError::DecryptionError)?;
+    let decrypted = decrypt_hyper_block(version, &plain_header, &mut encrypted_bytes, key_bytes)?;
+    let mut decrypted_bytes = &decrypted[..];
 
     let first_u32 = decrypted_bytes.read_u32::<LittleEndian>()?;
     if first_u32 >> 20 != client_id {
         return Err(CzError::InvalidClientId);
     }
-    let now: u32 = chrono::Local::now()
+    let now: u32 = options
+        .clock_ref()
+        .today()
         .format("%y%m%d")
         .to_string()
         .parse()
         .map_err(|_| CzError::DatabaseFileCorrupted)?;
-    if now > first_u32 & 0xFFFFF {
+    let expired = now > first_u32 & 0xFFFFF;
+    if expired && !options.allow_expired {
         return Err(CzError::DatabaseExpired);
     }
 
@@ -163,37 +745,121 @@ pub fn read_hyper_header<R: Read>(
     Ok(HyperHeaderInfo {
         padding_size,
         encrypted_block_size,
+        info: DbInfo {
+            version,
+            client_id,
+            expiry_date: parse_header_date(first_u32 & 0xFFFFF),
+        },
+        expired,
     })
 }
 
+/// Header version marking an AES-128-GCM encrypted block.
+///
+/// Provisional: the published CZDB format defines no authenticated header, so this value and
+/// the layout below are this crate's own placeholder until the vendor specifies one, and may
+/// change. The block is laid out as a 12-byte nonce followed by the ciphertext and 16-byte
+/// tag, authenticated together with the 12-byte plain header.
+///
+/// 表示超头加密块使用 AES-128-GCM 的版本号。暂定值：已公开的 CZDB 格式并未定义认证超头，
+/// 在厂商给出规范前，该值及下述布局仅为本库的占位约定，今后可能变更。加密块由 12 字节
+/// nonce、密文及 16 字节标签组成，并与 12 字节明文头一同认证。
+pub(crate) const AES_GCM_HEADER_VERSION: u32 = 0x0100_0000;
+
+/// Decrypt the hyper header block, dispatching on the header version.
+fn decrypt_hyper_block<'a>(
+    version: u32,
+    plain_header: &[u8; 12],
+    encrypted: &'a mut [u8],
+    key_bytes: &[u8],
+) -> Result<Cow<'a, [u8]>, CzError> {
+    if version == AES_GCM_HEADER_VERSION {
+        return decrypt_gcm_block(plain_header, encrypted, key_bytes).map(Cow::Owned);
+    }
+    let cipher = Aes128::new(Key::<Aes128>::from_slice(key_bytes));
+    cipher
+        .decrypt_padded_mut::<Pkcs7>(encrypted)
+        .map(Cow::Borrowed)
+        .map_err(|_| CzError::DecryptionError)
+}
+
+#[cfg(feature = "aes-gcm")]
+fn decrypt_gcm_block(
+    plain_header: &[u8; 12],
+    encrypted: &[u8],
+    key_bytes: &[u8],
+) -> Result<Vec<u8>, CzError> {
+    use aes_gcm::{
+        Aes128Gcm, Nonce,
+        aead::{Aead, Payload},
+    };
+
+    if encrypted.len() < 12 {
+        return Err(CzError::DecryptionError);
+    }
+    let (nonce, ciphertext) = encrypted.split_at(12);
+    let cipher = Aes128Gcm::new(Key::<Aes128Gcm>::from_slice(key_bytes));
+    cipher
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: plain_header,
+            },
+        )
+        .map_err(|_| CzError::DecryptionError)
+}
+
+/// Without the `aes-gcm` feature, GCM headers cannot be decrypted.
+#[cfg(not(feature = "aes-gcm"))]
+fn decrypt_gcm_block(_: &[u8; 12], _: &[u8], _: &[u8]) -> Result<Vec<u8>, CzError> {
+    Err(CzError::DecryptionError)
+}
+
+/// Validate `total_header_block_size` and return the header block count.
+///
+/// The size must be a whole number of blocks and fit in the `available` bytes after the
+/// super part, so a corrupt value cannot trigger a huge allocation.
+///
+/// 校验 `total_header_block_size` 并返回头部块数量。大小必须是块长度的整数倍，
+/// 且不能超过超级块之后的 `available` 字节数，避免损坏的值导致超大内存分配。
+fn header_block_count(total_header_block_size: u32, available: u64) -> Result<u32, CzError> {
+    if !total_header_block_size.is_multiple_of(HEADER_BLOCK_LENGTH as u32)
+        || total_header_block_size as u64 > available
+    {
+        return Err(CzError::DatabaseFileCorrupted);
+    }
+    Ok(total_header_block_size / HEADER_BLOCK_LENGTH as u32)
+}
+
 /// Parse metadata from in-memory database bytes (after hyper header).
 ///
 /// 从内存数据库字节解析元数据（位于超头之后）。
 pub fn parse_meta_from_bytes(
     bindata: &[u8],
     file_size_total: u64,
-    padding_size: u32,
-    encrypted_block_size: u32,
+    header: &HyperHeaderInfo,
     key_bytes: &[u8],
+    options: CzdbOptions,
 ) -> Result<DbMeta, CzError> {
+    let HyperHeaderInfo {
+        padding_size,
+        encrypted_block_size,
+        info,
+        expired,
+    } = *header;
     let mut bindata_cursor = Cursor::new(bindata);
-    let db_type = if bindata_cursor.read_u8()? & 1 == 0 {
-        DbType::Ipv4
-    } else {
-        DbType::Ipv6
-    };
+    let db_type = DbType::from_type_byte(bindata_cursor.read_u8()?);
     let file_size = bindata_cursor.read_u32::<LittleEndian>()?;
-    if file_size_total != (padding_size + encrypted_block_size + 12 + file_size) as u64 {
+    if file_size_total != header.data_offset() + file_size as u64 {
         return Err(CzError::DatabaseFileCorrupted);
     }
     let start_index = bindata_cursor.read_u32::<LittleEndian>()?;
     let total_header_block_size = bindata_cursor.read_u32::<LittleEndian>()?;
     let end_index = bindata_cursor.read_u32::<LittleEndian>()?;
 
-    if total_header_block_size % HEADER_BLOCK_LENGTH as u32 != 0 {
-        return Err(CzError::DatabaseFileCorrupted);
-    }
-    let total_header_block = total_header_block_size / HEADER_BLOCK_LENGTH as u32;
+    let available = (bindata.len() as u64).saturating_sub(SUPER_PART_LENGTH as u64);
+    let total_header_block = header_block_count(total_header_block_size, available)?;
 
     let mut buffer = [0u8; HEADER_BLOCK_LENGTH];
     let mut header_sip = Vec::with_capacity(total_header_block as usize);
@@ -213,19 +879,27 @@ pub fn parse_meta_from_bytes(
         return Err(CzError::DatabaseFileCorrupted);
     }
 
-    let column_selection_ptr = end_index + db_type.index_block_len() as u32;
+    let column_selection_ptr = end_index
+        .checked_add(db_type.index_block_len() as u32)
+        .ok_or(CzError::DatabaseFileCorrupted)?;
     bindata_cursor.seek(SeekFrom::Start(column_selection_ptr as u64))?;
     let column_selection = bindata_cursor.read_u32::<LittleEndian>()? as u64;
     let mut geo_map_data = None;
     if column_selection != 0 {
         let geo_map_size = bindata_cursor.read_u32::<LittleEndian>()?;
+        let remaining = (bindata.len() as u64).saturating_sub(bindata_cursor.position());
+        if geo_map_size as u64 > remaining {
+            return Err(CzError::DatabaseFileCorrupted);
+        }
         let mut buffer = vec![0; geo_map_size as usize];
         bindata_cursor.read_exact(&mut buffer)?;
-        let decryptor = GeoDataDecryptor::new(key_bytes.to_vec())?;
-        let data = decryptor.decrypt(&buffer);
-        geo_map_data = Some(data);
+        geo_map_data = Some(GeoMap::new(buffer, key_bytes, options.lazy_geo_map)?);
     }
 
+    let content_hash = options
+        .result_cache
+        .is_some()
+        .then(|| content_hash(bindata));
     Ok(DbMeta {
         db_type,
         header_sip,
@@ -234,7 +908,42 @@ pub fn parse_meta_from_bytes(
         geo_map_data,
         start_index,
         end_index,
-    })
+        options,
+        invalid_utf8: AtomicUsize::new(0),
+        padding_size,
+        encrypted_block_size,
+        file_size,
+        label: None,
+        fingerprint: 0,
+        info,
+        expired,
+    }
+    .with_fingerprint(content_hash))
+}
+
+/// Hash of database bytes, identifying their contents in cache keys and index sidecars.
+///
+/// `DefaultHasher` may change between Rust releases; a changed hash only misses the caches
+/// keyed by it.
+pub fn content_hash(data: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(data);
+    hasher.finish()
+}
+
+/// `content_hash` of everything from `offset` to the end of `reader`, read in chunks.
+pub fn content_hash_from<R: Read + Seek>(reader: &mut R, offset: u64) -> io::Result<u64> {
+    reader.seek(SeekFrom::Start(offset))?;
+    let mut hasher = DefaultHasher::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) => return Ok(hasher.finish()),
+            Ok(n) => hasher.write(&buf[..n]),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
 }
 
 /// Parse metadata from a file using the data offset.
@@ -244,26 +953,28 @@ pub fn parse_meta_from_file<R: Read + Seek>(
     reader: &mut R,
     data_offset: u64,
     file_size_total: u64,
-    padding_size: u32,
-    encrypted_block_size: u32,
+    header: &HyperHeaderInfo,
     key_bytes: &[u8],
+    options: CzdbOptions,
 ) -> Result<DbMeta, CzError> {
+    let HyperHeaderInfo {
+        padding_size,
+        encrypted_block_size,
+        info,
+        expired,
+    } = *header;
     reader.seek(SeekFrom::Start(data_offset))?;
     let mut super_bytes = [0u8; SUPER_PART_LENGTH];
     reader.read_exact(&mut super_bytes)?;
 
-    let db_type = if super_bytes[0] & 1 == 0 {
-        DbType::Ipv4
-    } else {
-        DbType::Ipv6
-    };
+    let db_type = DbType::from_type_byte(super_bytes[0]);
     let file_size = u32::from_le_bytes([
         super_bytes[1],
         super_bytes[2],
         super_bytes[3],
         super_bytes[4],
     ]);
-    if file_size_total != (padding_size + encrypted_block_size + 12 + file_size) as u64 {
+    if file_size_total != header.data_offset() + file_size as u64 {
         return Err(CzError::DatabaseFileCorrupted);
     }
     let start_index = u32::from_le_bytes([
@@ -285,10 +996,8 @@ pub fn parse_meta_from_file<R: Read + Seek>(
         super_bytes[16],
     ]);
 
-    if total_header_block_size % HEADER_BLOCK_LENGTH as u32 != 0 {
-        return Err(CzError::DatabaseFileCorrupted);
-    }
-    let total_header_block = total_header_block_size / HEADER_BLOCK_LENGTH as u32;
+    let available = file_size_total.saturating_sub(data_offset + SUPER_PART_LENGTH as u64);
+    let total_header_block = header_block_count(total_header_block_size, available)?;
 
     reader.seek(SeekFrom::Start(data_offset + SUPER_PART_LENGTH as u64))?;
     let mut header_bytes = vec![0u8; total_header_block_size as usize];
@@ -297,9 +1006,12 @@ pub fn parse_meta_from_file<R: Read + Seek>(
     let mut header_sip = Vec::with_capacity(total_header_block as usize);
     let mut header_ptr = Vec::with_capacity(total_header_block as usize);
     for i in (0..header_bytes.len()).step_by(HEADER_BLOCK_LENGTH) {
-        let data_ptr =
-            u32::from_le_bytes([header_bytes[i + 16], header_bytes[i + 17], header_bytes[i + 18],
-                header_bytes[i + 19]]);
+        let data_ptr = u32::from_le_bytes([
+            header_bytes[i + 16],
+            header_bytes[i + 17],
+            header_bytes[i + 18],
+            header_bytes[i + 19],
+        ]);
         if data_ptr == 0 {
             break;
         }
@@ -312,10 +1024,11 @@ pub fn parse_meta_from_file<R: Read + Seek>(
         return Err(CzError::DatabaseFileCorrupted);
     }
 
-    let column_selection_ptr = end_index + db_type.index_block_len() as u32;
-    reader.seek(SeekFrom::Start(
-        data_offset + column_selection_ptr as u64,
-    ))?;
+    let column_selection_ptr = end_index
+        .checked_add(db_type.index_block_len() as u32)
+        .ok_or(CzError::DatabaseFileCorrupted)?;
+    let column_selection_pos = data_offset + column_selection_ptr as u64;
+    reader.seek(SeekFrom::Start(column_selection_pos))?;
     let mut buf = [0u8; 4];
     reader.read_exact(&mut buf)?;
     let column_selection = u32::from_le_bytes(buf) as u64;
@@ -324,13 +1037,18 @@ pub fn parse_meta_from_file<R: Read + Seek>(
     if column_selection != 0 {
         reader.read_exact(&mut buf)?;
         let geo_map_size = u32::from_le_bytes(buf);
+        if geo_map_size as u64 > file_size_total.saturating_sub(column_selection_pos + 8) {
+            return Err(CzError::DatabaseFileCorrupted);
+        }
         let mut map = vec![0u8; geo_map_size as usize];
         reader.read_exact(&mut map)?;
-        let decryptor = GeoDataDecryptor::new(key_bytes.to_vec())?;
-        let data = decryptor.decrypt(&map);
-        geo_map_data = Some(data);
+        geo_map_data = Some(GeoMap::new(map, key_bytes, options.lazy_geo_map)?);
     }
 
+    let content_hash = match options.result_cache {
+        Some(_) => Some(content_hash_from(reader, data_offset)?),
+        None => None,
+    };
     Ok(DbMeta {
         db_type,
         header_sip,
@@ -339,67 +1057,680 @@ pub fn parse_meta_from_file<R: Read + Seek>(
         geo_map_data,
         start_index,
         end_index,
-    })
+        options,
+        invalid_utf8: AtomicUsize::new(0),
+        padding_size,
+        encrypted_block_size,
+        file_size,
+        label: None,
+        fingerprint: 0,
+        info,
+        expired,
+    }
+    .with_fingerprint(content_hash))
 }
 
-/// Decode a region payload into a string, applying geo mapping if needed.
+/// Read the geo-map `(data_ptr, data_len)` referenced by a region payload.
 ///
-/// 解析区域数据为字符串，必要时应用地理映射。
-pub fn decode_region_from_bytes(region_bytes: &[u8], meta: &DbMeta) -> Option<String> {
+/// Returns `None` for inline regions and unreadable payloads.
+///
+/// 读取区域数据引用的地理映射 `(data_ptr, data_len)`；内联区域或无法解析时返回 `None`。
+pub fn read_geo_map_ref(region_bytes: &[u8]) -> Option<(usize, usize)> {
     let mut region_data = Cursor::new(region_bytes);
-    let geo_pos_mix_size = if let Ok(Value::Integer(i)) =
-        read_value(&mut region_data).map_err(|_| CzError::DatabaseFileCorrupted)
-    {
-        i.as_u64().unwrap_or(0)
-    } else {
+    match read_value(&mut region_data) {
+        Ok(Value::Integer(i)) => match i.as_u64() {
+            Some(0) | None => None,
+            Some(geo_pos_mix_size) => Some(split_geo_pos_mix(geo_pos_mix_size)),
+        },
+        _ => None,
+    }
+}
+
+/// Decode a MessagePack string, applying the invalid UTF-8 policy from the options.
+///
+/// Invalid strings are counted in `DbMeta::invalid_utf8` and become `"null"`, or a
+/// lossy decoding when `CzdbOptions::lossy_utf8` is enabled.
+fn decode_utf8<'a>(s: &'a Utf8String, meta: &DbMeta) -> Cow<'a, str> {
+    match s.as_str() {
+        Some(s) => Cow::Borrowed(s),
+        None => {
+            meta.invalid_utf8.fetch_add(1, AtomicOrdering::Relaxed);
+            if meta.options.lossy_utf8 {
+                String::from_utf8_lossy(s.as_bytes())
+            } else {
+                Cow::Borrowed("null")
+            }
+        }
+    }
+}
+
+/// Read the raw `other_data` bytes of a region payload without UTF-8 validation.
+///
+/// 读取区域数据中 `other_data` 的原始字节，不做 UTF-8 校验。
+pub fn read_other_data_bytes(region_bytes: &[u8]) -> Option<Vec<u8>> {
+    let mut region_data = Cursor::new(region_bytes);
+    if !matches!(read_value(&mut region_data), Ok(Value::Integer(_))) {
         return None;
-    };
-    let other_data = if let Ok(Value::String(s)) =
-        read_value(&mut region_data).map_err(|_| CzError::DatabaseFileCorrupted)
-    {
-        s.as_str().unwrap_or("null").to_string()
-    } else {
+    }
+    match read_value(&mut region_data) {
+        Ok(Value::String(s)) => Some(s.as_bytes().to_vec()),
+        _ => None,
+    }
+}
+
+/// Decode only the `other_data` string of a region payload, skipping geo-map columns.
+///
+/// Applies the same UTF-8 and whitespace options as full decoding.
+///
+/// 仅解析区域数据中的 `other_data` 字符串，跳过地理映射列；UTF-8 与空白处理选项与完整解析一致。
+pub fn decode_other_data(region_bytes: &[u8], meta: &DbMeta) -> Option<String> {
+    let mut region_data = Cursor::new(region_bytes);
+    if !matches!(read_value(&mut region_data), Ok(Value::Integer(_))) {
         return None;
+    }
+    read_other_data(&mut region_data, meta).ok()
+}
+
+/// Read the `other_data` string that follows the geo position in a region payload.
+fn read_other_data(
+    region_data: &mut Cursor<&[u8]>,
+    meta: &DbMeta,
+) -> Result<String, RegionErrorKind> {
+    match read_value(region_data) {
+        Ok(Value::String(s)) => {
+            let other_data = decode_utf8(&s, meta);
+            if meta.options.normalize_whitespace {
+                Ok(other_data.split_whitespace().collect::<Vec<_>>().join(" "))
+            } else {
+                Ok(other_data.to_string())
+            }
+        }
+        _ => Err(RegionErrorKind::MalformedOtherData),
+    }
+}
+
+/// Decode a region payload, borrowing from `region_bytes` when the region is inline.
+///
+/// Regions that need geo-map columns or whitespace normalization are returned owned.
+///
+/// 解析区域数据；内联区域直接借用 `region_bytes`，需要地理映射或空白规范化时返回拥有所有权的字符串。
+pub fn decode_region_ref<'a>(
+    region_bytes: &'a [u8],
+    meta: &DbMeta,
+    site: RecordSite,
+) -> Option<Cow<'a, str>> {
+    let mut region_data = Cursor::new(region_bytes);
+    if let Ok(Value::Integer(i)) = read_value(&mut region_data)
+        && i.as_u64() == Some(0)
+        && !meta.options.normalize_whitespace
+    {
+        let rest = &region_bytes[region_data.position() as usize..];
+        if let Ok((other_data, _)) = rmp::decode::read_str_from_slice(rest) {
+            return Some(Cow::Borrowed(other_data));
+        }
+    }
+    decode_region_from_bytes(region_bytes, meta, site).map(Cow::Owned)
+}
+
+/// Decode a region payload into a string, applying geo mapping if needed.
+///
+/// 解析区域数据为字符串，必要时应用地理映射。
+pub fn decode_region_from_bytes(
+    region_bytes: &[u8],
+    meta: &DbMeta,
+    site: RecordSite,
+) -> Option<String> {
+    decode_region_with_columns(region_bytes, meta, meta.column_selection, site)
+}
+
+/// Decode a region payload, selecting geo-map columns with `column_selection`
+/// instead of the database's own mask.
+///
+/// 解析区域数据，使用 `column_selection` 而非数据库自带掩码选择地理映射列。
+pub fn decode_region_with_columns(
+    region_bytes: &[u8],
+    meta: &DbMeta,
+    column_selection: u64,
+    site: RecordSite,
+) -> Option<String> {
+    decode_region_at(region_bytes, meta, column_selection, site).ok()
+}
+
+/// Decode the region payload of the record at `site`, reporting a malformed record as
+/// `CorruptRegion`.
+///
+/// Every region decode goes through here, so with the `tracing` feature each corrupt record
+/// emits one `warn` event; see `report_record_error`.
+///
+/// 解析 `site` 处记录的区域数据，记录格式错误时返回 `CorruptRegion`。所有区域解析都经过此处，
+/// 因此启用 `tracing` feature 时每条损坏记录都会发出一次 `warn` 事件，参见 `report_record_error`。
+pub fn decode_region_at(
+    region_bytes: &[u8],
+    meta: &DbMeta,
+    column_selection: u64,
+    site: RecordSite,
+) -> Result<String, CzError> {
+    decode_region_checked(region_bytes, meta, column_selection).map_err(|kind| {
+        report_record_error(
+            site,
+            CzError::CorruptRegion {
+                data_ptr: site.data_ptr,
+                kind,
+            },
+        )
+    })
+}
+
+/// Location of a region record, and the IP it is decoded for, as reported in `tracing` events.
+///
+/// 区域记录的位置及其对应的查询 IP，用于 `tracing` 事件。
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+pub struct RecordSite {
+    pub ip: Option<IpAddr>,
+    pub data_ptr: usize,
+    pub data_len: usize,
+}
+
+impl RecordSite {
+    /// The record `block` points at, decoded for a lookup of `ip`.
+    ///
+    /// 查询 `ip` 时 `block` 指向的记录。
+    pub fn lookup(ip: IpAddr, block: &IndexBlock) -> Self {
+        Self {
+            ip: Some(ip),
+            ..Self::scan(block)
+        }
+    }
+
+    /// The record `block` points at, decoded while walking the index rather than for a lookup.
+    ///
+    /// 遍历索引（而非查询）时 `block` 指向的记录。
+    pub fn scan(block: &IndexBlock) -> Self {
+        Self {
+            ip: None,
+            data_ptr: block.data_ptr,
+            data_len: block.data_len,
+        }
+    }
+}
+
+/// Emit a `warn` event for a lookup of `ip` that failed on a corrupt or unreadable record,
+/// returning `error` unchanged.
+///
+/// The event carries the IP, the error and, once the index block was read, its `data_ptr`
+/// and `data_len`.
+///
+/// 为查询 `ip` 时因记录损坏或无法读取而失败的情况发出 `warn` 事件，并原样返回 `error`。
+/// 事件包含 IP、错误，以及读取到索引块后的 `data_ptr` 与 `data_len`。
+#[cfg(feature = "tracing")]
+pub fn report_lookup_error(ip: IpAddr, block: Option<&IndexBlock>, error: CzError) -> CzError {
+    match block {
+        Some(block) => report_record_error(RecordSite::lookup(ip, block), error),
+        None => {
+            tracing::warn!(%ip, %error, "czdb lookup hit a bad index");
+            error
+        }
+    }
+}
+
+/// Without the `tracing` feature, lookup errors are only returned.
+#[cfg(not(feature = "tracing"))]
+pub fn report_lookup_error(_: IpAddr, _: Option<&IndexBlock>, error: CzError) -> CzError {
+    error
+}
+
+/// Emit a `warn` event for the unreadable or corrupt record at `site`, returning `error`
+/// unchanged.
+///
+/// The event carries the record's `data_ptr` and `data_len`, the error and, for lookups,
+/// the IP.
+///
+/// 为 `site` 处无法读取或已损坏的记录发出 `warn` 事件，并原样返回 `error`。事件包含记录的
+/// `data_ptr`、`data_len`、错误，查询时还包含 IP。
+#[cfg(feature = "tracing")]
+pub fn report_record_error(site: RecordSite, error: CzError) -> CzError {
+    tracing::warn!(
+        ip = site.ip.map(tracing::field::display),
+        data_ptr = site.data_ptr,
+        data_len = site.data_len,
+        %error,
+        "czdb lookup hit a bad record"
+    );
+    error
+}
+
+/// Without the `tracing` feature, record errors are only returned.
+#[cfg(not(feature = "tracing"))]
+pub fn report_record_error(_: RecordSite, error: CzError) -> CzError {
+    error
+}
+
+/// Decode a region payload, reporting which part of the record was malformed.
+///
+/// 解析区域数据，并在失败时指出记录中出错的部分。
+pub fn decode_region_checked(
+    region_bytes: &[u8],
+    meta: &DbMeta,
+    column_selection: u64,
+) -> Result<String, RegionErrorKind> {
+    let mut region_data = Cursor::new(region_bytes);
+    let geo_pos_mix_size = match read_value(&mut region_data) {
+        Ok(Value::Integer(i)) => i.as_u64().unwrap_or(0),
+        _ => return Err(RegionErrorKind::MissingGeoPosition),
     };
+    let other_data = read_other_data(&mut region_data, meta)?;
     if geo_pos_mix_size == 0 {
-        return Some(other_data);
+        return Ok(other_data);
     }
 
-    let data_len = ((geo_pos_mix_size >> 24) & 0xff) as usize;
-    let data_ptr = (geo_pos_mix_size & 0x00ffffff) as usize;
-    let geo_map_data = meta.geo_map_data.as_ref()?;
+    let (data_ptr, data_len) = split_geo_pos_mix(geo_pos_mix_size);
+    let geo_map_data = meta
+        .geo_map_data
+        .as_ref()
+        .ok_or(RegionErrorKind::InvalidGeoColumns)?
+        .data();
     if data_ptr + data_len > geo_map_data.len() {
-        return None;
+        return Err(RegionErrorKind::InvalidGeoColumns);
     }
 
     let mut region_data = Cursor::new(&geo_map_data[data_ptr..data_ptr + data_len]);
-    if let Ok(value) = read_value(&mut region_data) {
-        if let Value::Array(values) = value {
-            let mut region = String::new();
-            for (index, v) in values.into_iter().enumerate() {
-                let column_selected = ((meta.column_selection >> (index + 1)) & 1) == 1;
-                if column_selected {
-                    let mut value = v.as_str().unwrap_or("null");
-                    if value.is_empty() {
-                        value = "null";
-                    }
-                    region.push_str(value);
-                    region.push('\t');
-                }
+    match read_value(&mut region_data) {
+        Ok(mut array @ Value::Array(_)) => {
+            apply_utf8_policy(&mut array, column_selection, meta);
+            let decoder: &dyn RegionDecoder = meta
+                .options
+                .region_decoder
+                .as_deref()
+                .unwrap_or(&DefaultRegionDecoder);
+            Ok(decoder.decode(&array, column_selection, &other_data))
+        }
+        _ => Err(RegionErrorKind::InvalidGeoColumns),
+    }
+}
+
+/// Replace selected string columns that are not valid UTF-8 according to
+/// `CzdbOptions::lossy_utf8`, counting each one, so decoders only see valid strings.
+fn apply_utf8_policy(geo_map_array: &mut Value, column_selection: u64, meta: &DbMeta) {
+    let Value::Array(values) = geo_map_array else {
+        return;
+    };
+    for (index, v) in values.iter_mut().enumerate() {
+        if let Value::String(s) = v
+            && is_column_selected(column_selection, index)
+            && s.as_str().is_none()
+        {
+            *v = Value::from(decode_utf8(s, meta).into_owned());
+        }
+    }
+}
+
+/// Decode a region payload into a MessagePack array of every geo-map column followed by
+/// `other_data`.
+///
+/// Columns are not filtered by the column selection and no text options are applied.
+/// Inline regions yield a one-element array.
+///
+/// 将区域数据解析为 MessagePack 数组：全部地理映射列在前，`other_data` 在后。
+/// 不按列选择过滤，也不应用任何文本选项；内联区域返回仅含一个元素的数组。
+pub fn decode_region_value(region_bytes: &[u8], meta: &DbMeta) -> Option<Value> {
+    let mut region_data = Cursor::new(region_bytes);
+    let geo_pos_mix_size = match read_value(&mut region_data) {
+        Ok(Value::Integer(i)) => i.as_u64().unwrap_or(0),
+        _ => return None,
+    };
+    let other_data = match read_value(&mut region_data) {
+        Ok(other_data @ Value::String(_)) => other_data,
+        _ => return None,
+    };
+    if geo_pos_mix_size == 0 {
+        return Some(Value::Array(vec![other_data]));
+    }
+
+    let (data_ptr, data_len) = split_geo_pos_mix(geo_pos_mix_size);
+    let geo_map_data = meta.geo_map_data.as_ref()?.data();
+    let geo_map = geo_map_data.get(data_ptr..data_ptr.checked_add(data_len)?)?;
+    match read_value(&mut Cursor::new(geo_map)) {
+        Ok(Value::Array(mut columns)) => {
+            columns.push(other_data);
+            Some(Value::Array(columns))
+        }
+        _ => None,
+    }
+}
+
+/// Whether geo-map column `index` is selected. The mask only has room for 63 columns, so
+/// later ones are never selected.
+fn is_column_selected(column_selection: u64, index: usize) -> bool {
+    // Bit 0 is reserved; column `index` is selected by bit `index + 1`.
+    index < 63 && (column_selection >> (index + 1)) & 1 == 1
+}
+
+/// Join the geo-map columns selected by `column_selection`, then `other_data`, with tabs.
+///
+/// When `other_data` is empty the result ends with the last column, not a trailing tab.
+///
+/// `push_str` appends a string column; numeric columns are written in decimal, and empty
+/// or other non-string columns become `"null"`.
+///
+/// 以制表符连接 `column_selection` 选中的地理映射列及 `other_data`（为空时不保留末尾分隔符）；
+/// `push_str` 负责追加字符串列，数值列以十进制输出，空列与其他非字符串列记为 `"null"`。
+pub fn join_columns(
+    geo_map_array: &Value,
+    column_selection: u64,
+    other_data: &str,
+    push_str: impl Fn(&mut String, &Utf8String),
+) -> String {
+    let mut region = String::new();
+    let values = geo_map_array
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    for (index, v) in values.iter().enumerate() {
+        if is_column_selected(column_selection, index) {
+            let len = region.len();
+            match v {
+                Value::String(s) => push_str(&mut region, s),
+                Value::Integer(i) => region.push_str(&i.to_string()),
+                Value::F32(f) => region.push_str(&f.to_string()),
+                Value::F64(f) => region.push_str(&f.to_string()),
+                _ => {}
+            }
+            if region.len() == len {
+                region.push_str("null");
             }
-            region.push_str(&other_data);
-            return Some(region);
+            region.push('\t');
         }
     }
+    if other_data.is_empty() {
+        // Don't leave a dangling separator after the last column.
+        if region.ends_with('\t') {
+            region.pop();
+        }
+    } else {
+        region.push_str(other_data);
+    }
+    region
+}
+
+/// Reduce a decoded region to its ASCII content.
+///
+/// Non-ASCII characters are dropped from every tab-separated field, and fields left empty
+/// (such as purely CJK columns) are removed, so pinyin or English columns survive intact.
+///
+/// 将区域字符串精简为 ASCII 内容：删除各制表符分隔字段中的非 ASCII 字符，
+/// 并移除因此变为空的字段（如纯中文列），拼音或英文列得以保留。
+pub fn ascii_only(region: &str) -> String {
+    region
+        .split('\t')
+        .map(|field| field.chars().filter(char::is_ascii).collect::<String>())
+        .filter(|field| !field.trim().is_empty())
+        .map(|field| field.trim().to_string())
+        .collect::<Vec<_>>()
+        .join("\t")
+}
 
-    None
+/// Write one entry as a JSON object followed by a newline.
+///
+/// Addresses use their canonical textual form; the region is escaped as a JSON string.
+///
+/// 将单个条目写为一行 JSON 对象。地址使用规范文本形式，区域按 JSON 字符串转义。
+pub fn write_jsonl_entry<W: Write>(
+    w: &mut W,
+    start: IpAddr,
+    end: IpAddr,
+    region: &str,
+) -> io::Result<()> {
+    write!(w, "{{\"start\":\"{start}\",\"end\":\"{end}\",\"region\":\"")?;
+    for c in region.chars() {
+        match c {
+            '"' => w.write_all(b"\\\"")?,
+            '\\' => w.write_all(b"\\\\")?,
+            '\n' => w.write_all(b"\\n")?,
+            '\r' => w.write_all(b"\\r")?,
+            '\t' => w.write_all(b"\\t")?,
+            c if c < ' ' => write!(w, "\\u{:04x}", c as u32)?,
+            c => write!(w, "{c}")?,
+        }
+    }
+    w.write_all(b"\"}\n")
+}
+
+/// Write one CSV record, quoting fields that contain a comma, quote or line break.
+///
+/// 写入一行 CSV 记录；包含逗号、引号或换行的字段会加引号。
+pub fn write_csv_row<W: Write>(w: &mut W, fields: &[&str]) -> io::Result<()> {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            w.write_all(b",")?;
+        }
+        if field.contains([',', '"', '\n', '\r']) {
+            write!(w, "\"{}\"", field.replace('"', "\"\""))?;
+        } else {
+            w.write_all(field.as_bytes())?;
+        }
+    }
+    w.write_all(b"\r\n")
 }
 
 impl DbMeta {
+    /// Compute `fingerprint` as an FNV-1a hash of the header metadata, layout fields, header
+    /// index and, if given, the `content_hash` of the data section.
+    ///
+    /// 以头部元数据、布局字段、头部索引及（若提供）数据区 `content_hash` 的 FNV-1a 哈希计算
+    /// `fingerprint`。
+    pub fn with_fingerprint(mut self, content_hash: Option<u64>) -> Self {
+        let mut hash = 0xcbf2_9ce4_8422_2325u64;
+        let mut feed = |bytes: &[u8]| {
+            for &b in bytes {
+                hash = (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3);
+            }
+        };
+        feed(&[self.db_type as u8]);
+        for field in [
+            self.info.version,
+            self.info.client_id,
+            self.file_size,
+            self.padding_size,
+            self.encrypted_block_size,
+            self.start_index,
+            self.end_index,
+        ] {
+            feed(&field.to_le_bytes());
+        }
+        feed(&self.column_selection.to_le_bytes());
+        if let Some(date) = self.info.expiry_date {
+            feed(date.to_string().as_bytes());
+        }
+        for (sip, ptr) in self.header_sip.iter().zip(&self.header_ptr) {
+            feed(sip);
+            feed(&ptr.to_le_bytes());
+        }
+        if let Some(content_hash) = content_hash {
+            feed(&content_hash.to_le_bytes());
+        }
+        self.fingerprint = hash;
+        self
+    }
+
+    /// Drop `region` if it is one of the `treat_as_miss` placeholders.
+    ///
+    /// 若 `region` 属于 `treat_as_miss` 占位区域则丢弃。
+    pub fn filter_miss<S: AsRef<str>>(&self, region: Option<S>) -> Option<S> {
+        region.filter(|region| {
+            !self
+                .options
+                .treat_as_miss
+                .iter()
+                .any(|miss| miss == region.as_ref())
+        })
+    }
+
+    /// Region cached for `ip` by the configured `ResultCache`, if any.
+    ///
+    /// 返回已配置的 `ResultCache` 中 `ip` 对应的缓存区域（若存在）。
+    pub fn cached_region(&self, ip: IpAddr) -> Option<String> {
+        let cache = self.options.result_cache.as_ref()?;
+        cache.get(&CacheKey {
+            fingerprint: self.fingerprint,
+            ip,
+        })
+    }
+
+    /// Store a found region for `ip` in the configured `ResultCache`.
+    ///
+    /// 将 `ip` 命中的区域写入已配置的 `ResultCache`。
+    pub fn cache_region(&self, ip: IpAddr, region: &Option<String>) {
+        if let (Some(cache), Some(region)) = (&self.options.result_cache, region) {
+            let key = CacheKey {
+                fingerprint: self.fingerprint,
+                ip,
+            };
+            cache.put(key, region.clone());
+        }
+    }
+
+    /// Column mask registered for `lang`, or the database's own column selection.
+    ///
+    /// 返回为 `lang` 注册的列掩码，未注册时返回数据库自带的列选择。
+    pub fn language_mask(&self, lang: &str) -> u64 {
+        self.options
+            .language_columns
+            .get(lang)
+            .copied()
+            .unwrap_or(self.column_selection)
+    }
+
+    /// Columns that lead a geo-mapped region string, or none when a custom
+    /// `RegionDecoder` controls the layout.
+    ///
+    /// 地理映射区域字符串开头的列；使用自定义 `RegionDecoder` 时布局不可知，返回空。
+    pub fn structured_columns(&self) -> Vec<usize> {
+        if self.options.region_decoder.is_some() {
+            return Vec::new();
+        }
+        selected_column_positions(self.column_selection)
+    }
+
+    /// Split a decoded region string into a `Region`; `geo_mapped` tells whether it was built
+    /// from geo-map columns rather than stored inline. With a custom `RegionDecoder` the layout
+    /// of the string is unknown, so only `raw` is set.
+    ///
+    /// 将已解析的区域字符串拆分为 `Region`；`geo_mapped` 表示其由地理映射列组成而非内联存储。
+    /// 使用自定义 `RegionDecoder` 时字符串布局未知，仅填充 `raw`。
+    pub fn structured_region(&self, raw: String, geo_mapped: bool) -> Region {
+        let columns = if geo_mapped {
+            self.structured_columns()
+        } else {
+            Vec::new()
+        };
+        Region::from_joined(raw, &columns)
+    }
+
+    /// Address to look up for `ip`: IPv4-mapped (`::ffff:a.b.c.d`) and IPv4-compatible
+    /// (`::a.b.c.d`) IPv6 addresses become IPv4 for IPv4 databases, unless
+    /// `strict_ip_version` is set. `::` and `::1` are left alone.
+    ///
+    /// 返回实际查询的地址：对 IPv4 数据库，IPv4 映射与 IPv4 兼容的 IPv6 地址转换为 IPv4，
+    /// 除非启用了 `strict_ip_version`。`::` 与 `::1` 保持不变。
+    pub fn normalize_ip(&self, ip: IpAddr) -> IpAddr {
+        if self.options.strict_ip_version {
+            ip
+        } else {
+            section::normalize_ip(ip, self.db_type)
+        }
+    }
+
+    /// Names of the geo-map columns selected by `column_selection`, in output order.
+    ///
+    /// 按输出顺序返回 `column_selection` 选中的地理映射列名称。
+    pub fn selected_columns(&self) -> Vec<&'static str> {
+        selected_column_positions(self.column_selection)
+            .into_iter()
+            .map(geo_column_name)
+            .collect()
+    }
+
+    /// Byte range of the index blocks within the data section.
+    ///
+    /// 索引块在数据区中的字节范围。
+    pub fn index_range(&self) -> std::ops::Range<usize> {
+        self.start_index as usize..self.end_index as usize + self.db_type.index_block_len()
+    }
+
+    /// Number of index blocks between `start_index` and `end_index`, inclusive.
+    ///
+    /// 从 `start_index` 到 `end_index`（含）的索引块数量。
+    pub fn entry_count(&self) -> usize {
+        let (start, end) = (self.start_index as usize, self.end_index as usize);
+        if end < start {
+            return 0;
+        }
+        (end - start) / self.db_type.index_block_len() + 1
+    }
+
+    /// Version, client id and expiry date from the hyper header.
+    ///
+    /// 超头中的版本、客户端 ID 与到期日期。
+    pub fn info(&self) -> DbInfo {
+        self.info
+    }
+
+    /// `(padding_size, encrypted_block_size)` from the decrypted hyper header.
+    ///
+    /// 解密超头中的 `(padding_size, encrypted_block_size)`。
+    pub fn header_geometry(&self) -> (u32, u32) {
+        (self.padding_size, self.encrypted_block_size)
+    }
+
+    /// Number of region strings seen so far that were not valid UTF-8.
+    ///
+    /// 目前为止遇到的非法 UTF-8 区域字符串数量。
+    pub fn invalid_utf8_count(&self) -> usize {
+        self.invalid_utf8.load(AtomicOrdering::Relaxed)
+    }
+
+    /// Returns the decrypted geo-map bytes, if the database has one.
+    ///
+    /// 返回解密后的地理映射数据（若存在）。
+    pub fn raw_geo_map(&self) -> Option<&[u8]> {
+        self.geo_map_data.as_ref().map(GeoMap::data)
+    }
+
+    /// Inclusive address ranges covered by each header partition.
+    ///
+    /// Each partition spans from its header start IP up to the address before the next
+    /// header start IP; the last partition extends to the maximum address.
+    ///
+    /// 每个头部分区覆盖的闭区间：从该分区起始 IP 到下一分区起始 IP 的前一个地址，
+    /// 最后一个分区延伸到最大地址。
+    pub fn header_partitions(&self) -> Vec<(IpAddr, IpAddr)> {
+        let mut partitions = Vec::with_capacity(self.header_sip.len());
+        for (idx, sip) in self.header_sip.iter().enumerate() {
+            let start = bytes_to_ip(sip, self.db_type);
+            let end = match (self.header_sip.get(idx + 1), start) {
+                (None, IpAddr::V4(_)) => IpAddr::V4(Ipv4Addr::BROADCAST),
+                (None, IpAddr::V6(_)) => IpAddr::V6(Ipv6Addr::from(u128::MAX)),
+                (Some(next), _) => match bytes_to_ip(next, self.db_type) {
+                    IpAddr::V4(ip) => IpAddr::V4(Ipv4Addr::from(u32::from(ip).saturating_sub(1))),
+                    IpAddr::V6(ip) => IpAddr::V6(Ipv6Addr::from(u128::from(ip).saturating_sub(1))),
+                },
+            };
+            partitions.push((start, end));
+        }
+        partitions
+    }
+
     /// Locate the index range in the header for the given IP bytes.
     ///
-    /// 根据 IP 字节在头部索引中定位范围。
-    pub fn search_in_header(&self, ip_bytes: &[u8; 16]) -> Option<(u32, u32)> {
+    /// Each binary-search step is recorded in `iterations`.
+    ///
+    /// 根据 IP 字节在头部索引中定位范围；每步二分查找都会记录到 `iterations`。
+    pub fn search_in_header(
+        &self,
+        ip_bytes: &[u8; 16],
+        iterations: &mut impl Iterations,
+    ) -> Option<(u32, u32)> {
         let header_len = self.header_sip.len();
         if header_len == 0 {
             return None;
@@ -411,6 +1742,7 @@ impl DbMeta {
         let mut eptr: u32 = 0;
 
         while l <= h {
+            iterations.step();
             let m = (l + h) >> 1;
             let cmp = compare_bytes(ip_bytes, &self.header_sip[m as usize], ip_len);
             if cmp == Ordering::Less {
@@ -440,7 +1772,7 @@ impl DbMeta {
                 eptr = self.header_ptr[idx + 1];
             } else {
                 sptr = self.header_ptr[header_len - 1];
-                eptr = sptr + self.db_type.index_block_len() as u32;
+                eptr = sptr.checked_add(self.db_type.index_block_len() as u32)?;
             }
         }
 
@@ -448,6 +1780,305 @@ impl DbMeta {
             return None;
         }
 
+        // The end pointer comes from the neighbouring header entry, so a corrupt
+        // header can yield a reversed or misaligned block range.
+        if eptr < sptr || !(eptr - sptr).is_multiple_of(self.db_type.index_block_len() as u32) {
+            return None;
+        }
+
         Some((sptr, eptr))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_only_keeps_ascii_columns() {
+        assert_eq!(
+            ascii_only("中国\tZhongguo\t广东\tGuangdong\t电信 Telecom"),
+            "Zhongguo\tGuangdong\tTelecom"
+        );
+        assert_eq!(ascii_only("中国\t广东"), "");
+    }
+
+    #[test]
+    fn validate_key_trims_and_reports_precise_errors() {
+        assert!(validate_key(" MDEyMzQ1Njc4OWFiY2RlZg==\n").is_ok());
+        assert!(matches!(
+            validate_key("not base64!"),
+            Err(CzError::KeyDecodingError(_))
+        ));
+        assert!(matches!(
+            validate_key("MDEyMzQ1Njc="),
+            Err(CzError::InvalidAesKeyLength(8))
+        ));
+    }
+
+    #[test]
+    fn write_jsonl_entry_escapes_region() {
+        let mut out = Vec::new();
+        let start = "2001:db8::".parse().unwrap();
+        let end = "2001:db8::ffff".parse().unwrap();
+        write_jsonl_entry(&mut out, start, end, "a\"b\\c\td\u{1}").unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"start\":\"2001:db8::\",\"end\":\"2001:db8::ffff\",\"region\":\"a\\\"b\\\\c\\td\\u0001\"}\n"
+        );
+    }
+
+    #[test]
+    fn region_fields_follow_the_column_index_for_sparse_masks() {
+        // Mask 0b1010 selects columns 0 and 2: country and city, no province.
+        let columns = selected_column_positions(0b1010);
+        assert_eq!(columns, [0, 2]);
+        let region = Region::from_joined("中国\t深圳\t电信".to_string(), &columns);
+        assert_eq!(region.country.as_deref(), Some("中国"));
+        assert_eq!(region.province, None);
+        assert_eq!(region.city.as_deref(), Some("深圳"));
+        assert_eq!(region.isp.as_deref(), Some("电信"));
+
+        let region = Region::from_joined("广东\t南山\t电信".to_string(), &[1, 3]);
+        assert_eq!(region.country, None);
+        assert_eq!(region.province.as_deref(), Some("广东"));
+        assert_eq!(region.city, None);
+        assert_eq!(
+            region.columns,
+            vec![("district".to_string(), "南山".to_string())]
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn result_types_serialize_with_snake_case_names() {
+        let region = Region::from_joined("中国\t北京\t联通".to_string(), &[0, 1]);
+        assert_eq!(
+            serde_json::to_string(&region).unwrap(),
+            r#"{"country":"中国","province":"北京","city":null,"isp":"联通","columns":[],"raw":"中国\t北京\t联通"}"#
+        );
+        let entry = MatchedEntry {
+            start: "1.0.0.0".parse().unwrap(),
+            end: "1.0.0.255".parse().unwrap(),
+            region: "a".to_string(),
+            db_type: DbType::Ipv4,
+        };
+        assert_eq!(
+            serde_json::to_string(&entry).unwrap(),
+            r#"{"start":"1.0.0.0","end":"1.0.0.255","region":"a","db_type":"ipv4"}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&QueryResult::NotCovered).unwrap(),
+            r#""not_covered""#
+        );
+    }
+
+    #[test]
+    fn write_csv_row_quotes_special_fields() {
+        let mut out = Vec::new();
+        write_csv_row(&mut out, &["1.0.0.0", "a,b", "say \"hi\"", "中国"]).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "1.0.0.0,\"a,b\",\"say \"\"hi\"\"\",中国\r\n"
+        );
+    }
+
+    #[cfg(feature = "aes-gcm")]
+    #[test]
+    fn gcm_hyper_header_detects_tampering() {
+        use crate::fixture::{FixtureBuilder, TEST_KEY_BYTES};
+
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.255.255.255", "a")
+            .aes_gcm()
+            .build();
+        let header = read_hyper_header(
+            &mut Cursor::new(&bytes),
+            bytes.len() as u64,
+            &TEST_KEY_BYTES,
+            &CzdbOptions::new(),
+        );
+        assert_eq!(header.unwrap().padding_size, 8);
+
+        for pos in [4, 30] {
+            let mut tampered = bytes.clone();
+            tampered[pos] ^= 1;
+            assert!(matches!(
+                read_hyper_header(
+                    &mut Cursor::new(&tampered),
+                    tampered.len() as u64,
+                    &TEST_KEY_BYTES,
+                    &CzdbOptions::new()
+                ),
+                Err(CzError::DecryptionError)
+            ));
+        }
+    }
+
+    #[test]
+    fn oversized_encrypted_block_is_rejected_before_allocating() {
+        use crate::fixture::{FixtureBuilder, TEST_KEY_BYTES};
+
+        let mut bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.255.255.255", "a")
+            .build();
+        bytes[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            read_hyper_header(
+                &mut Cursor::new(&bytes),
+                bytes.len() as u64,
+                &TEST_KEY_BYTES,
+                &CzdbOptions::new()
+            ),
+            Err(CzError::DatabaseFileReadError(err)) if err.kind() == io::ErrorKind::UnexpectedEof
+        ));
+    }
+
+    #[test]
+    fn peek_db_type_reads_version_of_expired_files_too() {
+        use crate::fixture::{FixtureBuilder, TempDb, test_key};
+
+        for db_type in [DbType::Ipv4, DbType::Ipv6] {
+            let start = if db_type == DbType::Ipv4 {
+                "1.0.0.0"
+            } else {
+                "::1"
+            };
+            let bytes = FixtureBuilder::new(db_type)
+                .block(start, start, "a")
+                .expiry(200101)
+                .build();
+            let tmp = TempDb::new(&bytes);
+            assert_eq!(peek_db_type(tmp.path_str(), &test_key()).unwrap(), db_type);
+        }
+        assert!(matches!(
+            peek_db_type("/nonexistent/czdb", &test_key()),
+            Err(CzError::DatabaseFileReadError(_))
+        ));
+    }
+
+    #[test]
+    fn join_columns_formats_numeric_columns() {
+        let array = Value::Array(vec![
+            Value::from("中国"),
+            Value::from(440300),
+            Value::from(1.5f64),
+            Value::Nil,
+        ]);
+        let region = join_columns(&array, 0b11110, "电信", |region, s| {
+            region.push_str(s.as_str().unwrap())
+        });
+        assert_eq!(region, "中国\t440300\t1.5\tnull\t电信");
+    }
+
+    #[test]
+    fn join_columns_ignores_columns_past_the_mask() {
+        let columns = (0..64).map(|i| Value::from(format!("c{i}"))).collect();
+        let array = Value::Array(columns);
+        assert_eq!(
+            DefaultRegionDecoder.decode(&array, 0b110, "isp"),
+            "c0\tc1\tisp"
+        );
+        assert_eq!(
+            DefaultRegionDecoder
+                .decode(&array, u64::MAX, "")
+                .split('\t')
+                .count(),
+            63
+        );
+    }
+
+    #[test]
+    fn join_columns_drops_trailing_tab_without_other_data() {
+        let array = Value::Array(vec![Value::from("中国"), Value::from("广东")]);
+        let push = |region: &mut String, s: &Utf8String| region.push_str(s.as_str().unwrap());
+        assert_eq!(join_columns(&array, 0b110, "", push), "中国\t广东");
+        assert_eq!(join_columns(&array, 0b000, "", push), "");
+    }
+
+    #[test]
+    fn covering_prefix_len_handles_aligned_and_unaligned_ranges() {
+        let prefix = |start: &str, end: &str| {
+            covering_prefix_len(start.parse().unwrap(), end.parse().unwrap())
+        };
+        assert_eq!(prefix("10.0.0.0", "10.0.0.255"), 24);
+        assert_eq!(prefix("10.0.0.7", "10.0.0.7"), 32);
+        assert_eq!(prefix("0.0.0.0", "255.255.255.255"), 0);
+        assert_eq!(prefix("10.0.0.128", "10.0.1.5"), 23);
+        assert_eq!(prefix("2001:db8::", "2001:db8::ffff"), 112);
+        assert_eq!(prefix("1.2.3.4", "::1"), 0);
+    }
+
+    #[test]
+    fn is_geolocatable_rejects_private_and_reserved_ranges() {
+        let check = |ip: &str| is_geolocatable(ip.parse().unwrap());
+        for ip in [
+            "8.8.8.8",
+            "100.128.0.1",
+            "223.5.5.5",
+            "2400:3200::1",
+            "::ffff:1.2.3.4",
+        ] {
+            assert!(check(ip), "{ip}");
+        }
+        for ip in [
+            "0.0.0.0",
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "100.64.0.1",
+            "169.254.1.1",
+            "192.0.2.1",
+            "198.19.0.1",
+            "224.0.0.1",
+            "240.0.0.1",
+            "255.255.255.255",
+            "::",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "ff02::1",
+            "2001:db8::1",
+            "::ffff:10.0.0.1",
+        ] {
+            assert!(!check(ip), "{ip}");
+        }
+    }
+
+    #[test]
+    fn address_conversions_round_trip() {
+        for text in ["0.0.0.0", "1.2.3.4", "192.168.0.255", "255.255.255.255"] {
+            let ip: Ipv4Addr = text.parse().unwrap();
+            let n = ipv4_to_u32(ip);
+            assert_eq!(n.to_be_bytes(), ip.octets());
+            assert_eq!(u32_to_ipv4(n), ip);
+            assert_eq!(numeric_to_ip(n as u128, DbType::Ipv4), IpAddr::V4(ip));
+        }
+        for text in [
+            "::",
+            "2001:db8::1",
+            "ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff",
+        ] {
+            let ip: Ipv6Addr = text.parse().unwrap();
+            assert_eq!(bytes_to_ipv6(ip.octets()), ip);
+            assert_eq!(numeric_to_ip(u128::from(ip), DbType::Ipv6), IpAddr::V6(ip));
+        }
+    }
+
+    #[test]
+    fn lazy_geo_map_decrypts_once_on_first_access() {
+        let key = b"0123456789abcdef";
+        let plain = b"geo-map payload".to_vec();
+        let encrypted = GeoDataDecryptor::new(key.to_vec()).unwrap().decrypt(&plain);
+
+        let lazy = GeoMap::new(encrypted.clone(), key, true).unwrap();
+        assert!(lazy.decrypted.get().is_none());
+        assert_eq!(lazy.data(), plain.as_slice());
+        assert!(lazy.decrypted.get().is_some());
+
+        let eager = GeoMap::new(encrypted, key, false).unwrap();
+        assert!(eager.decrypted.get().is_some());
+        assert_eq!(eager.data(), plain.as_slice());
+    }
+}
@@ -0,0 +1,43 @@
+use crate::common::join_columns;
+use rmpv::Value;
+use std::fmt::Debug;
+
+/// Builds the region string from a record's geo-map columns.
+///
+/// Register an implementation with `CzdbOptions::region_decoder` to read databases that
+/// reuse the CZDB container with a different column schema. Inline regions (records
+/// without geo-map columns) are returned as stored and never reach the decoder. Selected
+/// string columns that are not valid UTF-8 are replaced according to
+/// `CzdbOptions::lossy_utf8` before the decoder runs.
+///
+/// 根据记录的地理映射列生成区域字符串。可通过 `CzdbOptions::region_decoder`
+/// 注册自定义实现，以读取沿用 CZDB 容器但列含义不同的数据库。
+/// 不含地理映射列的内联区域按原样返回，不经过解码器；选中的非法 UTF-8 字符串列
+/// 会先按 `CzdbOptions::lossy_utf8` 替换，再交给解码器。
+pub trait RegionDecoder: Debug + Send + Sync {
+    /// Decode `geo_map_array` (a msgpack array) with `column_selection` and `other_data`.
+    ///
+    /// 结合 `column_selection` 与 `other_data` 解析 `geo_map_array`（msgpack 数组）。
+    fn decode(&self, geo_map_array: &Value, column_selection: u64, other_data: &str) -> String;
+}
+
+/// The built-in layout: selected columns then `other_data`, joined with tabs.
+///
+/// Searchers use it when no decoder is registered. Numeric columns are written in decimal;
+/// empty and other non-string columns become `"null"`. Searchers apply
+/// `CzdbOptions::lossy_utf8` to the selected columns first, so the lossy fallback for
+/// invalid UTF-8 only applies when it is called directly.
+///
+/// 内置布局：选中的列与 `other_data` 以制表符连接，未注册解码器时查询器即使用它。
+/// 数值列以十进制输出，空列与其他非字符串列记为 `"null"`。查询器会先对选中列应用
+/// `CzdbOptions::lossy_utf8`，因此仅在直接调用时才会对非法 UTF-8 做有损解码。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultRegionDecoder;
+
+impl RegionDecoder for DefaultRegionDecoder {
+    fn decode(&self, geo_map_array: &Value, column_selection: u64, other_data: &str) -> String {
+        join_columns(geo_map_array, column_selection, other_data, |region, s| {
+            region.push_str(&String::from_utf8_lossy(s.as_bytes()))
+        })
+    }
+}
@@ -1,145 +1,2230 @@
 use crate::{
-    CzError,
+    CzError, CzdbOptions,
     common::{
-        DbMeta, DbType, decode_aes_key, compare_bytes, decode_region_from_bytes, parse_meta_from_file,
-        read_hyper_header,
+        BackendKind, BoundaryKind, DbInfo, DbMeta, DbType, IndexBlock, Iterations, MatchedEntry,
+        QueryResult, RecordSite, Region, Searcher, Source, ascii_only, bytes_to_ip,
+        check_aes_key_len, check_index_order, decode_aes_key, decode_other_data, decode_region_at,
+        decode_region_from_bytes, decode_region_value, decode_region_with_columns,
+        find_index_block, ip_to_bytes, is_geolocatable, key_from_env, key_from_file,
+        open_with_first_key, parse_meta_from_file, read_geo_map_ref, read_hyper_header,
+        read_other_data_bytes, report_lookup_error, write_jsonl_entry,
     },
 };
+use chrono::NaiveDate;
+#[cfg(feature = "cache")]
+use lru::LruCache;
+use rmpv::Value;
+#[cfg(feature = "cache")]
+use std::num::NonZeroUsize;
 use std::{
+    fmt,
     fs::File,
-    io::{Read, Seek, SeekFrom},
-    net::IpAddr,
+    io::{self, Read, Seek, SeekFrom, Write},
+    net::{AddrParseError, IpAddr},
+    sync::{
+        Arc, Mutex, PoisonError,
+        mpsc::{self, RecvTimeoutError},
+    },
+    thread,
+    time::Duration,
 };
 
+/// Readable, seekable source that `CzdbDisk::from_reader` can own.
+trait ReadSeek: Read + Seek + Send {}
+
+impl<R: Read + Seek + Send> ReadSeek for R {}
+
+/// Storage a `CzdbDisk` reads from.
+enum Backing {
+    /// A file, read positionally without locking.
+    File(File),
+    /// Any other reader; its cursor is shared, so every read seeks under the lock.
+    Reader(Mutex<Box<dyn ReadSeek>>),
+}
+
+impl Backing {
+    fn read_exact_at(&self, buf: &mut [u8], pos: u64) -> io::Result<()> {
+        match self {
+            Backing::File(file) => read_exact_at(file, buf, pos),
+            Backing::Reader(reader) => {
+                let mut reader = reader.lock().unwrap_or_else(PoisonError::into_inner);
+                reader.seek(SeekFrom::Start(pos))?;
+                reader.read_exact(buf)
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Backing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Backing::File(file) => f.debug_tuple("File").field(file).finish(),
+            Backing::Reader(_) => f.write_str("Reader"),
+        }
+    }
+}
+
+/// Reusable window of file bytes serving nearby reads without another syscall.
+#[derive(Debug)]
+struct ReadAhead {
+    size: u64,
+    window: Mutex<Window>,
+}
+
+#[derive(Debug, Default)]
+struct Window {
+    start: u64,
+    buffer: Vec<u8>,
+}
+
+/// Index partitions and decoded regions kept by a `CzdbDisk` opened with a region cache.
+#[cfg(feature = "cache")]
+#[derive(Debug)]
+struct RegionCache {
+    /// Index bytes keyed by `(offset, len)` within the data section.
+    partitions: LruCache<(usize, usize), Vec<u8>>,
+    /// Decoded regions keyed by region pointer.
+    regions: LruCache<usize, Decoded>,
+}
+
+/// A region decoded with the database's column selection.
+#[derive(Debug, Clone)]
+struct Decoded {
+    region: String,
+    /// Whether the region was built from geo-map columns rather than stored inline.
+    geo_mapped: bool,
+}
+
 /// Disk-backed CZDB searcher.
 ///
-/// 基于磁盘读取的 CZDB 查询器。
+/// Queries take `&self`: reads are positional (`read_at` on Unix, `seek_read` on Windows),
+/// so one searcher can be shared across threads behind an `Arc`. With the default options
+/// reads take no lock. A read-ahead window (`CzdbOptions::readahead_kib`) or region cache is
+/// shared behind a mutex that every read through it takes, and searchers built with
+/// `from_reader` serialize all reads on the reader.
+///
+/// 基于磁盘读取的 CZDB 查询器。查询接受 `&self`：读取使用定位读（Unix 上为 `read_at`，
+/// Windows 上为 `seek_read`），因此可通过 `Arc` 在多个线程间共享同一个查询器。默认选项下
+/// 读取不加锁；启用预读窗口（`CzdbOptions::readahead_kib`）或区域缓存时，它们由互斥锁保护，
+/// 经过它们的每次读取都需加锁；通过 `from_reader` 创建的查询器对读取器的所有读取串行执行。
 #[derive(Debug)]
 pub struct CzdbDisk {
-    file: File,
+    backing: Backing,
+    file_len: u64,
     data_offset: u64,
     meta: DbMeta,
+    readahead: Option<ReadAhead>,
+    #[cfg(feature = "cache")]
+    region_cache: Option<Mutex<RegionCache>>,
+    source: Option<Source>,
 }
 
+/// Shared handle returned by [`CzdbDisk::shared`].
+///
+/// [`CzdbDisk::shared`] 返回的共享句柄。
+pub type SharedCzdbDisk = Arc<CzdbDisk>;
+
 impl CzdbDisk {
     /// Open a database file for disk-backed queries.
     ///
     /// 打开数据库文件用于磁盘查询。
     pub fn open(db_path: &str, key: &str) -> Result<Self, CzError> {
-        let key_bytes = decode_aes_key(key)?;
+        Self::open_with_options(db_path, key, CzdbOptions::default())
+    }
+
+    /// Open a database file for disk-backed queries with a raw 16-byte AES key instead of a
+    /// base64 string.
+    ///
+    /// Returns `InvalidAesKeyLength` if `key` is not 16 bytes long.
+    ///
+    /// 打开数据库文件用于磁盘查询，直接使用 16 字节原始 AES 密钥而非 base64 字符串；长度不是 16 字节时返回
+    /// `InvalidAesKeyLength`。
+    pub fn open_with_key_bytes(db_path: &str, key: &[u8]) -> Result<Self, CzError> {
+        Self::open_with_key_bytes_and_options(db_path, key, CzdbOptions::default())
+    }
+
+    /// Open a database file, reading the base64 key from the environment variable `var_name`.
+    ///
+    /// Surrounding whitespace in the key is ignored. Returns `KeySourceError` if the variable
+    /// is not set.
+    ///
+    /// 打开数据库文件，从环境变量 `var_name` 读取 base64 密钥，忽略首尾空白；
+    /// 变量未设置时返回 `KeySourceError`。
+    pub fn open_with_key_env(db_path: &str, var_name: &str) -> Result<Self, CzError> {
+        Self::open(db_path, &key_from_env(var_name)?)
+    }
+
+    /// Open a database file, reading the base64 key from the file at `key_path`.
+    ///
+    /// Surrounding whitespace and newlines in the file are ignored. Returns `KeySourceError`
+    /// if the file cannot be read.
+    ///
+    /// 打开数据库文件，从 `key_path` 处的文件读取 base64 密钥，忽略首尾空白与换行；
+    /// 文件无法读取时返回 `KeySourceError`。
+    pub fn open_with_key_file(db_path: &str, key_path: &str) -> Result<Self, CzError> {
+        Self::open(db_path, &key_from_file(key_path)?)
+    }
+
+    /// Open a database file with the first of `keys` that decrypts it, e.g. while keys are
+    /// being rotated.
+    ///
+    /// A key that fails anywhere in the header parse is skipped. If none of them fits, returns
+    /// `NoMatchingKey` with the number of keys tried and the last error that was not a plain
+    /// decryption failure, such as `DatabaseFileCorrupted` for a damaged file.
+    ///
+    /// 使用 `keys` 中第一个能解密的密钥打开数据库文件，适用于密钥轮换期间；头部解析任一环节
+    /// 失败的密钥会被跳过。全部不适用时返回 `NoMatchingKey`，其中包含尝试的密钥数量，以及最后一个
+    /// 并非单纯解密失败的错误（如文件损坏时的 `DatabaseFileCorrupted`）。
+    pub fn open_with_keys(db_path: &str, keys: &[&str]) -> Result<Self, CzError> {
+        open_with_first_key(keys, |key| Self::open(db_path, key))
+    }
+
+    /// Open a database file, checking expiry against `today` instead of the host clock.
+    ///
+    /// Equivalent to `open_with_options` with `CzdbOptions::clock(today)`.
+    ///
+    /// 打开数据库文件用于磁盘查询，以 `today` 而非主机时钟判断是否过期；
+    /// 等价于使用 `CzdbOptions::clock(today)` 的 `open_with_options`。
+    pub fn open_with_reference_date(
+        db_path: &str,
+        key: &str,
+        today: NaiveDate,
+    ) -> Result<Self, CzError> {
+        Self::open_with_options(db_path, key, CzdbOptions::new().clock(today))
+    }
+
+    /// Open a database file with a read-ahead window of `kib` KiB.
+    ///
+    /// Index and region reads are served from an aligned, reusable window so that
+    /// clustered queries avoid a seek and read per lookup.
+    ///
+    /// 使用 `kib` KiB 的预读窗口打开数据库文件，相邻查询可复用窗口内的数据。
+    pub fn open_with_readahead(db_path: &str, key: &str, kib: usize) -> Result<Self, CzError> {
+        Self::open_with_options(db_path, key, CzdbOptions::new().readahead_kib(kib))
+    }
+
+    /// Open a database file, caching up to `capacity` index partitions and decoded regions.
+    ///
+    /// Equivalent to `open_with_options` with `CzdbOptions::region_cache(capacity)`.
+    ///
+    /// 打开数据库文件，并缓存至多 `capacity` 个索引分区与已解析区域；
+    /// 等价于使用 `CzdbOptions::region_cache(capacity)` 的 `open_with_options`。
+    #[cfg(feature = "cache")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "cache")))]
+    pub fn open_with_cache(db_path: &str, key: &str, capacity: usize) -> Result<Self, CzError> {
+        Self::open_with_options(db_path, key, CzdbOptions::new().region_cache(capacity))
+    }
+
+    /// Open a database file, giving up if opening takes longer than `timeout`.
+    ///
+    /// The open and the initial header/metadata reads run on a worker thread. On timeout
+    /// this returns `CzError::DatabaseFileReadError` with `io::ErrorKind::TimedOut`; the
+    /// worker is left to finish or hang in the background. Per-query reads are not covered.
+    ///
+    /// 在工作线程中打开数据库并读取头部与元数据，超过 `timeout` 即返回
+    /// `io::ErrorKind::TimedOut` 的 `CzError::DatabaseFileReadError`。后台线程不会被终止；
+    /// 该超时仅作用于打开阶段，不覆盖后续查询读取。
+    pub fn open_with_timeout(db_path: &str, key: &str, timeout: Duration) -> Result<Self, CzError> {
+        let (tx, rx) = mpsc::channel();
+        let db_path = db_path.to_string();
+        let key = key.to_string();
+        thread::spawn(move || {
+            let _ = tx.send(Self::open(&db_path, &key));
+        });
+        match rx.recv_timeout(timeout) {
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => Err(CzError::DatabaseFileReadError(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "timed out opening the database file",
+            ))),
+            Err(RecvTimeoutError::Disconnected) => Err(CzError::DatabaseFileReadError(
+                io::Error::other("database open worker exited unexpectedly"),
+            )),
+        }
+    }
+
+    /// Open a database file for disk-backed queries with custom options.
+    ///
+    /// 使用自定义选项打开数据库文件用于磁盘查询。
+    pub fn open_with_options(
+        db_path: &str,
+        key: &str,
+        options: CzdbOptions,
+    ) -> Result<Self, CzError> {
+        Self::open_with_key_bytes_and_options(db_path, &decode_aes_key(key)?, options)
+    }
+
+    /// `open_with_key_bytes` with custom options.
+    ///
+    /// 使用自定义选项的 `open_with_key_bytes`。
+    pub fn open_with_key_bytes_and_options(
+        db_path: &str,
+        key_bytes: &[u8],
+        options: CzdbOptions,
+    ) -> Result<Self, CzError> {
+        check_aes_key_len(key_bytes)?;
         let mut file = File::open(db_path)?;
-        let header = read_hyper_header(&mut file, &key_bytes)?;
-        let data_offset = (12 + header.padding_size + header.encrypted_block_size) as u64;
-        let file_size_total = file.metadata()?.len();
-        let meta = parse_meta_from_file(
-            &mut file,
+        let file_len = file.metadata()?.len();
+        let (data_offset, meta) = load_meta(&mut file, file_len, key_bytes, options)?;
+        let source = Source {
+            path: db_path.to_string(),
+            key: key_bytes.to_vec(),
+        };
+        Ok(Self::new(
+            Backing::File(file),
+            file_len,
             data_offset,
-            file_size_total,
-            header.padding_size,
-            header.encrypted_block_size,
-            &key_bytes,
-        )?;
+            meta,
+            Some(source),
+        ))
+    }
+
+    /// Open a database from any seekable reader, such as a ZIP entry or an in-memory cursor.
+    ///
+    /// The reader is kept and read on demand like a file. Its cursor is shared, so
+    /// concurrent queries take turns; prefer `open` for files on disk. `reload` is not
+    /// available on searchers built this way.
+    ///
+    /// 从任意可定位的读取器（如 ZIP 条目或内存游标）打开数据库。读取器会被保留并像文件一样按需读取；
+    /// 由于共享游标，并发查询需轮流进行，磁盘文件请优先使用 `open`。以此方式构建的查询器不支持 `reload`。
+    pub fn from_reader<R>(reader: R, key: &str) -> Result<Self, CzError>
+    where
+        R: Read + Seek + Send + 'static,
+    {
+        Self::from_reader_with_options(reader, key, CzdbOptions::default())
+    }
+
+    /// `from_reader` with custom options.
+    ///
+    /// 使用自定义选项的 `from_reader`。
+    pub fn from_reader_with_options<R>(
+        mut reader: R,
+        key: &str,
+        options: CzdbOptions,
+    ) -> Result<Self, CzError>
+    where
+        R: Read + Seek + Send + 'static,
+    {
+        let key_bytes = decode_aes_key(key)?;
+        let file_len = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(0))?;
+        let (data_offset, meta) = load_meta(&mut reader, file_len, &key_bytes, options)?;
+        let backing = Backing::Reader(Mutex::new(Box::new(reader)));
+        Ok(Self::new(backing, file_len, data_offset, meta, None))
+    }
 
-        Ok(Self {
-            file,
+    fn new(
+        backing: Backing,
+        file_len: u64,
+        data_offset: u64,
+        meta: DbMeta,
+        source: Option<Source>,
+    ) -> Self {
+        let readahead = match meta.options.readahead_kib.saturating_mul(1024) as u64 {
+            0 => None,
+            size => Some(ReadAhead {
+                size,
+                window: Mutex::default(),
+            }),
+        };
+        #[cfg(feature = "cache")]
+        let region_cache = NonZeroUsize::new(meta.options.region_cache).map(|capacity| {
+            Mutex::new(RegionCache {
+                partitions: LruCache::new(capacity),
+                regions: LruCache::new(capacity),
+            })
+        });
+        Self {
+            backing,
+            file_len,
             data_offset,
             meta,
-        })
+            readahead,
+            #[cfg(feature = "cache")]
+            region_cache,
+            source,
+        }
+    }
+
+    /// Re-open the file this searcher was opened from and replace its state.
+    ///
+    /// The same key and options are used and the label is kept. On failure the current
+    /// state is left untouched and keeps serving queries. Replace the file by renaming a new
+    /// one over it: the current state still reads the old file until the reload succeeds.
+    ///
+    /// 使用相同的密钥和选项重新打开原文件并替换内部状态，标签保持不变。
+    /// 失败时保留当前状态并继续提供查询。请通过重命名新文件覆盖原文件来更新，
+    /// 重新加载成功前当前状态仍会读取旧文件。
+    ///
+    /// Searchers built with `from_reader` have no file to reload and return an
+    /// `Unsupported` read error.
+    ///
+    /// 由 `from_reader` 构建的查询器没有可重新加载的文件，返回 `Unsupported` 读取错误。
+    pub fn reload(&mut self) -> Result<(), CzError> {
+        let source = self.source.as_ref().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                "database was not opened from a file",
+            )
+        })?;
+        let mut fresh = Self::open_with_key_bytes_and_options(
+            &source.path,
+            &source.key,
+            self.meta.options.clone(),
+        )?;
+        fresh.meta.label = self.meta.label.take();
+        *self = fresh;
+        Ok(())
+    }
+
+    /// Move this searcher into a cheaply clonable handle for sharing across threads or tasks.
+    ///
+    /// No lock is added: queries already take `&self` and read positionally, so the handle
+    /// is a plain `Arc` and clones query the same file concurrently.
+    ///
+    /// 将查询器移入可廉价克隆的句柄，以便在线程或任务间共享。不会额外加锁：
+    /// 查询本就只需 `&self` 且使用定位读，因此句柄就是普通的 `Arc`，各克隆可并发查询同一文件。
+    pub fn shared(self) -> SharedCzdbDisk {
+        Arc::new(self)
     }
 
     /// Search a single IP address.
     ///
-    /// 查询指定 IP 地址。
-    pub fn search(&mut self, ip: IpAddr) -> Option<String> {
-        if !self.meta.db_type.compare(&ip) {
-            return None;
+    /// Consults the configured `ResultCache` first, if any. Regions listed in
+    /// `CzdbOptions::treat_as_miss` yield `None`.
+    ///
+    /// 查询指定 IP 地址；若配置了 `ResultCache`，会先查询缓存。
+    /// `CzdbOptions::treat_as_miss` 中列出的区域返回 `None`。
+    pub fn search(&self, ip: IpAddr) -> Option<String> {
+        self.try_search(ip).ok().flatten()
+    }
+
+    /// Search a single IP address, reporting unreadable or corrupt records as errors.
+    ///
+    /// `Ok(None)` is a clean miss. Index or region bytes lying past the end of the file yield
+    /// `Err(CzError::DatabaseFileCorrupted)`, other read failures
+    /// `Err(CzError::DatabaseFileReadError)`, and regions that fail to decode
+    /// `Err(CzError::CorruptRegion)`. `search` maps every error to `None`.
+    ///
+    /// 查询指定 IP 地址，并将无法读取或已损坏的记录作为错误返回。`Ok(None)` 表示正常未命中；
+    /// 索引或区域数据超出文件末尾时返回 `Err(CzError::DatabaseFileCorrupted)`，其他读取失败返回
+    /// `Err(CzError::DatabaseFileReadError)`，区域解析失败返回 `Err(CzError::CorruptRegion)`。
+    /// `search` 会将所有错误映射为 `None`。
+    pub fn try_search(&self, ip: IpAddr) -> Result<Option<String>, CzError> {
+        if let Some(region) = self.meta.cached_region(ip) {
+            return Ok(Some(region));
         }
-        let mut ip_bytes = [0u8; 16];
-        match ip {
-            IpAddr::V4(ip) => ip_bytes[..4].copy_from_slice(&ip.octets()),
-            IpAddr::V6(ip) => ip_bytes.copy_from_slice(&ip.octets()),
+        let region = self.search_uncached(ip)?;
+        let region = self.meta.filter_miss(region);
+        self.meta.cache_region(ip, &region);
+        Ok(region)
+    }
+
+    fn search_uncached(&self, ip: IpAddr) -> Result<Option<String>, CzError> {
+        let Some(block) = self
+            .try_locate(ip, &mut ())
+            .map_err(|e| report_lookup_error(ip, None, e))?
+        else {
+            return Ok(None);
+        };
+        Ok(self.try_region(ip, &block)?.map(|decoded| decoded.region))
+    }
+
+    /// Decode the region `block` points at, served from the region cache when enabled.
+    fn try_region(&self, ip: IpAddr, block: &IndexBlock) -> Result<Option<Decoded>, CzError> {
+        #[cfg(feature = "cache")]
+        if let Some(cache) = &self.region_cache {
+            let hit = cache
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .regions
+                .get(&block.data_ptr)
+                .cloned();
+            if hit.is_some() {
+                return Ok(hit);
+            }
+            let decoded = self.decode_block(ip, block)?;
+            if let Some(decoded) = &decoded {
+                cache
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .regions
+                    .put(block.data_ptr, decoded.clone());
+            }
+            return Ok(decoded);
         }
+        self.decode_block(ip, block)
+    }
 
-        let (sptr, eptr) = self.meta.search_in_header(&ip_bytes)?;
-        let sptr = sptr as usize;
-        let eptr = eptr as usize;
-        if eptr < sptr {
+    fn decode_block(&self, ip: IpAddr, block: &IndexBlock) -> Result<Option<Decoded>, CzError> {
+        let Some(region_bytes) = self
+            .try_read_region(block)
+            .map_err(|e| report_lookup_error(ip, Some(block), e))?
+        else {
+            return Ok(None);
+        };
+        let site = RecordSite::lookup(ip, block);
+        let region = decode_region_at(&region_bytes, &self.meta, self.meta.column_selection, site)?;
+        Ok(Some(Decoded {
+            region,
+            geo_mapped: read_geo_map_ref(&region_bytes).is_some(),
+        }))
+    }
+
+    /// Search `ip` and split the region into named fields; see `Region`.
+    ///
+    /// 查询 `ip` 并将区域拆分为具名字段，参见 `Region`。
+    pub fn search_structured(&self, ip: IpAddr) -> Option<Region> {
+        let block = self.locate(ip)?;
+        let decoded = self.try_region(ip, &block).ok()??;
+        self.meta.filter_miss(Some(decoded.region.as_str()))?;
+        Some(
+            self.meta
+                .structured_region(decoded.region, decoded.geo_mapped),
+        )
+    }
+
+    /// Search `ip` only if it is a public address, returning `None` immediately otherwise.
+    ///
+    /// Private, loopback, link-local and other reserved addresses skip the lookup; see
+    /// `is_geolocatable` for the exact ranges.
+    ///
+    /// 仅在 `ip` 为公网地址时查询，否则直接返回 `None`。私有、环回、链路本地等保留地址
+    /// 不执行查询，具体范围参见 `is_geolocatable`。
+    pub fn search_public_only(&self, ip: IpAddr) -> Option<String> {
+        if !is_geolocatable(ip) {
             return None;
         }
+        self.search(ip)
+    }
 
-        let ip_len = self.meta.db_type.bytes_len();
-        let blen = self.meta.db_type.index_block_len();
-        let block_len = eptr - sptr;
-        let read_len = block_len + blen;
-        let mut index_buffer = vec![0u8; read_len];
-        if self
-            .file
-            .seek(SeekFrom::Start(self.data_offset + sptr as u64))
-            .is_err()
-        {
-            return None;
+    /// Search `ip` and report how many binary-search iterations the lookup took.
+    ///
+    /// The count covers both the header search and the search within the located index
+    /// partition, so an oversized partition shows up as a high count. The `ResultCache` is
+    /// bypassed; `treat_as_miss` still applies. `search` does no counting.
+    ///
+    /// 查询 `ip` 并返回查找所用的二分迭代次数。次数包含头部索引查找与所定位索引分区内的查找，
+    /// 过大的分区会表现为较高的次数。不使用 `ResultCache`，但仍应用 `treat_as_miss`。
+    /// `search` 不做计数。
+    pub fn search_instrumented(&self, ip: IpAddr) -> (Option<String>, usize) {
+        let mut iterations = 0;
+        let region = self.locate_counted(ip, &mut iterations).and_then(|block| {
+            let region_bytes = self.read_region(&block)?;
+            decode_region_from_bytes(&region_bytes, &self.meta, RecordSite::lookup(ip, &block))
+        });
+        (self.meta.filter_miss(region), iterations)
+    }
+
+    /// Search a single IP address given by reference.
+    ///
+    /// Convenience for generic code holding `&IpAddr`; equivalent to `search(*ip)`.
+    ///
+    /// 通过引用查询指定 IP，便于持有 `&IpAddr` 的泛型代码使用，等价于 `search(*ip)`。
+    pub fn search_by_ref(&self, ip: &IpAddr) -> Option<String> {
+        self.search(*ip)
+    }
+
+    /// Parse `ip` and search it, returning the parse error for malformed input.
+    ///
+    /// Lookups compare the parsed octets, so every spelling of an IPv6 address (letter case,
+    /// leading zeros, `::` compression) finds the same entry.
+    ///
+    /// 解析 `ip` 并查询；输入格式错误时返回解析错误。查询比较的是解析后的字节，因此同一 IPv6
+    /// 地址的任意写法（大小写、前导零、`::` 压缩）都会命中相同条目。
+    pub fn search_str(&self, ip: &str) -> Result<Option<String>, AddrParseError> {
+        Ok(self.search(ip.parse()?))
+    }
+
+    /// Search a single IP address, returning `default` when there is no usable region.
+    ///
+    /// Both a miss and a matched block whose region is empty after trimming yield
+    /// `default`; use `search` to tell them apart.
+    ///
+    /// 查询指定 IP，无可用区域时返回 `default`。未命中与命中但区域去除空白后为空
+    /// 两种情况均返回 `default`；如需区分请使用 `search`。
+    pub fn search_or(&self, ip: IpAddr, default: &str) -> String {
+        match self.search(ip) {
+            Some(region) if !region.trim().is_empty() => region,
+            _ => default.to_string(),
         }
-        if self.file.read_exact(&mut index_buffer).is_err() {
-            return None;
+    }
+
+    /// Look up `ip`, reporting why no region was returned.
+    ///
+    /// 查询 `ip`，并在未返回区域时说明原因。
+    pub fn query(&self, ip: IpAddr) -> QueryResult {
+        let ip = self.meta.normalize_ip(ip);
+        if !self.meta.db_type.compare(&ip) {
+            return QueryResult::WrongVersion;
         }
+        let Some(block) = self.locate(ip) else {
+            return QueryResult::NotCovered;
+        };
+        let region = self.try_region(ip, &block).ok().flatten();
+        QueryResult::covered(self.meta.filter_miss(region.map(|decoded| decoded.region)))
+    }
 
-        let mut l = 0usize;
-        let mut h = block_len / blen;
-        while l <= h {
-            let m = (l + h) >> 1;
-            let p = m * blen;
-            let start_ip = &index_buffer[p..p + ip_len];
-            let end_ip = &index_buffer[p + ip_len..p + ip_len * 2];
-            let cmp_start = compare_bytes(&ip_bytes, start_ip, ip_len);
-            let cmp_end = compare_bytes(&ip_bytes, end_ip, ip_len);
-
-            if cmp_start != std::cmp::Ordering::Less && cmp_end != std::cmp::Ordering::Greater {
-                let data_ptr = u32::from_le_bytes([
-                    index_buffer[p + ip_len * 2],
-                    index_buffer[p + ip_len * 2 + 1],
-                    index_buffer[p + ip_len * 2 + 2],
-                    index_buffer[p + ip_len * 2 + 3],
-                ]) as usize;
-                let data_len = index_buffer[p + ip_len * 2 + 4] as usize;
-                if data_ptr == 0 || data_len == 0 {
-                    return None;
-                }
-                let mut region_bytes = vec![0u8; data_len];
-                if self
-                    .file
-                    .seek(SeekFrom::Start(self.data_offset + data_ptr as u64))
-                    .is_err()
-                {
-                    return None;
-                }
-                if self.file.read_exact(&mut region_bytes).is_err() {
-                    return None;
-                }
-                return decode_region_from_bytes(&region_bytes, &self.meta);
-            } else if cmp_start == std::cmp::Ordering::Less {
-                if m == 0 {
-                    break;
-                }
-                h = m - 1;
-            } else {
-                l = m + 1;
+    /// Search a single IP address and keep only its ASCII content.
+    ///
+    /// This is lossy: non-ASCII characters are dropped and columns left empty are removed,
+    /// so purely Chinese regions come back as an empty string. Intended for downstream
+    /// systems that cannot handle UTF-8.
+    ///
+    /// 查询指定 IP 并仅保留 ASCII 内容。该操作有损：非 ASCII 字符被删除，变为空的列被移除，
+    /// 纯中文区域将返回空字符串。适用于无法处理 UTF-8 的下游系统。
+    pub fn search_ascii(&self, ip: IpAddr) -> Option<String> {
+        self.search(ip).map(|region| ascii_only(&region))
+    }
+
+    /// Returns only the inline `other_data` of the record matching `ip`.
+    ///
+    /// Geo-map columns are never decoded. Whitespace and UTF-8 options apply as in `search`.
+    ///
+    /// 仅返回匹配记录的内联 `other_data`，不解析地理映射列；空白与 UTF-8 选项与 `search` 一致。
+    pub fn search_other(&self, ip: IpAddr) -> Option<String> {
+        let block = self.locate(ip)?;
+        let region_bytes = self.read_region(&block)?;
+        decode_other_data(&region_bytes, &self.meta)
+    }
+
+    /// Returns the undecoded region of the record matching `ip` as a MessagePack array.
+    ///
+    /// The array holds every geo-map column, including unselected ones, followed by
+    /// `other_data`; inline regions have `other_data` only. Text options are not applied.
+    ///
+    /// 以 MessagePack 数组返回匹配记录未经格式化的区域：包含全部地理映射列（含未选中的列），
+    /// 最后为 `other_data`；内联区域仅含 `other_data`。不应用文本选项。
+    pub fn search_raw(&self, ip: IpAddr) -> Option<Value> {
+        let block = self.locate(ip)?;
+        decode_region_value(&self.read_region(&block)?, &self.meta)
+    }
+
+    /// Returns the raw `other_data` bytes of the record matching `ip`, without UTF-8 validation.
+    ///
+    /// 返回匹配记录 `other_data` 的原始字节，不做 UTF-8 校验。
+    pub fn search_raw_bytes(&self, ip: IpAddr) -> Option<Vec<u8>> {
+        let block = self.locate(ip)?;
+        let region_bytes = self.read_region(&block)?;
+        read_other_data_bytes(&region_bytes)
+    }
+
+    /// Returns the geo-map `(data_ptr, data_len)` referenced by the record matching `ip`.
+    ///
+    /// Returns `None` if the IP is not covered or its region is stored inline.
+    ///
+    /// 返回匹配记录引用的地理映射 `(data_ptr, data_len)`；未覆盖或内联区域时返回 `None`。
+    pub fn geo_map_ref(&self, ip: IpAddr) -> Option<(usize, usize)> {
+        let block = self.locate(ip)?;
+        let region_bytes = self.read_region(&block)?;
+        read_geo_map_ref(&region_bytes)
+    }
+
+    /// Number of region strings decoded so far that were not valid UTF-8.
+    ///
+    /// 目前为止解析到的非法 UTF-8 区域字符串数量。
+    pub fn invalid_utf8_count(&self) -> usize {
+        self.meta.invalid_utf8_count()
+    }
+
+    /// Returns the decrypted geo-map bytes, if the database has one.
+    ///
+    /// 返回解密后的地理映射数据（若存在）。
+    pub fn raw_geo_map(&self) -> Option<&[u8]> {
+        self.meta.raw_geo_map()
+    }
+
+    fn read_region(&self, block: &IndexBlock) -> Option<Vec<u8>> {
+        self.try_read_region(block).ok().flatten()
+    }
+
+    fn try_read_region(&self, block: &IndexBlock) -> Result<Option<Vec<u8>>, CzError> {
+        if block.data_ptr == 0 || block.data_len == 0 {
+            return Ok(None);
+        }
+        let mut region_bytes = vec![0u8; block.data_len];
+        self.read_data(block.data_ptr, &mut region_bytes)
+            .map_err(corrupt_on_eof)?;
+        Ok(Some(region_bytes))
+    }
+
+    /// Search a single IP address, returning the geo-map columns registered for `lang`.
+    ///
+    /// Languages without a mapping (see `CzdbOptions::language_columns`) fall back to
+    /// the database's column selection, matching `search`.
+    ///
+    /// 查询指定 IP，返回为 `lang` 注册的地理映射列；未注册的语言使用数据库自带的列选择，
+    /// 与 `search` 结果一致。
+    pub fn search_lang(&self, ip: IpAddr, lang: &str) -> Option<String> {
+        let block = self.locate(ip)?;
+        let region_bytes = self.read_region(&block)?;
+        let region = decode_region_with_columns(
+            &region_bytes,
+            &self.meta,
+            self.meta.language_mask(lang),
+            RecordSite::lookup(ip, &block),
+        );
+        self.meta.filter_miss(region)
+    }
+
+    /// Look up the entry covering `ip`, returning its range, region and database type.
+    ///
+    /// 查询覆盖 `ip` 的条目，返回其地址范围、区域及数据库类型。
+    pub fn lookup_entry(&self, ip: IpAddr) -> Option<MatchedEntry> {
+        let block = self.locate(ip)?;
+        let decoded = self.try_region(ip, &block).ok()??;
+        let db_type = self.meta.db_type;
+        Some(MatchedEntry {
+            start: bytes_to_ip(&block.start_ip, db_type),
+            end: bytes_to_ip(&block.end_ip, db_type),
+            region: self.meta.filter_miss(Some(decoded.region))?,
+            db_type,
+        })
+    }
+
+    /// Search `ip`, returning the region and the prefix length of the smallest CIDR
+    /// network containing the matched range.
+    ///
+    /// For ranges that are not CIDR-aligned the containing network is wider than the
+    /// range itself; see `covering_prefix_len`.
+    ///
+    /// 查询 `ip`，返回区域及包含命中范围的最小 CIDR 网络前缀长度。范围未按 CIDR 对齐时，
+    /// 该网络会比范围本身更大，参见 `covering_prefix_len`。
+    pub fn search_with_prefix(&self, ip: IpAddr) -> Option<(String, u8)> {
+        let entry = self.lookup_entry(ip)?;
+        let prefix_len = entry.prefix_len();
+        Some((entry.region, prefix_len))
+    }
+
+    /// Returns true only if `ip` is matched by a single-address block (start == end == ip).
+    ///
+    /// 仅当 `ip` 命中的块为单地址块（起始 == 结束 == ip）时返回 true。
+    pub fn is_exact_match(&self, ip: IpAddr) -> bool {
+        let ip = self.meta.normalize_ip(ip);
+        self.locate(ip)
+            .is_some_and(|block| block.is_exact(&ip_to_bytes(ip), self.meta.db_type))
+    }
+
+    /// Report whether the IP is a start, end, or interior address of its covering block.
+    ///
+    /// Returns `None` if no block covers the IP.
+    ///
+    /// 判断 IP 是所在块的起始、结束还是内部地址；未覆盖时返回 `None`。
+    pub fn boundary_kind(&self, ip: IpAddr) -> Option<BoundaryKind> {
+        let ip = self.meta.normalize_ip(ip);
+        let block = self.locate(ip)?;
+        Some(block.boundary_kind(&ip_to_bytes(ip), self.meta.db_type))
+    }
+
+    fn locate(&self, ip: IpAddr) -> Option<IndexBlock> {
+        self.locate_counted(ip, &mut ())
+    }
+
+    fn locate_counted(&self, ip: IpAddr, iterations: &mut impl Iterations) -> Option<IndexBlock> {
+        self.try_locate(ip, iterations).ok().flatten()
+    }
+
+    fn try_locate(
+        &self,
+        ip: IpAddr,
+        iterations: &mut impl Iterations,
+    ) -> Result<Option<IndexBlock>, CzError> {
+        let ip = self.meta.normalize_ip(ip);
+        if !self.meta.db_type.compare(&ip) {
+            return Ok(None);
+        }
+        let ip_bytes = ip_to_bytes(ip);
+
+        let Some((sptr, eptr)) = self.meta.search_in_header(&ip_bytes, iterations) else {
+            return Ok(None);
+        };
+        let sptr = sptr as usize;
+        // The last partition is reported one block past its end; keep reads inside the index.
+        let eptr = (eptr as usize).min(self.meta.end_index as usize).max(sptr);
+
+        let blen = self.meta.db_type.index_block_len();
+        let db_type = self.meta.db_type;
+        self.with_index(sptr, eptr - sptr + blen, |index| {
+            find_index_block(index, &ip_bytes, db_type, iterations)
+                .map(|p| IndexBlock::read(index, p, db_type))
+        })
+    }
+
+    /// Run `f` over `len` index bytes at `offset`, served from the region cache when enabled.
+    fn with_index<T>(
+        &self,
+        offset: usize,
+        len: usize,
+        f: impl FnOnce(&[u8]) -> T,
+    ) -> Result<T, CzError> {
+        #[cfg(feature = "cache")]
+        if let Some(cache) = &self.region_cache {
+            let mut guard = cache.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Some(index) = guard.partitions.get(&(offset, len)) {
+                return Ok(f(index));
+            }
+            drop(guard);
+            let mut index = vec![0u8; len];
+            self.read_data(offset, &mut index).map_err(corrupt_on_eof)?;
+            let result = f(&index);
+            cache
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .partitions
+                .put((offset, len), index);
+            return Ok(result);
+        }
+        let mut index = vec![0u8; len];
+        self.read_data(offset, &mut index).map_err(corrupt_on_eof)?;
+        Ok(f(&index))
+    }
+
+    /// Iterate over every index block, decoding each record independently.
+    ///
+    /// Records whose region pointer falls outside the file yield
+    /// `Err(CzError::DatabaseFileCorrupted)`; records that fail to decode yield
+    /// `Err(CzError::CorruptRegion)` naming the data pointer and the malformed part.
+    /// Iteration continues with the next record either way.
+    ///
+    /// 遍历所有索引块并逐条解析。区域指针越界的记录返回 `Err(CzError::DatabaseFileCorrupted)`；
+    /// 无法解析的记录返回 `Err(CzError::CorruptRegion)`，包含数据指针及出错部分。
+    /// 两种情况都会继续处理下一条。
+    pub fn iter_checked(
+        &self,
+    ) -> impl Iterator<Item = Result<(IpAddr, IpAddr, String), CzError>> + '_ {
+        let blen = self.meta.db_type.index_block_len();
+        let start = self.meta.start_index as usize;
+        let end = self.meta.end_index as usize;
+        (start..=end).step_by(blen).map(move |p| self.read_entry(p))
+    }
+
+    fn read_entry(&self, p: usize) -> Result<(IpAddr, IpAddr, String), CzError> {
+        let db_type = self.meta.db_type;
+        let mut index_buffer = vec![0u8; db_type.index_block_len()];
+        self.read_data(p, &mut index_buffer)
+            .map_err(corrupt_on_eof)?;
+        let block = IndexBlock::read(&index_buffer, 0, db_type);
+        let mut region_bytes = vec![0u8; block.data_len];
+        self.read_data(block.data_ptr, &mut region_bytes)
+            .map_err(corrupt_on_eof)?;
+        let region = decode_region_at(
+            &region_bytes,
+            &self.meta,
+            self.meta.column_selection,
+            RecordSite::scan(&block),
+        )?;
+        Ok((
+            bytes_to_ip(&block.start_ip, db_type),
+            bytes_to_ip(&block.end_ip, db_type),
+            region,
+        ))
+    }
+
+    /// Read `buf.len()` bytes at `offset` within the data section.
+    fn read_data(&self, offset: usize, buf: &mut [u8]) -> io::Result<()> {
+        let pos = self.data_offset + offset as u64;
+        let Some(readahead) = self
+            .readahead
+            .as_ref()
+            .filter(|r| buf.len() as u64 <= r.size)
+        else {
+            return self.backing.read_exact_at(buf, pos);
+        };
+        let size = readahead.size;
+        let mut window = readahead
+            .window
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        let end = pos + buf.len() as u64;
+        let window_end = window.start + window.buffer.len() as u64;
+        if pos < window.start || end > window_end {
+            let mut start = pos - pos % size;
+            if start + size < end {
+                start = pos;
+            }
+            let len = size.min(self.file_len.saturating_sub(start));
+            if start + len < end {
+                return Err(io::ErrorKind::UnexpectedEof.into());
             }
+            window.buffer.resize(len as usize, 0);
+            if let Err(err) = self.backing.read_exact_at(&mut window.buffer, start) {
+                window.buffer.clear();
+                return Err(err);
+            }
+            window.start = start;
         }
 
-        None
+        let from = (pos - window.start) as usize;
+        buf.copy_from_slice(&window.buffer[from..from + buf.len()]);
+        Ok(())
     }
 
     /// Search a small batch of IP addresses.
     ///
     /// 批量查询 IP（小批量）。
-    pub fn search_many(&mut self, ips: &[IpAddr]) -> Vec<Option<String>> {
+    pub fn search_many(&self, ips: &[IpAddr]) -> Vec<Option<String>> {
         ips.iter().map(|ip| self.search(*ip)).collect()
     }
 
+    /// Export every entry as newline-delimited JSON (`{"start","end","region"}` per line).
+    ///
+    /// Stops at the first record that fails to decode.
+    ///
+    /// 将全部条目导出为 JSONL，每行一个 `{"start","end","region"}` 对象；
+    /// 遇到无法解析的记录时停止。
+    pub fn export_jsonl<W: Write>(&self, w: &mut W) -> Result<(), CzError> {
+        for entry in self.iter_checked() {
+            let (start, end, region) = entry?;
+            write_jsonl_entry(w, start, end, &region)?;
+        }
+        Ok(())
+    }
+
+    /// Lowest and highest addresses covered: the first block's start and the last block's end.
+    ///
+    /// 返回覆盖的最低与最高地址，即首个索引块的起始地址与最后一个索引块的结束地址。
+    pub fn address_bounds(&self) -> Option<(IpAddr, IpAddr)> {
+        let db_type = self.meta.db_type;
+        let mut buffer = vec![0u8; db_type.index_block_len()];
+        self.read_data(self.meta.start_index as usize, &mut buffer)
+            .ok()?;
+        let first = IndexBlock::read(&buffer, 0, db_type);
+        self.read_data(self.meta.end_index as usize, &mut buffer)
+            .ok()?;
+        let last = IndexBlock::read(&buffer, 0, db_type);
+        Some((
+            bytes_to_ip(&first.start_ip, db_type),
+            bytes_to_ip(&last.end_ip, db_type),
+        ))
+    }
+
+    /// Geo-map column mask; bit `n + 1` selects column `n`, bit 0 is reserved.
+    ///
+    /// 地理映射列掩码；第 `n + 1` 位选择第 `n` 列，第 0 位保留。
+    pub fn column_selection(&self) -> u64 {
+        self.meta.column_selection
+    }
+
+    /// Names of the selected geo-map columns (`country`, `province`, `city`, `district`,
+    /// `isp`), in the order `search` joins them. Columns the name table does not cover are
+    /// `"unknown"`; the record's inline data follows them in `search` results.
+    ///
+    /// 选中的地理映射列名称（`country`、`province`、`city`、`district`、`isp`），顺序与
+    /// `search` 拼接顺序一致；名称表未覆盖的列为 `"unknown"`。
+    /// `search` 结果中这些列之后为记录的内联数据。
+    pub fn selected_columns(&self) -> Vec<&'static str> {
+        self.meta.selected_columns()
+    }
+
+    /// Number of IP ranges (index blocks) in the database.
+    ///
+    /// 数据库中 IP 段（索引块）的数量。
+    pub fn entry_count(&self) -> usize {
+        self.meta.entry_count()
+    }
+
+    /// Returns the storage backend of this searcher.
+    ///
+    /// 返回该查询器的存储模式。
+    pub fn backend_kind(&self) -> BackendKind {
+        BackendKind::Disk
+    }
+
+    /// Attach a human-readable label, e.g. to identify this database in logs.
+    ///
+    /// 附加便于识别的标签，例如用于在日志中区分数据库。
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.meta.label = Some(label.into());
+        self
+    }
+
+    /// The label set with `with_label`, if any.
+    ///
+    /// 通过 `with_label` 设置的标签（若有）。
+    pub fn label(&self) -> Option<&str> {
+        self.meta.label.as_deref()
+    }
+
+    /// Inclusive address ranges covered by each header partition.
+    ///
+    /// 每个头部分区覆盖的地址闭区间。
+    pub fn header_partitions(&self) -> Vec<(IpAddr, IpAddr)> {
+        self.meta.header_partitions()
+    }
+
     /// Returns the database IP version.
     ///
     /// 返回数据库类型（IPv4 或 IPv6）。
     pub fn db_type(&self) -> DbType {
         self.meta.db_type
     }
+
+    /// Whether the database can answer queries for `ip`'s address family.
+    ///
+    /// 判断数据库能否查询 `ip` 所属的地址族。
+    pub fn supports(&self, ip: IpAddr) -> bool {
+        self.meta.db_type.compare(&self.meta.normalize_ip(ip))
+    }
+
+    /// The IP version this database answers for.
+    ///
+    /// 数据库支持查询的 IP 版本。
+    pub fn supported_version(&self) -> DbType {
+        self.meta.db_type
+    }
+
+    /// Format version, client id and expiry date read from the hyper header.
+    ///
+    /// 从超头读取的格式版本、客户端 ID 与到期日期。
+    pub fn info(&self) -> DbInfo {
+        self.meta.info()
+    }
+
+    /// Whether the database was past its expiry date when opened.
+    ///
+    /// Only ever true when opened with `CzdbOptions::allow_expired`.
+    ///
+    /// 打开时数据库是否已过期；仅在使用 `CzdbOptions::allow_expired` 打开时可能为 true。
+    pub fn is_expired(&self) -> bool {
+        self.meta.expired
+    }
+
+    /// Header geometry as `(padding_size, encrypted_block_size)`.
+    ///
+    /// The data section starts at `12 + padding_size + encrypted_block_size`.
+    ///
+    /// 返回头部布局 `(padding_size, encrypted_block_size)`；
+    /// 数据区起始于 `12 + padding_size + encrypted_block_size`。
+    pub fn header_geometry(&self) -> (u32, u32) {
+        self.meta.header_geometry()
+    }
+
+    /// Length in bytes of the data section, everything after the hyper header and padding.
+    ///
+    /// 数据区（超头与填充之后的全部内容）的字节长度。
+    pub fn data_section_len(&self) -> usize {
+        self.meta.file_size as usize
+    }
+}
+
+/// Decrypt the hyper header and parse the metadata, returning it with the data offset.
+///
+/// `reader` must be positioned at the start of the database.
+fn load_meta<R: Read + Seek>(
+    reader: &mut R,
+    file_len: u64,
+    key_bytes: &[u8],
+    options: CzdbOptions,
+) -> Result<(u64, DbMeta), CzError> {
+    let header = read_hyper_header(reader, file_len, key_bytes, &options)?;
+    let data_offset = header.data_offset();
+    if data_offset >= file_len {
+        return Err(CzError::NoDataSection);
+    }
+    let meta = parse_meta_from_file(reader, data_offset, file_len, &header, key_bytes, options)?;
+    if meta.options.strict {
+        let range = meta.index_range();
+        let mut index = vec![0u8; range.len()];
+        reader.seek(SeekFrom::Start(data_offset + range.start as u64))?;
+        reader.read_exact(&mut index).map_err(corrupt_on_eof)?;
+        check_index_order(&index, meta.db_type)?;
+    }
+    Ok((data_offset, meta))
+}
+
+/// Fill `buf` from `file` at `pos` without touching the shared cursor.
+#[cfg(unix)]
+fn read_exact_at(file: &File, buf: &mut [u8], pos: u64) -> io::Result<()> {
+    std::os::unix::fs::FileExt::read_exact_at(file, buf, pos)
+}
+
+/// Fill `buf` from `file` at `pos`; `seek_read` may return short reads, so loop.
+#[cfg(windows)]
+fn read_exact_at(file: &File, mut buf: &mut [u8], mut pos: u64) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !buf.is_empty() {
+        match file.seek_read(buf, pos) {
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => {
+                buf = &mut buf[n..];
+                pos += n as u64;
+            }
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+/// Fallback for targets without positional reads: seek and read under a process-wide lock.
+#[cfg(not(any(unix, windows)))]
+fn read_exact_at(file: &File, buf: &mut [u8], pos: u64) -> io::Result<()> {
+    static CURSOR: Mutex<()> = Mutex::new(());
+    let _guard = CURSOR.lock().unwrap_or_else(PoisonError::into_inner);
+    let mut file = file;
+    file.seek(SeekFrom::Start(pos))?;
+    file.read_exact(buf)
+}
+
+fn corrupt_on_eof(err: io::Error) -> CzError {
+    if err.kind() == io::ErrorKind::UnexpectedEof {
+        CzError::DatabaseFileCorrupted
+    } else {
+        CzError::DatabaseFileReadError(err)
+    }
+}
+
+impl Searcher for CzdbDisk {
+    fn lookup(&self, ip: IpAddr) -> Option<String> {
+        self.search(ip)
+    }
+
+    fn db_type(&self) -> DbType {
+        self.supported_version()
+    }
+
+    fn backend_kind(&self) -> BackendKind {
+        CzdbDisk::backend_kind(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        CzdbMemory, DefaultRegionDecoder, KeySourceErrorKind, RegionDecoder, RegionErrorKind,
+        fixture::{FixtureBuilder, TEST_KEY_BYTES, TempDb, geo_columns, region_payload, test_key},
+    };
+    use rmpv::Value;
+    use std::sync::Arc;
+
+    fn panic_report_fixture() -> FixtureBuilder {
+        FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.127.255.255", "A")
+            .block("1.128.0.0", "1.199.255.255", "B")
+            .block("1.200.0.0", "1.255.255.255", "C")
+            .block("2.0.0.0", "39.0.0.0", "D")
+            .block("39.0.0.1", "39.255.255.255", "E")
+            .block("40.0.0.0", "40.255.255.255", "F")
+            .block("41.0.0.0", "41.255.255.255", "G")
+            .header_every(2)
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn search_resolves_reported_ips_on_valid_header() {
+        let bytes = panic_report_fixture().build();
+        let tmp = TempDb::new(&bytes);
+        let db = CzdbDisk::open(tmp.path_str(), &test_key()).unwrap();
+        assert_eq!(db.search(ip("1.199.236.9")), Some("B".to_string()));
+        assert_eq!(db.search(ip("1.204.105.161")), Some("C".to_string()));
+        assert_eq!(db.search(ip("39.180.75.143")), Some("E".to_string()));
+        assert_eq!(db.search(ip("41.1.1.1")), Some("G".to_string()));
+    }
+
+    #[test]
+    fn readahead_matches_direct_reads() {
+        let bytes = panic_report_fixture().build();
+        let tmp = TempDb::new(&bytes);
+        let direct = CzdbDisk::open(tmp.path_str(), &test_key()).unwrap();
+        let buffered = CzdbDisk::open_with_readahead(tmp.path_str(), &test_key(), 1).unwrap();
+        for s in [
+            "1.0.0.0",
+            "1.199.236.9",
+            "1.204.105.161",
+            "39.180.75.143",
+            "40.0.0.1",
+            "41.255.255.255",
+            "42.0.0.1",
+            "0.0.0.1",
+        ] {
+            assert_eq!(buffered.search(ip(s)), direct.search(ip(s)), "{s}");
+        }
+    }
+
+    #[test]
+    fn iter_checked_reports_corrupt_records_and_continues() {
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.255.255.255", "A")
+            .block_payload("2.0.0.0", "2.255.255.255", vec![0xc1, 0xc1])
+            .block("3.0.0.0", "3.255.255.255", "C")
+            .build();
+        let tmp = TempDb::new(&bytes);
+        let db = CzdbDisk::open(tmp.path_str(), &test_key()).unwrap();
+        let results: Vec<_> = db.iter_checked().collect();
+        assert_eq!(results.len(), 3);
+        assert_eq!(
+            results[0].as_ref().unwrap(),
+            &(ip("1.0.0.0"), ip("1.255.255.255"), "A".to_string())
+        );
+        assert!(matches!(
+            results[1],
+            Err(CzError::CorruptRegion {
+                kind: RegionErrorKind::MissingGeoPosition,
+                ..
+            })
+        ));
+        assert_eq!(results[2].as_ref().unwrap().2, "C");
+    }
+
+    #[test]
+    fn iter_checked_distinguishes_region_failure_points() {
+        let mut truncated = region_payload(0, "truncated");
+        truncated.truncate(truncated.len() - 3);
+        let fixture = FixtureBuilder::new(DbType::Ipv4)
+            .block_payload("1.0.0.0", "1.255.255.255", vec![0xc1])
+            .block_payload("2.0.0.0", "2.255.255.255", truncated)
+            .block_payload("3.0.0.0", "3.255.255.255", region_payload(0x0300_0040, "x"))
+            .block("4.0.0.0", "4.255.255.255", "ok");
+        let bytes = fixture.build();
+        let region_ptr = |n| {
+            let pos = fixture.block_pos(n) + 8;
+            u32::from_le_bytes(bytes[pos..pos + 4].try_into().unwrap()) as usize
+        };
+        let tmp = TempDb::new(&bytes);
+        let db = CzdbDisk::open(tmp.path_str(), &test_key()).unwrap();
+        let errors: Vec<_> = db
+            .iter_checked()
+            .filter_map(|r| match r {
+                Err(CzError::CorruptRegion { data_ptr, kind }) => Some((data_ptr, kind)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                (region_ptr(0), RegionErrorKind::MissingGeoPosition),
+                (region_ptr(1), RegionErrorKind::MalformedOtherData),
+                (region_ptr(2), RegionErrorKind::InvalidGeoColumns),
+            ]
+        );
+    }
+
+    #[test]
+    fn try_search_separates_misses_from_corrupt_regions() {
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block_payload("1.0.0.0", "1.255.255.255", vec![0xc1])
+            .block("2.0.0.0", "2.255.255.255", "ok")
+            .build();
+        let tmp = TempDb::new(&bytes);
+        let db = CzdbDisk::open(tmp.path_str(), &test_key()).unwrap();
+        assert!(matches!(
+            db.try_search(ip("1.0.0.1")),
+            Err(CzError::CorruptRegion {
+                kind: RegionErrorKind::MissingGeoPosition,
+                ..
+            })
+        ));
+        assert_eq!(db.search(ip("1.0.0.1")), None);
+        assert_eq!(db.try_search(ip("2.0.0.1")).unwrap().as_deref(), Some("ok"));
+        assert_eq!(db.try_search(ip("9.0.0.1")).unwrap(), None);
+        #[cfg(feature = "mmap")]
+        {
+            let db = crate::CzdbMmap::open(tmp.path_str(), &test_key()).unwrap();
+            assert!(matches!(
+                db.try_search(ip("1.0.0.1")),
+                Err(CzError::CorruptRegion { .. })
+            ));
+            assert_eq!(db.try_search(ip("9.0.0.1")).unwrap(), None);
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn corrupt_records_are_traced_on_every_decode_path() {
+        use std::sync::Mutex;
+
+        #[derive(Clone, Default)]
+        struct Capture(Arc<Mutex<Vec<u8>>>);
+
+        impl io::Write for Capture {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block_payload("1.0.0.0", "1.255.255.255", vec![0xc1])
+            .block("2.0.0.0", "2.255.255.255", "ok")
+            .build();
+        let tmp = TempDb::new(&bytes);
+        let disk = CzdbDisk::open(tmp.path_str(), &test_key()).unwrap();
+        let Err(CzError::CorruptRegion { data_ptr, .. }) = disk.try_search(ip("1.0.0.1")) else {
+            panic!("expected a corrupt region");
+        };
+
+        let traced = |lookup: &dyn Fn()| {
+            let capture = Capture::default();
+            let writer = capture.clone();
+            let subscriber = tracing_subscriber::fmt()
+                .with_ansi(false)
+                .with_writer(move || writer.clone())
+                .finish();
+            tracing::subscriber::with_default(subscriber, lookup);
+            let logs = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+            assert!(logs.contains(&format!("data_ptr={data_ptr}")), "{logs}");
+        };
+        let target = ip("1.0.0.1");
+        traced(&|| assert!(disk.search_structured(target).is_none()));
+        traced(&|| assert!(disk.lookup_entry(target).is_none()));
+        traced(&|| assert!(disk.iter_checked().next().unwrap().is_err()));
+        #[cfg(feature = "mmap")]
+        {
+            let mmap = crate::CzdbMmap::open(tmp.path_str(), &test_key()).unwrap();
+            traced(&|| assert!(mmap.search_ref(target).is_none()));
+            traced(&|| assert!(mmap.search(target).is_none()));
+        }
+        traced(&|| assert!(CzdbMemory::from_bytes(bytes.clone(), &test_key()).is_err()));
+    }
+
+    #[test]
+    fn lazy_geo_map_resolves_columns_on_first_use() {
+        let mut geo_map = Vec::new();
+        let mix = geo_columns(&mut geo_map, &["中国", "广东", "深圳"]);
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block_payload("1.0.0.0", "1.255.255.255", region_payload(mix, "电信"))
+            .block("2.0.0.0", "2.255.255.255", "inline")
+            .geo_map(0b1110, geo_map)
+            .build();
+        let tmp = TempDb::new(&bytes);
+        let options = CzdbOptions::new().lazy_geo_map(true);
+        let db = CzdbDisk::open_with_options(tmp.path_str(), &test_key(), options).unwrap();
+        assert_eq!(db.search(ip("2.0.0.1")).as_deref(), Some("inline"));
+        assert_eq!(
+            db.search(ip("1.0.0.1")).as_deref(),
+            Some("中国\t广东\t深圳\t电信")
+        );
+
+        let (ptr, len) = db.geo_map_ref(ip("1.0.0.1")).unwrap();
+        assert_eq!((ptr as u64 | (len as u64) << 24), mix);
+        let raw = db.raw_geo_map().unwrap();
+        assert_eq!(ptr + len, raw.len());
+        assert_eq!(db.geo_map_ref(ip("2.0.0.1")), None);
+    }
+
+    #[test]
+    fn debug_output_redacts_the_key() {
+        let mut geo_map = Vec::new();
+        let mix = geo_columns(&mut geo_map, &["中国", "广东"]);
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block_payload("1.0.0.0", "1.255.255.255", region_payload(mix, "电信"))
+            .geo_map(0b110, geo_map)
+            .build();
+        let tmp = TempDb::new(&bytes);
+        let options = CzdbOptions::new().lazy_geo_map(true);
+        let disk = CzdbDisk::open_with_options(tmp.path_str(), &test_key(), options).unwrap();
+        let memory = CzdbMemory::open(tmp.path_str(), &test_key()).unwrap();
+
+        let key_bytes = format!("{:?}", TEST_KEY_BYTES.to_vec());
+        for debug in [format!("{disk:?}"), format!("{memory:?}")] {
+            assert!(debug.contains("<redacted>"));
+            assert!(!debug.contains(&key_bytes));
+            assert!(!debug.contains(&test_key()));
+        }
+    }
+
+    #[test]
+    fn search_other_skips_geo_columns() {
+        let mut geo_map = Vec::new();
+        let mix = geo_columns(&mut geo_map, &["中国", "广东"]);
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block_payload("1.0.0.0", "1.255.255.255", region_payload(mix, "电信"))
+            .block_payload(
+                "2.0.0.0",
+                "2.255.255.255",
+                region_payload(0xff_ffff, "联通"),
+            )
+            .block("3.0.0.0", "3.255.255.255", "inline")
+            .geo_map(0b110, geo_map)
+            .build();
+        let tmp = TempDb::new(&bytes);
+        let db = CzdbDisk::open(tmp.path_str(), &test_key()).unwrap();
+        assert_eq!(db.search_other(ip("1.0.0.1")).as_deref(), Some("电信"));
+        assert_eq!(db.search(ip("2.0.0.1")), None);
+        assert_eq!(db.search_other(ip("2.0.0.1")).as_deref(), Some("联通"));
+        assert_eq!(db.search_other(ip("3.0.0.1")).as_deref(), Some("inline"));
+        assert_eq!(db.search_other(ip("9.0.0.1")), None);
+    }
+
+    #[test]
+    fn search_raw_returns_every_column_and_other_data() {
+        let mut geo_map = Vec::new();
+        let mix = geo_columns(&mut geo_map, &["中国", "广东"]);
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block_payload("1.0.0.0", "1.255.255.255", region_payload(mix, "电信"))
+            .block("2.0.0.0", "2.255.255.255", "inline")
+            .geo_map(0b010, geo_map)
+            .build();
+        let tmp = TempDb::new(&bytes);
+        let db = CzdbDisk::open(tmp.path_str(), &test_key()).unwrap();
+        assert_eq!(
+            db.search_raw(ip("1.0.0.1")),
+            Some(Value::Array(vec![
+                "中国".into(),
+                "广东".into(),
+                "电信".into()
+            ]))
+        );
+        assert_eq!(
+            db.search_raw(ip("2.0.0.1")),
+            Some(Value::Array(vec!["inline".into()]))
+        );
+        assert_eq!(db.search_raw(ip("9.0.0.1")), None);
+    }
+
+    #[test]
+    fn column_selection_bit_zero_is_reserved() {
+        let open = |column_selection| {
+            let mut geo_map = Vec::new();
+            let mix = geo_columns(&mut geo_map, &["中国", "广东"]);
+            let bytes = FixtureBuilder::new(DbType::Ipv4)
+                .block_payload("1.0.0.0", "1.255.255.255", region_payload(mix, "电信"))
+                .block("2.0.0.0", "2.255.255.255", "inline")
+                .geo_map(column_selection, geo_map)
+                .build();
+            let tmp = TempDb::new(&bytes);
+            CzdbDisk::open(tmp.path_str(), &test_key())
+                .unwrap()
+                .search(ip("1.0.0.1"))
+        };
+        assert_eq!(open(0b001).as_deref(), Some("电信"));
+        assert_eq!(open(0b010).as_deref(), Some("中国\t电信"));
+        assert_eq!(open(0b111).as_deref(), Some("中国\t广东\t电信"));
+    }
+
+    #[test]
+    fn header_geometry_locates_data_section() {
+        let fixture = panic_report_fixture();
+        let tmp = TempDb::new(&fixture.build());
+        let db = CzdbDisk::open(tmp.path_str(), &test_key()).unwrap();
+        let (padding, encrypted) = db.header_geometry();
+        assert_eq!(12 + padding + encrypted, fixture.data_offset() as u32);
+        assert_eq!(db.data_offset, fixture.data_offset() as u64);
+        assert_eq!(db.data_section_len() as u64, db.file_len - db.data_offset);
+    }
+
+    #[test]
+    fn search_or_treats_blank_region_as_missing() {
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.255.255.255", "  ")
+            .block("2.0.0.0", "2.255.255.255", "beijing")
+            .build();
+        let tmp = TempDb::new(&bytes);
+        let db = CzdbDisk::open(tmp.path_str(), &test_key()).unwrap();
+        assert_eq!(db.search_or(ip("1.0.0.1"), "-"), "-");
+        assert_eq!(db.search_or(ip("2.0.0.1"), "-"), "beijing");
+        assert_eq!(db.search_or(ip("3.0.0.1"), "-"), "-");
+    }
+
+    #[test]
+    fn strict_rejects_overlapping_index_blocks() {
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.255.255.255", "a")
+            .block("1.128.0.0", "2.255.255.255", "b")
+            .build();
+        let tmp = TempDb::new(&bytes);
+        assert!(CzdbDisk::open(tmp.path_str(), &test_key()).is_ok());
+        let strict = CzdbOptions::new().strict(true);
+        assert!(matches!(
+            CzdbDisk::open_with_options(tmp.path_str(), &test_key(), strict),
+            Err(CzError::DatabaseFileCorrupted)
+        ));
+    }
+
+    #[test]
+    fn region_decoder_replaces_column_handling() {
+        #[derive(Debug)]
+        struct Reversed;
+        impl RegionDecoder for Reversed {
+            fn decode(&self, geo_map_array: &Value, _: u64, other_data: &str) -> String {
+                let mut parts: Vec<_> = geo_map_array
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .collect();
+                parts.reverse();
+                parts.push(other_data);
+                parts.join("/")
+            }
+        }
+
+        let mut geo_map = Vec::new();
+        let mix = geo_columns(&mut geo_map, &["中国", "广东", "深圳"]);
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block_payload("1.0.0.0", "1.255.255.255", region_payload(mix, "电信"))
+            .block("2.0.0.0", "2.255.255.255", "inline")
+            .geo_map(0b0110, geo_map)
+            .build();
+        let tmp = TempDb::new(&bytes);
+        let default = CzdbDisk::open_with_options(
+            tmp.path_str(),
+            &test_key(),
+            CzdbOptions::new().region_decoder(DefaultRegionDecoder),
+        )
+        .unwrap();
+        assert_eq!(
+            default.search(ip("1.0.0.1")).as_deref(),
+            Some("中国\t广东\t电信")
+        );
+
+        let options = CzdbOptions::new().region_decoder(Reversed);
+        let db = CzdbDisk::open_with_options(tmp.path_str(), &test_key(), options).unwrap();
+        assert_eq!(
+            db.search(ip("1.0.0.1")).as_deref(),
+            Some("深圳/广东/中国/电信")
+        );
+        assert_eq!(db.search(ip("2.0.0.1")).as_deref(), Some("inline"));
+    }
+
+    #[test]
+    fn is_exact_match_detects_single_host_blocks() {
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.0.0.9", "range")
+            .block("1.0.0.10", "1.0.0.10", "host")
+            .block("1.0.0.11", "1.0.0.255", "range")
+            .build();
+        let tmp = TempDb::new(&bytes);
+        let db = CzdbDisk::open(tmp.path_str(), &test_key()).unwrap();
+        assert!(db.is_exact_match(ip("1.0.0.10")));
+        assert!(!db.is_exact_match(ip("1.0.0.9")));
+        assert!(!db.is_exact_match(ip("9.0.0.1")));
+    }
+
+    #[test]
+    fn address_bounds_read_first_and_last_blocks() {
+        let tmp = TempDb::new(&panic_report_fixture().build());
+        let db = CzdbDisk::open(tmp.path_str(), &test_key()).unwrap();
+        assert_eq!(
+            db.address_bounds(),
+            Some((ip("1.0.0.0"), ip("41.255.255.255")))
+        );
+    }
+
+    #[test]
+    fn header_only_file_reports_missing_data_section() {
+        let fixture = panic_report_fixture();
+        let bytes = fixture.build();
+        let tmp = TempDb::new(&bytes[..fixture.data_offset()]);
+        assert!(matches!(
+            CzdbDisk::open(tmp.path_str(), &test_key()),
+            Err(CzError::NoDataSection)
+        ));
+    }
+
+    #[test]
+    fn search_instrumented_counts_header_and_partition_steps() {
+        // Seven /8 blocks in one header partition: two header steps, then a partition search
+        // that hits block 3 first, block 1 second and block 0 third.
+        let fixture = (1..=7).fold(
+            FixtureBuilder::new(DbType::Ipv4).header_every(7),
+            |fixture, n| fixture.block(&format!("{n}.0.0.0"), &format!("{n}.255.255.255"), "r"),
+        );
+        let tmp = TempDb::new(&fixture.build());
+        let db = CzdbDisk::open(tmp.path_str(), &test_key()).unwrap();
+        for (addr, expected) in [("4.1.2.3", 3), ("2.1.2.3", 4), ("1.1.2.3", 5)] {
+            let (region, iterations) = db.search_instrumented(ip(addr));
+            assert_eq!(region.as_deref(), Some("r"));
+            assert_eq!(iterations, expected, "{addr}");
+        }
+    }
+
+    #[test]
+    fn entry_count_matches_memory_backend() {
+        let fixture = panic_report_fixture();
+        let bytes = fixture.build();
+        let tmp = TempDb::new(&bytes);
+        let db = CzdbDisk::open(tmp.path_str(), &test_key()).unwrap();
+        let memory = CzdbMemory::from_bytes(bytes, &test_key()).unwrap();
+        assert_eq!(db.entry_count(), 7);
+        assert_eq!(memory.entry_count(), db.entry_count());
+    }
+
+    #[test]
+    fn shared_arc_is_queried_concurrently() {
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.255.255.255", "beijing")
+            .block("2.0.0.0", "2.255.255.255", "shanghai")
+            .build();
+        let tmp = TempDb::new(&bytes);
+        for kib in [0, 4] {
+            let db = CzdbDisk::open_with_readahead(tmp.path_str(), &test_key(), kib)
+                .unwrap()
+                .shared();
+            let handles: Vec<_> = (0..8)
+                .map(|i| {
+                    let db = Arc::clone(&db);
+                    std::thread::spawn(move || {
+                        let (ip, expected) = if i % 2 == 0 {
+                            ("1.2.3.4", "beijing")
+                        } else {
+                            ("2.3.4.5", "shanghai")
+                        };
+                        for _ in 0..100 {
+                            assert_eq!(db.search(ip.parse().unwrap()).as_deref(), Some(expected));
+                        }
+                    })
+                })
+                .collect();
+            for handle in handles {
+                handle.join().unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn reference_date_decides_expiry() {
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.255.255.255", "beijing")
+            .block("2.0.0.0", "2.255.255.255", "shanghai")
+            .expiry(250101)
+            .build();
+        let tmp = TempDb::new(&bytes);
+        let open = |y, m, d| {
+            let today = NaiveDate::from_ymd_opt(y, m, d).unwrap();
+            CzdbDisk::open_with_reference_date(tmp.path_str(), &test_key(), today)
+        };
+        assert!(open(2025, 1, 1).is_ok());
+        assert!(matches!(open(2025, 1, 2), Err(CzError::DatabaseExpired)));
+    }
+
+    #[test]
+    fn key_is_read_from_env_or_file() {
+        let tmp = TempDb::new(&panic_report_fixture().build());
+        let key_file = TempDb::new(format!("  {}\n", test_key()).as_bytes());
+        let db = CzdbDisk::open_with_key_file(tmp.path_str(), key_file.path_str()).unwrap();
+        assert_eq!(db.search(ip("1.0.0.1")).as_deref(), Some("A"));
+
+        let key = crate::common::key_from_env_with("CZDB_KEY", |name| {
+            (name == "CZDB_KEY").then(test_key)
+        })
+        .unwrap();
+        let db = CzdbDisk::open(tmp.path_str(), &key).unwrap();
+        assert_eq!(db.search(ip("1.0.0.1")).as_deref(), Some("A"));
+
+        assert!(matches!(
+            CzdbDisk::open_with_key_env(tmp.path_str(), "CZDB_DISK_TEST_KEY_UNSET"),
+            Err(CzError::KeySourceError(KeySourceErrorKind::MissingEnvVar { name }))
+                if name == "CZDB_DISK_TEST_KEY_UNSET"
+        ));
+        assert!(matches!(
+            CzdbDisk::open_with_key_file(tmp.path_str(), "/nonexistent/czdb.key"),
+            Err(CzError::KeySourceError(KeySourceErrorKind::UnreadableFile { source, .. }))
+                if source.kind() == io::ErrorKind::NotFound
+        ));
+    }
+
+    #[test]
+    fn open_with_keys_uses_the_first_key_that_fits() {
+        let tmp = TempDb::new(&panic_report_fixture().build());
+        let (old, new) = ("ZmVkY2JhOTg3NjU0MzIxMA==", test_key());
+        let db = CzdbDisk::open_with_keys(tmp.path_str(), &[old, &new]).unwrap();
+        assert_eq!(db.search(ip("1.0.0.1")).as_deref(), Some("A"));
+
+        let err = CzdbDisk::open_with_keys(tmp.path_str(), &[old, old]).unwrap_err();
+        assert!(matches!(
+            &err,
+            CzError::NoMatchingKey { tried: 2, last } if matches!(**last, CzError::DecryptionError)
+        ));
+        assert!(err.to_string().contains("2 keys"), "{err}");
+        assert!(matches!(
+            CzdbDisk::open_with_keys(tmp.path_str(), &[]),
+            Err(CzError::NoMatchingKey { tried: 0, .. })
+        ));
+        assert!(matches!(
+            CzdbDisk::open_with_keys("/nonexistent/czdb.db", &[old, &new]),
+            Err(CzError::DatabaseFileReadError(_))
+        ));
+    }
+
+    #[test]
+    fn open_with_keys_reports_a_corrupt_file_rather_than_a_key_mismatch() {
+        let fixture = panic_report_fixture();
+        let mut bytes = fixture.build();
+        let pos = fixture.data_offset() + 9;
+        bytes[pos..pos + 4].copy_from_slice(&7u32.to_le_bytes());
+        let tmp = TempDb::new(&bytes);
+        let old = "ZmVkY2JhOTg3NjU0MzIxMA==";
+        assert!(matches!(
+            CzdbDisk::open_with_keys(tmp.path_str(), &[&test_key(), old]),
+            Err(CzError::NoMatchingKey { tried: 2, last })
+                if matches!(*last, CzError::DatabaseFileCorrupted)
+        ));
+    }
+
+    #[test]
+    fn open_with_keys_skips_keys_that_fail_after_decryption() {
+        use base64::{Engine, engine::general_purpose};
+
+        let tmp = TempDb::new(&panic_report_fixture().build());
+        // A wrong key occasionally yields valid padding; the header parse must reject it.
+        let lucky = (0u32..)
+            .map(|i| general_purpose::STANDARD.encode(format!("wrong-key-{i:06}")))
+            .find(|key| {
+                !matches!(
+                    CzdbDisk::open(tmp.path_str(), key),
+                    Err(CzError::DecryptionError)
+                )
+            })
+            .unwrap();
+        assert!(CzdbDisk::open(tmp.path_str(), &lucky).is_err());
+        let db = CzdbDisk::open_with_keys(tmp.path_str(), &[&lucky, &test_key()]).unwrap();
+        assert_eq!(db.search(ip("1.0.0.1")).as_deref(), Some("A"));
+
+        let expired = TempDb::new(&panic_report_fixture().expiry(200101).build());
+        assert!(matches!(
+            CzdbDisk::open_with_keys(expired.path_str(), &[&test_key(), &lucky]),
+            Err(CzError::DatabaseExpired)
+        ));
+    }
+
+    #[test]
+    fn raw_key_bytes_open_like_base64_keys() {
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.255.255.255", "beijing")
+            .block("2.0.0.0", "2.255.255.255", "shanghai")
+            .build();
+        let tmp = TempDb::new(&bytes);
+        let mut db = CzdbDisk::open_with_key_bytes(tmp.path_str(), &TEST_KEY_BYTES).unwrap();
+        assert_eq!(
+            db.search("2.3.4.5".parse().unwrap()).as_deref(),
+            Some("shanghai")
+        );
+        db.reload().unwrap();
+        assert_eq!(
+            db.search("1.2.3.4".parse().unwrap()).as_deref(),
+            Some("beijing")
+        );
+
+        let memory = CzdbMemory::open_with_key_bytes(tmp.path_str(), &TEST_KEY_BYTES).unwrap();
+        assert_eq!(
+            memory.search_ref("2.3.4.5".parse().unwrap()),
+            Some("shanghai")
+        );
+        assert!(matches!(
+            CzdbDisk::open_with_key_bytes(tmp.path_str(), &TEST_KEY_BYTES[..8]),
+            Err(CzError::InvalidAesKeyLength(8))
+        ));
+        assert!(matches!(
+            CzdbMemory::open_with_key_bytes(tmp.path_str(), &[0; 17]),
+            Err(CzError::InvalidAesKeyLength(17))
+        ));
+    }
+
+    #[cfg(feature = "cache")]
+    #[test]
+    fn region_cache_serves_repeat_lookups_without_reads() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct CountingReader {
+            inner: io::Cursor<Vec<u8>>,
+            reads: Arc<AtomicUsize>,
+        }
+        impl Read for CountingReader {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                self.reads.fetch_add(1, Ordering::Relaxed);
+                self.inner.read(buf)
+            }
+        }
+        impl Seek for CountingReader {
+            fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+                self.inner.seek(pos)
+            }
+        }
+
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.255.255.255", "beijing")
+            .block("2.0.0.0", "2.255.255.255", "shanghai")
+            .build();
+        let reads = Arc::new(AtomicUsize::new(0));
+        let reader = CountingReader {
+            inner: io::Cursor::new(bytes),
+            reads: Arc::clone(&reads),
+        };
+        let options = CzdbOptions::new().region_cache(4);
+        let db = CzdbDisk::from_reader_with_options(reader, &test_key(), options).unwrap();
+        let reads_during = |lookup: &dyn Fn()| {
+            let before = reads.load(Ordering::Relaxed);
+            lookup();
+            reads.load(Ordering::Relaxed) - before
+        };
+        let search = || assert_eq!(db.search(ip("1.2.3.4")).as_deref(), Some("beijing"));
+        assert!(reads_during(&search) > 0);
+        assert_eq!(reads_during(&search), 0);
+        assert_eq!(
+            reads_during(&|| {
+                assert!(matches!(db.try_search(ip("1.9.9.9")), Ok(Some(_))));
+                assert_eq!(
+                    db.query(ip("1.3.3.3")),
+                    QueryResult::Found("beijing".into())
+                );
+                let region = db.search_structured(ip("1.4.4.4")).unwrap();
+                assert_eq!(region.raw, "beijing");
+                assert_eq!(db.lookup_entry(ip("1.5.5.5")).unwrap().region, "beijing");
+            }),
+            0
+        );
+        assert!(reads_during(&|| assert!(db.search(ip("2.2.2.2")).is_some())) > 0);
+    }
+
+    #[test]
+    fn from_reader_queries_an_in_memory_cursor() {
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.255.255.255", "beijing")
+            .block("2.0.0.0", "2.255.255.255", "shanghai")
+            .build();
+        for kib in [0, 4] {
+            let options = CzdbOptions::new().readahead_kib(kib);
+            let mut db = CzdbDisk::from_reader_with_options(
+                io::Cursor::new(bytes.clone()),
+                &test_key(),
+                options,
+            )
+            .unwrap();
+            assert_eq!(
+                db.search("2.3.4.5".parse().unwrap()).as_deref(),
+                Some("shanghai")
+            );
+            assert_eq!(
+                db.search("1.2.3.4".parse().unwrap()).as_deref(),
+                Some("beijing")
+            );
+            assert_eq!(db.search("9.9.9.9".parse().unwrap()), None);
+            assert!(matches!(
+                db.reload(),
+                Err(CzError::DatabaseFileReadError(err)) if err.kind() == io::ErrorKind::Unsupported
+            ));
+        }
+
+        let memory = CzdbMemory::from_reader(bytes.as_slice(), &test_key()).unwrap();
+        assert_eq!(
+            memory.search_ref("2.3.4.5".parse().unwrap()),
+            Some("shanghai")
+        );
+    }
+
+    #[test]
+    fn boxed_searchers_share_one_interface() {
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.255.255.255", "beijing")
+            .block("2.0.0.0", "2.255.255.255", "shanghai")
+            .build();
+        let tmp = TempDb::new(&bytes);
+        let searchers: Vec<Box<dyn Searcher>> = vec![
+            Box::new(CzdbDisk::open(tmp.path_str(), &test_key()).unwrap()),
+            Box::new(CzdbMemory::from_bytes(bytes, &test_key()).unwrap()),
+        ];
+        for searcher in &searchers {
+            assert_eq!(searcher.db_type(), DbType::Ipv4);
+            assert_eq!(
+                searcher.lookup("2.3.4.5".parse().unwrap()).as_deref(),
+                Some("shanghai")
+            );
+            assert_eq!(searcher.lookup("9.9.9.9".parse().unwrap()), None);
+        }
+        assert_eq!(searchers[0].backend_kind(), BackendKind::Disk);
+        assert_eq!(searchers[1].backend_kind(), BackendKind::Memory);
+    }
+
+    #[test]
+    fn region_pointer_past_end_of_data_is_a_miss() {
+        let fixture = panic_report_fixture();
+        let mut bytes = fixture.build();
+        let pos = fixture.block_pos(1) + 8;
+        let past_end = (bytes.len() - fixture.data_offset()) as u32 - 2;
+        bytes[pos..pos + 4].copy_from_slice(&past_end.to_le_bytes());
+        let tmp = TempDb::new(&bytes);
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+
+        let db = CzdbDisk::open(tmp.path_str(), &test_key()).unwrap();
+        assert_eq!(db.search(ip("1.130.0.1")), None);
+        assert!(matches!(
+            db.try_search(ip("1.130.0.1")),
+            Err(CzError::DatabaseFileCorrupted)
+        ));
+        assert_eq!(db.search(ip("1.0.0.1")).as_deref(), Some("A"));
+        assert!(matches!(
+            db.iter_checked().nth(1),
+            Some(Err(CzError::DatabaseFileCorrupted))
+        ));
+        #[cfg(feature = "mmap")]
+        {
+            let db = crate::CzdbMmap::open(tmp.path_str(), &test_key()).unwrap();
+            assert_eq!(db.search(ip("1.130.0.1")), None);
+            assert!(matches!(
+                db.try_search(ip("1.130.0.1")),
+                Err(CzError::DatabaseFileCorrupted)
+            ));
+            assert_eq!(db.search(ip("1.0.0.1")).as_deref(), Some("A"));
+        }
+        assert!(matches!(
+            CzdbMemory::from_bytes(bytes, &test_key()),
+            Err(CzError::DatabaseFileCorrupted)
+        ));
+    }
+
+    #[test]
+    fn inflated_header_block_size_is_rejected() {
+        let fixture = panic_report_fixture();
+        let mut bytes = fixture.build();
+        let pos = fixture.data_offset() + 9;
+        bytes[pos..pos + 4].copy_from_slice(&(u32::MAX / 20 * 20).to_le_bytes());
+        let tmp = TempDb::new(&bytes);
+        assert!(matches!(
+            CzdbDisk::open(tmp.path_str(), &test_key()),
+            Err(CzError::DatabaseFileCorrupted)
+        ));
+        assert!(matches!(
+            CzdbMemory::from_bytes(bytes, &test_key()),
+            Err(CzError::DatabaseFileCorrupted)
+        ));
+    }
+
+    #[test]
+    fn overflowing_end_index_is_rejected() {
+        let fixture = panic_report_fixture();
+        let mut bytes = fixture.build();
+        let pos = fixture.data_offset() + 13;
+        bytes[pos..pos + 4].copy_from_slice(&(u32::MAX - 3).to_le_bytes());
+        let tmp = TempDb::new(&bytes);
+        assert!(matches!(
+            CzdbDisk::open(tmp.path_str(), &test_key()),
+            Err(CzError::DatabaseFileCorrupted)
+        ));
+        assert!(matches!(
+            CzdbMemory::from_bytes(bytes, &test_key()),
+            Err(CzError::DatabaseFileCorrupted)
+        ));
+    }
+
+    #[test]
+    fn oversized_geo_map_is_rejected_before_allocating() {
+        let mut geo_map = Vec::new();
+        let mix = geo_columns(&mut geo_map, &["中国", "广东"]);
+        let fixture = FixtureBuilder::new(DbType::Ipv4)
+            .block_payload("1.0.0.0", "1.255.255.255", region_payload(mix, "电信"))
+            .geo_map(0b110, geo_map);
+        let mut bytes = fixture.build();
+        let offset = fixture.data_offset();
+        let end_index = u32::from_le_bytes(bytes[offset + 13..offset + 17].try_into().unwrap());
+        let pos = offset + end_index as usize + DbType::Ipv4.index_block_len() + 4;
+        bytes[pos..pos + 4].copy_from_slice(&0xf000_0000u32.to_le_bytes());
+        let tmp = TempDb::new(&bytes);
+        assert!(matches!(
+            CzdbDisk::open(tmp.path_str(), &test_key()),
+            Err(CzError::DatabaseFileCorrupted)
+        ));
+        assert!(matches!(
+            CzdbMemory::from_bytes(bytes, &test_key()),
+            Err(CzError::DatabaseFileCorrupted)
+        ));
+    }
+
+    #[test]
+    fn reload_swaps_in_new_file_and_keeps_old_state_on_failure() {
+        let build = |region| {
+            FixtureBuilder::new(DbType::Ipv4)
+                .block("1.0.0.0", "1.255.255.255", region)
+                .block("2.0.0.0", "2.255.255.255", "other")
+                .build()
+        };
+        let tmp = TempDb::new(&build("before"));
+        let mut db = CzdbDisk::open(tmp.path_str(), &test_key())
+            .unwrap()
+            .with_label("primary");
+
+        // Replace the file by rename, as a hot update would; the old handle keeps the old inode.
+        let replace = |bytes: &[u8]| {
+            let staged = format!("{}.new", tmp.path_str());
+            std::fs::write(&staged, bytes).unwrap();
+            std::fs::rename(&staged, tmp.path_str()).unwrap();
+        };
+        replace(&build("after"));
+        db.reload().unwrap();
+        assert_eq!(db.search(ip("1.0.0.1")).as_deref(), Some("after"));
+        assert_eq!(db.label(), Some("primary"));
+
+        replace(b"not a database");
+        assert!(db.reload().is_err());
+        assert_eq!(db.search(ip("1.0.0.1")).as_deref(), Some("after"));
+        assert_eq!(db.label(), Some("primary"));
+    }
+
+    #[test]
+    fn query_distinguishes_outcomes_on_every_backend() {
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.255.255.255", " ")
+            .block("2.0.0.0", "2.255.255.255", "beijing")
+            .block("3.0.0.0", "3.255.255.255", "reserved")
+            .build();
+        let tmp = TempDb::new(&bytes);
+        let options = || CzdbOptions::new().treat_as_miss(["reserved"]);
+        let expected = [
+            ("::1", QueryResult::WrongVersion),
+            ("9.0.0.1", QueryResult::NotCovered),
+            ("1.0.0.1", QueryResult::CoveredEmpty),
+            ("3.0.0.1", QueryResult::CoveredEmpty),
+            ("2.0.0.1", QueryResult::Found("beijing".to_string())),
+        ];
+
+        let db = CzdbDisk::open_with_options(tmp.path_str(), &test_key(), options()).unwrap();
+        for (addr, outcome) in &expected {
+            assert_eq!(&db.query(ip(addr)), outcome, "disk {addr}");
+        }
+        #[cfg(feature = "mmap")]
+        {
+            let db =
+                crate::CzdbMmap::open_with_options(tmp.path_str(), &test_key(), options()).unwrap();
+            for (addr, outcome) in &expected {
+                assert_eq!(&db.query(ip(addr)), outcome, "mmap {addr}");
+            }
+        }
+        let db = CzdbMemory::from_bytes_with_options(bytes, &test_key(), options()).unwrap();
+        for (addr, outcome) in &expected {
+            assert_eq!(&db.query(ip(addr)), outcome, "memory {addr}");
+        }
+    }
+
+    #[test]
+    fn treat_as_miss_applies_to_every_lookup_on_every_backend() {
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.255.255.255", "保留地址")
+            .block("2.0.0.0", "2.255.255.255", "beijing")
+            .build();
+        let tmp = TempDb::new(&bytes);
+        let options = || CzdbOptions::new().treat_as_miss(["保留地址"]);
+        let (miss, hit) = (ip("1.0.0.1"), ip("2.0.0.1"));
+
+        let db = CzdbDisk::open_with_options(tmp.path_str(), &test_key(), options()).unwrap();
+        assert_eq!(db.search(miss), None);
+        assert!(db.search_structured(miss).is_none());
+        assert_eq!(db.search_instrumented(miss).0, None);
+        assert_eq!(db.search_lang(miss, "zh"), None);
+        assert!(db.lookup_entry(miss).is_none());
+        assert_eq!(db.search_with_prefix(miss), None);
+        assert_eq!(db.query(miss), QueryResult::CoveredEmpty);
+        assert!(db.lookup_entry(hit).is_some());
+
+        #[cfg(feature = "mmap")]
+        {
+            let db =
+                crate::CzdbMmap::open_with_options(tmp.path_str(), &test_key(), options()).unwrap();
+            assert_eq!(db.search(miss), None);
+            assert_eq!(db.search_ref(miss), None);
+            assert!(db.search_structured(miss).is_none());
+            assert_eq!(db.search_instrumented(miss).0, None);
+            assert_eq!(db.search_lang(miss, "zh"), None);
+            assert!(db.lookup_entry(miss).is_none());
+            assert_eq!(db.query(miss), QueryResult::CoveredEmpty);
+            assert!(db.lookup_entry(hit).is_some());
+        }
+
+        let db =
+            crate::CzdbBorrowed::from_bytes_with_options(&bytes, &test_key(), options()).unwrap();
+        assert_eq!(db.search(miss), None);
+        assert_eq!(db.search_ref(miss), None);
+        assert!(db.lookup_entry(miss).is_none());
+        assert!(db.lookup_entry(hit).is_some());
+
+        let db = CzdbMemory::from_bytes_with_options(bytes, &test_key(), options()).unwrap();
+        assert_eq!(db.search(miss), None);
+        assert_eq!(db.search_ref(miss), None);
+        assert!(db.search_structured(miss).is_none());
+        assert_eq!(db.search_instrumented(miss).0, None);
+        assert!(db.lookup_entry(miss).is_none());
+        assert_eq!(db.search_with_prefix(miss), None);
+        assert!(db.lookup_entry(hit).is_some());
+    }
+
+    #[test]
+    fn open_with_timeout_returns_database() {
+        let bytes = panic_report_fixture().build();
+        let tmp = TempDb::new(&bytes);
+        let db = CzdbDisk::open_with_timeout(tmp.path_str(), &test_key(), Duration::from_secs(30))
+            .unwrap();
+        assert_eq!(db.search(ip("1.199.236.9")), Some("B".to_string()));
+    }
+
+    #[test]
+    fn invalid_utf8_regions_are_counted_and_recoverable() {
+        let mut payload = region_payload(0, "");
+        payload.truncate(payload.len() - 1);
+        payload.extend_from_slice(&[0xa3, 0xff, 0xfe, b'A']);
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block_payload("1.0.0.0", "1.255.255.255", payload)
+            .block("2.0.0.0", "2.255.255.255", "valid")
+            .build();
+        let tmp = TempDb::new(&bytes);
+
+        let db = CzdbDisk::open(tmp.path_str(), &test_key()).unwrap();
+        assert_eq!(db.search(ip("1.0.0.1")).as_deref(), Some("null"));
+        assert_eq!(db.invalid_utf8_count(), 1);
+        assert_eq!(
+            db.search_raw_bytes(ip("1.0.0.1")),
+            Some(vec![0xff, 0xfe, b'A'])
+        );
+
+        let options = CzdbOptions::new().lossy_utf8(true);
+        let lossy = CzdbDisk::open_with_options(tmp.path_str(), &test_key(), options).unwrap();
+        assert_eq!(
+            lossy.search(ip("1.0.0.1")).as_deref(),
+            Some("\u{fffd}\u{fffd}A")
+        );
+    }
+
+    #[test]
+    fn default_decoder_matches_built_in_geo_map_handling() {
+        // ["中国", <invalid UTF-8>, "深圳"]
+        let mut geo_map = vec![0x93, 0xa6];
+        geo_map.extend_from_slice("中国".as_bytes());
+        geo_map.extend_from_slice(&[0xa3, 0xff, 0xfe, b'A', 0xa6]);
+        geo_map.extend_from_slice("深圳".as_bytes());
+        let mix = (geo_map.len() as u64) << 24;
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block_payload("1.0.0.0", "1.255.255.255", region_payload(mix, "电信"))
+            .geo_map(0b1110, geo_map)
+            .build();
+        let tmp = TempDb::new(&bytes);
+
+        for lossy in [false, true] {
+            let options = CzdbOptions::new().lossy_utf8(lossy);
+            let built_in =
+                CzdbDisk::open_with_options(tmp.path_str(), &test_key(), options.clone()).unwrap();
+            let explicit = CzdbDisk::open_with_options(
+                tmp.path_str(),
+                &test_key(),
+                options.region_decoder(DefaultRegionDecoder),
+            )
+            .unwrap();
+            let region = built_in.search(ip("1.0.0.1"));
+            assert_eq!(region, explicit.search(ip("1.0.0.1")));
+            assert_eq!(built_in.invalid_utf8_count(), 1);
+            assert_eq!(explicit.invalid_utf8_count(), 1);
+            let expected = if lossy {
+                "中国\t\u{fffd}\u{fffd}A\t深圳\t电信"
+            } else {
+                "中国\tnull\t深圳\t电信"
+            };
+            assert_eq!(region.as_deref(), Some(expected));
+        }
+    }
+
+    #[test]
+    fn search_lang_selects_registered_columns() {
+        let mut geo_map = Vec::new();
+        let mix = geo_columns(&mut geo_map, &["中国", "北京", "China", "Beijing"]);
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block_payload("1.0.0.0", "1.255.255.255", region_payload(mix, "ISP"))
+            .block("2.0.0.0", "2.255.255.255", "inline")
+            .geo_map(0b110, geo_map)
+            .build();
+        let tmp = TempDb::new(&bytes);
+        let options = CzdbOptions::new()
+            .language_columns("zh", &[0, 1])
+            .language_columns("en", &[2, 3]);
+        let db = CzdbDisk::open_with_options(tmp.path_str(), &test_key(), options).unwrap();
+        let target = ip("1.0.0.1");
+        assert_eq!(
+            db.search_lang(target, "en").as_deref(),
+            Some("China\tBeijing\tISP")
+        );
+        assert_eq!(
+            db.search_lang(target, "zh").as_deref(),
+            Some("中国\t北京\tISP")
+        );
+        assert_eq!(db.search_lang(target, "fr"), db.search(target));
+    }
+
+    #[test]
+    fn search_structured_names_selected_columns() {
+        let mut geo_map = Vec::new();
+        let mix = geo_columns(&mut geo_map, &["中国", "广东", "深圳", "南山"]);
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block_payload("1.0.0.0", "1.255.255.255", region_payload(mix, "电信"))
+            .block("2.0.0.0", "2.255.255.255", "inline\tregion")
+            .geo_map(0b11110, geo_map)
+            .build();
+        let tmp = TempDb::new(&bytes);
+        let db = CzdbDisk::open(tmp.path_str(), &test_key()).unwrap();
+
+        let region = db.search_structured(ip("1.0.0.1")).unwrap();
+        assert_eq!(region.country.as_deref(), Some("中国"));
+        assert_eq!(region.province.as_deref(), Some("广东"));
+        assert_eq!(region.city.as_deref(), Some("深圳"));
+        assert_eq!(
+            region.columns,
+            vec![("district".to_string(), "南山".to_string())]
+        );
+        assert_eq!(region.isp.as_deref(), Some("电信"));
+        assert_eq!(Some(region.to_string()), db.search(ip("1.0.0.1")));
+
+        let inline = db.search_structured(ip("2.0.0.1")).unwrap();
+        assert_eq!(inline.country, None);
+        assert_eq!(inline.raw, "inline\tregion");
+        assert_eq!(db.search_structured(ip("9.0.0.1")), None);
+    }
+
+    #[test]
+    fn boundary_kind_matches_block_edges() {
+        let bytes = panic_report_fixture().build();
+        let tmp = TempDb::new(&bytes);
+        let db = CzdbDisk::open(tmp.path_str(), &test_key()).unwrap();
+        assert_eq!(db.boundary_kind(ip("1.128.0.0")), Some(BoundaryKind::Start));
+        assert_eq!(
+            db.boundary_kind(ip("1.199.255.255")),
+            Some(BoundaryKind::End)
+        );
+        assert_eq!(
+            db.boundary_kind(ip("39.180.75.143")),
+            Some(BoundaryKind::Interior)
+        );
+        assert_eq!(db.boundary_kind(ip("42.0.0.1")), None);
+    }
+
+    #[test]
+    fn search_rejects_misaligned_or_reversed_header_ranges() {
+        let fixture = panic_report_fixture();
+        let mut bytes = fixture.build();
+        let data_offset = fixture.data_offset();
+        let misaligned = (fixture.block_pos(2) - data_offset + 5) as u32;
+        let reversed = (fixture.block_pos(0) - data_offset) as u32;
+        let pos = fixture.header_ptr_pos(1);
+        bytes[pos..pos + 4].copy_from_slice(&misaligned.to_le_bytes());
+        let pos = fixture.header_ptr_pos(3);
+        bytes[pos..pos + 4].copy_from_slice(&reversed.to_le_bytes());
+        let tmp = TempDb::new(&bytes);
+        let db = CzdbDisk::open(tmp.path_str(), &test_key()).unwrap();
+
+        assert_eq!(db.search(ip("1.204.105.161")), None);
+        assert_eq!(db.search(ip("1.199.236.9")), None);
+        assert_eq!(db.search(ip("39.180.75.143")), None);
+    }
+
+    #[test]
+    fn ipv6_spellings_share_an_entry_and_ranges_render_canonically() {
+        let bytes = FixtureBuilder::new(DbType::Ipv6)
+            .block("2001:0DB8::", "2001:db8:0:0:0:0:0:FFFF", "doc")
+            .build();
+        let tmp = TempDb::new(&bytes);
+        let db = CzdbDisk::open(tmp.path_str(), &test_key()).unwrap();
+
+        let upper = db.lookup_entry(ip("2001:DB8::1")).unwrap();
+        let expanded = db.lookup_entry(ip("2001:db8:0:0:0:0:0:1")).unwrap();
+        assert_eq!(upper, expanded);
+        assert_eq!(
+            db.search_str("2001:0db8::0001").unwrap().as_deref(),
+            Some("doc")
+        );
+        assert_eq!(upper.start.to_string(), "2001:db8::");
+        assert_eq!(upper.end.to_string(), "2001:db8::ffff");
+    }
 }
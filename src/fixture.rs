@@ -0,0 +1,308 @@
+//! Test-only builder producing complete, encrypted CZDB files.
+//!
+//! 仅用于测试：构造完整的加密 CZDB 文件。
+
+use crate::common::{AES_GCM_HEADER_VERSION, DbType};
+use aes::{
+    Aes128,
+    cipher::{Key, KeyInit},
+};
+use base64::{Engine, engine::general_purpose};
+use cipher::{BlockEncryptMut, block_padding::Pkcs7};
+use rmpv::{Value, encode::write_value};
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+pub(crate) const TEST_KEY_BYTES: [u8; 16] = *b"0123456789abcdef";
+pub(crate) const CLIENT_ID: u32 = 42;
+pub(crate) const EXPIRY: u32 = 991231;
+const PADDING: usize = 8;
+const SUPER_PART_LENGTH: usize = 17;
+const HEADER_BLOCK_LENGTH: usize = 20;
+
+pub(crate) fn test_key() -> String {
+    general_purpose::STANDARD.encode(TEST_KEY_BYTES)
+}
+
+/// Encode a region payload as stored in the data section.
+pub(crate) fn region_payload(geo_pos_mix_size: u64, other_data: &str) -> Vec<u8> {
+    let mut buf = Vec::new();
+    write_value(&mut buf, &Value::Integer(geo_pos_mix_size.into())).unwrap();
+    write_value(&mut buf, &Value::String(other_data.into())).unwrap();
+    buf
+}
+
+/// Append a geo-map column array and return its packed `geo_pos_mix_size`.
+pub(crate) fn geo_columns(geo_map: &mut Vec<u8>, columns: &[&str]) -> u64 {
+    let ptr = geo_map.len() as u64;
+    let values = columns.iter().map(|c| Value::String((*c).into())).collect();
+    write_value(geo_map, &Value::Array(values)).unwrap();
+    let len = geo_map.len() as u64 - ptr;
+    (len << 24) | ptr
+}
+
+fn ip_bytes(ip: IpAddr) -> [u8; 16] {
+    let mut bytes = [0u8; 16];
+    match ip {
+        IpAddr::V4(ip) => bytes[..4].copy_from_slice(&ip.octets()),
+        IpAddr::V6(ip) => bytes.copy_from_slice(&ip.octets()),
+    }
+    bytes
+}
+
+struct Block {
+    start: [u8; 16],
+    end: [u8; 16],
+    payload: Vec<u8>,
+}
+
+pub(crate) struct FixtureBuilder {
+    db_type: DbType,
+    blocks: Vec<Block>,
+    header_every: usize,
+    column_selection: u32,
+    geo_map: Vec<u8>,
+    expiry: u32,
+    aes_gcm: bool,
+}
+
+impl FixtureBuilder {
+    pub(crate) fn new(db_type: DbType) -> Self {
+        Self {
+            db_type,
+            blocks: Vec::new(),
+            header_every: 2,
+            column_selection: 0,
+            geo_map: Vec::new(),
+            expiry: EXPIRY,
+            aes_gcm: false,
+        }
+    }
+
+    /// Add an index block with an inline region (no geo-map indirection).
+    pub(crate) fn block(self, start: &str, end: &str, region: &str) -> Self {
+        self.block_payload(start, end, region_payload(0, region))
+    }
+
+    /// Add an index block with an arbitrary region payload.
+    pub(crate) fn block_payload(mut self, start: &str, end: &str, payload: Vec<u8>) -> Self {
+        self.blocks.push(Block {
+            start: ip_bytes(start.parse().unwrap()),
+            end: ip_bytes(end.parse().unwrap()),
+            payload,
+        });
+        self
+    }
+
+    /// Emit a header entry every `n` index blocks (the last block always gets one).
+    pub(crate) fn header_every(mut self, n: usize) -> Self {
+        self.header_every = n.max(1);
+        self
+    }
+
+    /// Set the column selection mask and the (unencrypted) geo-map bytes.
+    pub(crate) fn geo_map(mut self, column_selection: u32, geo_map: Vec<u8>) -> Self {
+        self.column_selection = column_selection;
+        self.geo_map = geo_map;
+        self
+    }
+
+    /// Set the expiry date, encoded as `yymmdd`.
+    pub(crate) fn expiry(mut self, yymmdd: u32) -> Self {
+        self.expiry = yymmdd;
+        self
+    }
+
+    /// Encrypt the hyper header block with AES-128-GCM.
+    #[cfg(feature = "aes-gcm")]
+    pub(crate) fn aes_gcm(mut self) -> Self {
+        self.aes_gcm = true;
+        self
+    }
+
+    fn header_indices(&self) -> Vec<usize> {
+        let mut indices: Vec<usize> = (0..self.blocks.len()).step_by(self.header_every).collect();
+        if let Some(last) = self.blocks.len().checked_sub(1)
+            && indices.last() != Some(&last)
+        {
+            indices.push(last);
+        }
+        indices
+    }
+
+    fn plain_block(&self) -> [u8; 8] {
+        let mut plain = [0u8; 8];
+        plain[..4].copy_from_slice(&((CLIENT_ID << 20) | self.expiry).to_le_bytes());
+        plain[4..8].copy_from_slice(&(PADDING as u32).to_le_bytes());
+        plain
+    }
+
+    /// The 12-byte plain header followed by the encrypted hyper header block.
+    fn hyper_header(&self) -> Vec<u8> {
+        let (version, encrypted) = if self.aes_gcm {
+            (AES_GCM_HEADER_VERSION, self.gcm_block())
+        } else {
+            let mut plain = [0u8; 16];
+            plain[..8].copy_from_slice(&self.plain_block());
+            let cipher = Aes128::new(Key::<Aes128>::from_slice(&TEST_KEY_BYTES));
+            let encrypted = cipher
+                .encrypt_padded_mut::<Pkcs7>(&mut plain, 8)
+                .unwrap()
+                .to_vec();
+            (1, encrypted)
+        };
+        let mut header = plain_header(version, encrypted.len());
+        header.extend_from_slice(&encrypted);
+        header
+    }
+
+    #[cfg(feature = "aes-gcm")]
+    fn gcm_block(&self) -> Vec<u8> {
+        use aes_gcm::{
+            Aes128Gcm, Nonce,
+            aead::{Aead, Payload},
+        };
+
+        let nonce = [7u8; 12];
+        let aad = plain_header(AES_GCM_HEADER_VERSION, 12 + 8 + 16);
+        let cipher = Aes128Gcm::new(Key::<Aes128Gcm>::from_slice(&TEST_KEY_BYTES));
+        let plain = self.plain_block();
+        let payload = Payload {
+            msg: &plain,
+            aad: &aad,
+        };
+        let mut block = nonce.to_vec();
+        block.extend(cipher.encrypt(Nonce::from_slice(&nonce), payload).unwrap());
+        block
+    }
+
+    #[cfg(not(feature = "aes-gcm"))]
+    fn gcm_block(&self) -> Vec<u8> {
+        unreachable!("GCM fixtures need the `aes-gcm` feature")
+    }
+
+    /// Offset of the data section within the built file.
+    pub(crate) fn data_offset(&self) -> usize {
+        self.hyper_header().len() + PADDING
+    }
+
+    /// Absolute file offset of the pointer field of header entry `k`.
+    pub(crate) fn header_ptr_pos(&self, k: usize) -> usize {
+        self.data_offset() + SUPER_PART_LENGTH + k * HEADER_BLOCK_LENGTH + 16
+    }
+
+    /// Absolute file offset of index block `n`.
+    pub(crate) fn block_pos(&self, n: usize) -> usize {
+        let headers = self.header_indices().len();
+        self.data_offset()
+            + SUPER_PART_LENGTH
+            + headers * HEADER_BLOCK_LENGTH
+            + n * self.db_type.index_block_len()
+    }
+
+    /// Assemble the complete database file.
+    pub(crate) fn build(&self) -> Vec<u8> {
+        let ip_len = self.db_type.bytes_len();
+        let blen = self.db_type.index_block_len();
+        let headers = self.header_indices();
+        let header_size = headers.len() * HEADER_BLOCK_LENGTH;
+        let start_index = SUPER_PART_LENGTH + header_size;
+        let end_index = start_index + self.blocks.len().saturating_sub(1) * blen;
+        let geo_section = if self.column_selection != 0 {
+            8 + self.geo_map.len()
+        } else {
+            4
+        };
+        let mut region_ptr = end_index + blen + geo_section;
+
+        let mut header = Vec::with_capacity(header_size);
+        for &idx in &headers {
+            header.extend_from_slice(&self.blocks[idx].start);
+            header.extend_from_slice(&((start_index + idx * blen) as u32).to_le_bytes());
+        }
+
+        // Identical payloads share one region record, as in vendor-built files.
+        let mut index = Vec::with_capacity(self.blocks.len() * blen);
+        let mut regions = Vec::new();
+        let mut shared = HashMap::new();
+        for block in &self.blocks {
+            let ptr = *shared.entry(&block.payload).or_insert_with(|| {
+                let ptr = region_ptr;
+                regions.extend_from_slice(&block.payload);
+                region_ptr += block.payload.len();
+                ptr
+            });
+            index.extend_from_slice(&block.start[..ip_len]);
+            index.extend_from_slice(&block.end[..ip_len]);
+            index.extend_from_slice(&(ptr as u32).to_le_bytes());
+            index.push(block.payload.len() as u8);
+        }
+
+        let mut bindata = Vec::new();
+        bindata.push(if self.db_type == DbType::Ipv4 { 0 } else { 1 });
+        bindata.extend_from_slice(&0u32.to_le_bytes());
+        bindata.extend_from_slice(&(start_index as u32).to_le_bytes());
+        bindata.extend_from_slice(&(header_size as u32).to_le_bytes());
+        bindata.extend_from_slice(&(end_index as u32).to_le_bytes());
+        bindata.extend_from_slice(&header);
+        bindata.extend_from_slice(&index);
+        bindata.extend_from_slice(&self.column_selection.to_le_bytes());
+        if self.column_selection != 0 {
+            bindata.extend_from_slice(&(self.geo_map.len() as u32).to_le_bytes());
+            bindata.extend(
+                self.geo_map
+                    .iter()
+                    .enumerate()
+                    .map(|(i, b)| b ^ TEST_KEY_BYTES[i % TEST_KEY_BYTES.len()]),
+            );
+        }
+        bindata.extend_from_slice(&regions);
+        let file_size = bindata.len() as u32;
+        bindata[1..5].copy_from_slice(&file_size.to_le_bytes());
+
+        let mut file = self.hyper_header();
+        file.extend_from_slice(&[0u8; PADDING]);
+        file.extend_from_slice(&bindata);
+        file
+    }
+}
+
+fn plain_header(version: u32, encrypted_len: usize) -> Vec<u8> {
+    let mut header = Vec::with_capacity(12);
+    header.extend_from_slice(&version.to_le_bytes());
+    header.extend_from_slice(&CLIENT_ID.to_le_bytes());
+    header.extend_from_slice(&(encrypted_len as u32).to_le_bytes());
+    header
+}
+
+/// A database written to a temporary file, removed on drop.
+pub(crate) struct TempDb {
+    path: PathBuf,
+}
+
+impl TempDb {
+    pub(crate) fn new(bytes: &[u8]) -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "czdb-test-{}-{}.czdb",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::write(&path, bytes).unwrap();
+        Self { path }
+    }
+
+    pub(crate) fn path_str(&self) -> &str {
+        self.path.to_str().unwrap()
+    }
+}
+
+impl Drop for TempDb {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
@@ -5,8 +5,21 @@
 //! ## Features
 //! - Supports both IPv4 and IPv6 address searches.
 //! - Disk-based search (`CzdbDisk`) for low memory usage.
-//! - Optional memory-mapped file support (`mmap` feature) via `CzdbMmap`.
+//! - Optional memory-mapped file support (`mmap` feature) via `CzdbMmap`, with `MmapAdvice`
+//!   paging hints.
 //! - In-memory accelerated search via `CzdbMemory` (available by default).
+//! - Zero-copy search over database bytes already in memory via `CzdbBorrowed`.
+//! - Optional, provisional AES-128-GCM authenticated headers (`aes-gcm` feature).
+//! - `tower::Service` adapter `CzdbService` over a shared `CzdbMmap` (`tower` feature).
+//! - Cross-backend parity check `assert_backends_agree` (`test-util` feature).
+//! - Parallel batch lookups with `CzdbMemory::search_many_par` (`rayon` feature).
+//! - CIDR range queries with `CzdbMemory::search_cidr` (`ipnet` feature).
+//! - `serde::Serialize` for `Region` and the other result types (`serde` feature).
+//! - `DataSection`, a `core`-only parser and search over a decrypted data section; building
+//!   without the default `std` feature leaves only it, for `no_std` targets.
+//! - LRU cache of index partitions and decoded regions for `CzdbDisk::open_with_cache`
+//!   (`cache` feature).
+//! - `warn` events for lookups that hit corrupt records (`tracing` feature).
 //!
 //! ## Usage
 //!
@@ -16,7 +29,7 @@
 //!
 //! let db_path = "path/to/your/czdb_file";
 //! let key = "your_base64_key";
-//! let mut czdb = CzdbDisk::open(db_path, key).expect("Failed to load database");
+//! let czdb = CzdbDisk::open(db_path, key).expect("Failed to load database");
 //! ```
 //!
 //! 2. Search for IP address geolocation data:
@@ -47,7 +60,7 @@
 //!
 //! let db_path = "path/to/your/czdb_file";
 //! let key = "your_base64_key";
-//! let mut czdb = CzdbDisk::open(db_path, key).expect("Failed to load database");
+//! let czdb = CzdbDisk::open(db_path, key).expect("Failed to load database");
 //! ```
 //!
 //! 2. 查询 IP 数据：
@@ -62,30 +75,100 @@
 //! ```
 
 #![cfg_attr(docsrs, feature(doc_cfg))]
+#![cfg_attr(not(feature = "std"), no_std)]
 
+#[cfg(feature = "std")]
+mod borrowed;
+#[cfg(feature = "std")]
+mod cache;
+#[cfg(feature = "std")]
+mod clock;
+#[cfg(feature = "std")]
 mod common;
+#[cfg(feature = "std")]
+mod decoder;
+#[cfg(feature = "std")]
 mod disk;
+#[cfg(all(test, feature = "std"))]
+mod fixture;
+#[cfg(feature = "std")]
 mod memory;
 #[cfg(feature = "mmap")]
 #[cfg_attr(docsrs, doc(cfg(feature = "mmap")))]
 mod mmap;
+#[cfg(feature = "std")]
+mod options;
+mod section;
+#[cfg(feature = "tower")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tower")))]
+mod service;
+#[cfg(any(all(test, feature = "std"), feature = "test-util"))]
+#[cfg_attr(docsrs, doc(cfg(feature = "test-util")))]
+mod test_util;
 
-pub use common::DbType;
-pub use disk::CzdbDisk;
-pub use memory::CzdbMemory;
+#[cfg(feature = "std")]
+pub use borrowed::CzdbBorrowed;
+#[cfg(feature = "std")]
+pub use cache::{CacheKey, ResultCache};
+#[cfg(feature = "std")]
+pub use clock::{Clock, SystemClock};
+#[cfg(feature = "std")]
+pub use common::{
+    BackendKind, DbInfo, KeySourceErrorKind, MatchedEntry, QueryResult, Region, RegionErrorKind,
+    Searcher, covering_prefix_len, ipv4_to_u32, is_geolocatable, numeric_to_ip, peek_db_type,
+    validate_key,
+};
+#[cfg(feature = "std")]
+pub use decoder::{DefaultRegionDecoder, RegionDecoder};
+#[cfg(feature = "std")]
+pub use disk::{CzdbDisk, SharedCzdbDisk};
+#[cfg(feature = "std")]
+pub use memory::{CzdbMemory, RegionStats};
 #[cfg(feature = "mmap")]
 #[cfg_attr(docsrs, doc(cfg(feature = "mmap")))]
-pub use mmap::CzdbMmap;
+pub use mmap::{CzdbMmap, MmapAdvice};
+#[cfg(feature = "std")]
+pub use options::{CzdbOptions, Ipv4ByteOrder};
+/// Re-exported so `RegionDecoder` implementations use the same `rmpv::Value`.
+///
+/// 重新导出，使 `RegionDecoder` 实现使用相同版本的 `rmpv::Value`。
+#[cfg(feature = "std")]
+pub use rmpv;
+pub use section::{BoundaryKind, DataSection, DbType, SectionEntry, decrypt_geo_map};
+#[cfg(feature = "tower")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tower")))]
+pub use service::CzdbService;
+#[cfg(feature = "test-util")]
+#[cfg_attr(docsrs, doc(cfg(feature = "test-util")))]
+pub use test_util::assert_backends_agree;
+
+// Every searcher can be shared across threads behind an `Arc`; `CzdbDisk` may still take
+// internal locks (see its docs). Fail the build if a backend stops being `Send + Sync`.
+#[cfg(feature = "std")]
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<CzdbDisk>();
+    assert_send_sync::<CzdbMemory>();
+    assert_send_sync::<CzdbBorrowed<'static>>();
+    #[cfg(feature = "mmap")]
+    assert_send_sync::<CzdbMmap>();
+};
 
 /// Possible errors returned by CZDB operations.
 ///
-/// CZDB 操作可能返回的错误。
+/// New variants may be added in minor releases, so matches need a wildcard arm.
+///
+/// CZDB 操作可能返回的错误。后续小版本可能新增错误类型，匹配时需保留通配分支。
+#[cfg(feature = "std")]
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum CzError {
     #[error("Failed to read the database file: {0}")]
     DatabaseFileReadError(#[from] std::io::Error),
     #[error("Failed to decode the key from base64: {0}")]
     KeyDecodingError(#[from] base64::DecodeError),
+    #[error("Failed to load the key: {0}")]
+    KeySourceError(KeySourceErrorKind),
     #[error("Invalid AES key length: expected 16, got {0}")]
     InvalidAesKeyLength(usize),
     #[error("Decryption operation failed")]
@@ -96,4 +179,19 @@ pub enum CzError {
     DatabaseExpired,
     #[error("The database file is corrupted or contains invalid data")]
     DatabaseFileCorrupted,
+    #[error("The database file is corrupted: no data section after the header")]
+    NoDataSection,
+    #[error("Index entry {at} is inverted, out of order, or overlaps the previous entry")]
+    IndexOutOfOrder { at: usize },
+    #[error("None of the {tried} keys opened the database: {last}")]
+    NoMatchingKey {
+        tried: usize,
+        #[source]
+        last: Box<CzError>,
+    },
+    #[error("Corrupted region record at data pointer {data_ptr}: {kind}")]
+    CorruptRegion {
+        data_ptr: usize,
+        kind: RegionErrorKind,
+    },
 }
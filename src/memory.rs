@@ -1,36 +1,47 @@
 use crate::{
-    CzError,
+    CzError, CzdbOptions, Ipv4ByteOrder,
     common::{
-        DbMeta, DbType, decode_aes_key, decode_region_from_bytes, parse_meta_from_bytes,
-        read_hyper_header, compare_bytes,
+        BackendKind, BoundaryKind, DbInfo, DbMeta, DbType, Iterations, MatchedEntry, QueryResult,
+        RecordSite, Region, Searcher, Source, ascii_only, bytes_to_ipv6, check_aes_key_len,
+        compare_bytes, content_hash, decode_aes_key, decode_region_from_bytes,
+        decode_region_with_columns, ipv4_to_u32, is_geolocatable, key_from_env, key_from_file,
+        numeric_to_ip, open_with_first_key, parse_meta_from_bytes, read_hyper_header, u32_to_ipv4,
+        write_csv_row, write_jsonl_entry,
     },
 };
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use chrono::NaiveDate;
 use std::{
     collections::HashMap,
     fs::File,
-    io::{Cursor, Read},
-    net::IpAddr,
+    io::{self, BufWriter, Cursor, Read, Write},
+    net::{AddrParseError, IpAddr, Ipv4Addr, Ipv6Addr},
 };
 
 #[derive(Debug)]
 struct MemoryIndex {
+    /// `start_ip` of each IPv4 entry, so the binary search probes a contiguous array.
+    starts_v4: Vec<u32>,
     entries_v4: Vec<IndexEntryV4>,
     entries_v6: Vec<IndexEntryV6>,
     regions: RegionPool,
+    /// Regions decoded with each `CzdbOptions::language_columns` mask, sharing the ids of
+    /// `regions`.
+    lang_regions: HashMap<String, RegionPool>,
 }
 
 #[derive(Debug)]
 struct IndexEntryV4 {
     start_ip: u32,
     end_ip: u32,
-    region_id: usize,
+    region_id: u32,
 }
 
 #[derive(Debug)]
 struct IndexEntryV6 {
     start_ip: [u8; 16],
     end_ip: [u8; 16],
-    region_id: usize,
+    region_id: u32,
 }
 
 #[derive(Debug)]
@@ -46,19 +57,81 @@ struct RegionPool {
 }
 
 impl RegionPool {
-    fn get(&self, region_id: usize) -> &str {
-        let span = &self.spans[region_id];
+    fn get(&self, region_id: u32) -> &str {
+        let span = &self.spans[region_id as usize];
         &self.data[span.start..span.start + span.len]
     }
 }
 
+/// A `RegionPool` under construction.
+#[derive(Default)]
+struct RegionPoolBuilder {
+    text: String,
+    spans: Vec<RegionSpan>,
+}
+
+impl RegionPoolBuilder {
+    fn push(&mut self, region: &str) {
+        self.spans.push(RegionSpan {
+            start: self.text.len(),
+            len: region.len(),
+        });
+        self.text.push_str(region);
+    }
+
+    fn finish(self) -> RegionPool {
+        RegionPool {
+            data: self.text.into_boxed_str(),
+            spans: self.spans,
+        }
+    }
+}
+
+/// Length statistics over the regions in a `CzdbMemory` string pool.
+///
+/// Lengths are in bytes of the decoded region strings. All fields are zero for a pool
+/// without regions.
+///
+/// `CzdbMemory` 字符串池中区域长度的统计信息。长度为解析后区域字符串的字节数；
+/// 池中没有区域时所有字段均为零。
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RegionStats {
+    /// Number of distinct region records in the pool.
+    ///
+    /// 池中不同区域记录的数量。
+    pub distinct: usize,
+    /// Length of the shortest region.
+    ///
+    /// 最短区域的长度。
+    pub min_len: usize,
+    /// Length of the longest region.
+    ///
+    /// 最长区域的长度。
+    pub max_len: usize,
+    /// Mean region length.
+    ///
+    /// 区域的平均长度。
+    pub mean_len: f64,
+    /// Total size of the string pool.
+    ///
+    /// 字符串池的总大小。
+    pub total_bytes: usize,
+}
+
 /// In-memory CZDB searcher with a prebuilt index and string pool.
 ///
-/// 预构建索引与字符串池的内存 CZDB 查询器。
+/// `CzdbMemory` is `Send + Sync` and every query method takes `&self`, so a single loaded
+/// database can be kept in a static or an `Arc` and queried from many threads or async tasks
+/// without locking.
+///
+/// 预构建索引与字符串池的内存 CZDB 查询器。`CzdbMemory` 实现了 `Send + Sync`，且所有查询方法都只需
+/// `&self`，因此加载后的数据库可放在静态变量或 `Arc` 中，供多个线程或异步任务无锁并发查询。
 #[derive(Debug)]
 pub struct CzdbMemory {
     meta: DbMeta,
     memory_index: MemoryIndex,
+    source: Option<Source>,
 }
 
 impl CzdbMemory {
@@ -66,106 +139,769 @@ impl CzdbMemory {
     ///
     /// 打开数据库文件并构建内存索引。
     pub fn open(db_path: &str, key: &str) -> Result<Self, CzError> {
+        Self::open_with_options(db_path, key, CzdbOptions::default())
+    }
+
+    /// Open a database file and build in-memory indices with a raw 16-byte AES key instead
+    /// of a base64 string.
+    ///
+    /// Returns `InvalidAesKeyLength` if `key` is not 16 bytes long.
+    ///
+    /// 打开数据库文件并构建内存索引，直接使用 16 字节原始 AES 密钥而非 base64 字符串；
+    /// 长度不是 16 字节时返回 `InvalidAesKeyLength`。
+    pub fn open_with_key_bytes(db_path: &str, key: &[u8]) -> Result<Self, CzError> {
+        Self::open_with_key_bytes_and_options(db_path, key, CzdbOptions::default())
+    }
+
+    /// Open a database file, reading the base64 key from the environment variable `var_name`.
+    ///
+    /// Surrounding whitespace in the key is ignored. Returns `KeySourceError` if the variable
+    /// is not set.
+    ///
+    /// 打开数据库文件，从环境变量 `var_name` 读取 base64 密钥，忽略首尾空白；
+    /// 变量未设置时返回 `KeySourceError`。
+    pub fn open_with_key_env(db_path: &str, var_name: &str) -> Result<Self, CzError> {
+        Self::open(db_path, &key_from_env(var_name)?)
+    }
+
+    /// Open a database file, reading the base64 key from the file at `key_path`.
+    ///
+    /// Surrounding whitespace and newlines in the file are ignored. Returns `KeySourceError`
+    /// if the file cannot be read.
+    ///
+    /// 打开数据库文件，从 `key_path` 处的文件读取 base64 密钥，忽略首尾空白与换行；
+    /// 文件无法读取时返回 `KeySourceError`。
+    pub fn open_with_key_file(db_path: &str, key_path: &str) -> Result<Self, CzError> {
+        Self::open(db_path, &key_from_file(key_path)?)
+    }
+
+    /// Open a database file with the first of `keys` that decrypts it, e.g. while keys are
+    /// being rotated.
+    ///
+    /// A key that fails anywhere in the header parse is skipped. If none of them fits, returns
+    /// `NoMatchingKey` with the number of keys tried and the last error that was not a plain
+    /// decryption failure, such as `DatabaseFileCorrupted` for a damaged file.
+    ///
+    /// 使用 `keys` 中第一个能解密的密钥打开数据库文件，适用于密钥轮换期间；头部解析任一环节
+    /// 失败的密钥会被跳过。全部不适用时返回 `NoMatchingKey`，其中包含尝试的密钥数量，以及最后一个
+    /// 并非单纯解密失败的错误（如文件损坏时的 `DatabaseFileCorrupted`）。
+    pub fn open_with_keys(db_path: &str, keys: &[&str]) -> Result<Self, CzError> {
+        open_with_first_key(keys, |key| Self::open(db_path, key))
+    }
+
+    /// Open a database file, checking expiry against `today` instead of the host clock.
+    ///
+    /// Equivalent to `open_with_options` with `CzdbOptions::clock(today)`.
+    ///
+    /// 打开数据库文件并构建内存索引，以 `today` 而非主机时钟判断是否过期；
+    /// 等价于使用 `CzdbOptions::clock(today)` 的 `open_with_options`。
+    pub fn open_with_reference_date(
+        db_path: &str,
+        key: &str,
+        today: NaiveDate,
+    ) -> Result<Self, CzError> {
+        Self::open_with_options(db_path, key, CzdbOptions::new().clock(today))
+    }
+
+    /// Open a database file with custom options and build in-memory indices.
+    ///
+    /// 使用自定义选项打开数据库文件并构建内存索引。
+    pub fn open_with_options(
+        db_path: &str,
+        key: &str,
+        options: CzdbOptions,
+    ) -> Result<Self, CzError> {
+        Self::open_with_key_bytes_and_options(db_path, &decode_aes_key(key)?, options)
+    }
+
+    /// `open_with_key_bytes` with custom options.
+    ///
+    /// 使用自定义选项的 `open_with_key_bytes`。
+    pub fn open_with_key_bytes_and_options(
+        db_path: &str,
+        key_bytes: &[u8],
+        options: CzdbOptions,
+    ) -> Result<Self, CzError> {
+        check_aes_key_len(key_bytes)?;
         let mut file = File::open(db_path)?;
         let mut data = Vec::new();
         file.read_to_end(&mut data)?;
-        Self::from_bytes(data, key)
+        let mut db = Self::from_key_bytes(data, key_bytes, options)?;
+        db.source = Some(Source {
+            path: db_path.to_string(),
+            key: key_bytes.to_vec(),
+        });
+        Ok(db)
     }
 
     /// Build from raw bytes and construct in-memory indices.
     ///
     /// 从原始字节构建并生成内存索引。
     pub fn from_bytes(data: Vec<u8>, key: &str) -> Result<Self, CzError> {
+        Self::from_bytes_with_options(data, key, CzdbOptions::default())
+    }
+
+    /// Build from any reader, such as a ZIP entry, reading it to the end.
+    ///
+    /// 从任意读取器（如 ZIP 条目）读取全部内容并构建内存索引。
+    pub fn from_reader<R: Read>(reader: R, key: &str) -> Result<Self, CzError> {
+        Self::from_reader_with_options(reader, key, CzdbOptions::default())
+    }
+
+    /// `from_reader` with custom options.
+    ///
+    /// 使用自定义选项的 `from_reader`。
+    pub fn from_reader_with_options<R: Read>(
+        mut reader: R,
+        key: &str,
+        options: CzdbOptions,
+    ) -> Result<Self, CzError> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        Self::from_bytes_with_options(data, key, options)
+    }
+
+    /// Build from raw bytes with custom options and construct in-memory indices.
+    ///
+    /// 使用自定义选项从原始字节构建并生成内存索引。
+    pub fn from_bytes_with_options(
+        data: Vec<u8>,
+        key: &str,
+        options: CzdbOptions,
+    ) -> Result<Self, CzError> {
+        Self::from_key_bytes(data, &decode_aes_key(key)?, options)
+    }
+
+    fn from_key_bytes(
+        data: Vec<u8>,
+        key_bytes: &[u8],
+        options: CzdbOptions,
+    ) -> Result<Self, CzError> {
+        let (meta, data_offset) = load_meta(&data, key_bytes, options)?;
+        let memory_index = build_memory_index(&data[data_offset..], &meta)?;
+
+        Self {
+            meta,
+            memory_index,
+            source: None,
+        }
+        .checked()
+    }
+
+    /// Run `validate` when `CzdbOptions::strict` is set, reporting failures as corruption.
+    fn checked(self) -> Result<Self, CzError> {
+        if self.meta.options.strict {
+            self.validate()
+                .map_err(|_| CzError::DatabaseFileCorrupted)?;
+        }
+        Ok(self)
+    }
+
+    /// Open a database file, reusing the index saved by `save_index` at `index_path`.
+    ///
+    /// The sidecar is used only if it was saved for a byte-identical database file and with
+    /// the same decoding options; otherwise, or if it is missing or unreadable,
+    /// the index is built from the database as `open` does. A custom `RegionDecoder` is not
+    /// recorded in the sidecar, so keep using the one it was saved with. The sidecar holds no
+    /// `search_lang` pools, so the index is always rebuilt when languages are registered
+    /// with `CzdbOptions::language_columns`.
+    ///
+    /// 打开数据库文件，并复用 `save_index` 保存在 `index_path` 的索引。仅当附属文件对应内容完全相同的
+    /// 数据库文件且解析选项一致时才会使用；否则，或文件缺失、无法读取时，按 `open` 的方式重新构建索引。
+    /// 附属文件不记录自定义 `RegionDecoder`，请继续使用保存时的解码器。附属文件不包含
+    /// `search_lang` 所用的字符串池，因此通过 `CzdbOptions::language_columns` 注册了语言时总会重新构建索引。
+    pub fn load_with_index(db_path: &str, key: &str, index_path: &str) -> Result<Self, CzError> {
+        Self::load_with_index_with_options(db_path, key, index_path, CzdbOptions::default())
+    }
+
+    /// `load_with_index` with custom options.
+    ///
+    /// 使用自定义选项的 `load_with_index`。
+    pub fn load_with_index_with_options(
+        db_path: &str,
+        key: &str,
+        index_path: &str,
+        options: CzdbOptions,
+    ) -> Result<Self, CzError> {
         let key_bytes = decode_aes_key(key)?;
-        let mut cursor = Cursor::new(&data);
-        let header = read_hyper_header(&mut cursor, &key_bytes)?;
-        let data_offset = (12 + header.padding_size + header.encrypted_block_size) as usize;
-        if data_offset > data.len() {
-            return Err(CzError::DatabaseFileCorrupted);
+        let data = std::fs::read(db_path)?;
+        let (meta, data_offset) = load_meta(&data, &key_bytes, options)?;
+        let memory_index = match read_index_sidecar(index_path, &meta, content_hash(&data)) {
+            Some(memory_index) => memory_index,
+            None => build_memory_index(&data[data_offset..], &meta)?,
+        };
+
+        Self {
+            meta,
+            memory_index,
+            source: Some(Source {
+                path: db_path.to_string(),
+                key: key_bytes,
+            }),
+        }
+        .checked()
+    }
+
+    /// Save the built index to `path` for `load_with_index` to reuse.
+    ///
+    /// The database file is read again to hash its contents, so building a searcher does not
+    /// pay for it. Databases built from bytes have no file to hash and return an `Unsupported`
+    /// read error; a file whose header changed since it was opened is rejected the same way.
+    ///
+    /// 将已构建的索引保存到 `path`，供 `load_with_index` 复用。保存时会重新读取数据库文件以计算内容哈希，
+    /// 因此构建查询器时无需承担该开销。由字节构建的数据库没有可哈希的文件，返回 `Unsupported`
+    /// 读取错误；打开后头部已变化的文件同样会被拒绝。
+    pub fn save_index(&self, path: &str) -> Result<(), CzError> {
+        let source = self.source.as_ref().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                "database was not opened from a file",
+            )
+        })?;
+        let data = std::fs::read(&source.path)?;
+        let (meta, _) = load_meta(&data, &source.key, self.meta.options.clone())?;
+        if meta.fingerprint != self.meta.fingerprint {
+            return Err(io::Error::other("database file changed since it was opened").into());
         }
-        let file_size_total = data.len() as u64;
-        let meta = parse_meta_from_bytes(
-            &data[data_offset..],
-            file_size_total,
-            header.padding_size,
-            header.encrypted_block_size,
+        let mut w = BufWriter::new(File::create(path)?);
+        write_index_sidecar(&mut w, &self.memory_index, &self.meta, content_hash(&data))?;
+        w.flush()?;
+        Ok(())
+    }
+
+    /// Build from a database downloaded in segments, tolerating overlaps and trailing slop.
+    ///
+    /// Each segment is given with its byte offset in the file, as requested by a range
+    /// download, and may arrive in any order. Where segments overlap, as after a retried
+    /// range, the shared bytes must agree; conflicting bytes or a gap between segments is
+    /// an `InvalidData` read error. The reassembled file is trimmed to the size declared by
+    /// its header; bytes past that size are ignored and fewer bytes than declared is
+    /// `DatabaseFileCorrupted`.
+    ///
+    /// 由分段下载的数据库构建，容忍分段重叠与末尾多余数据。每个分段附带其在文件中的字节偏移
+    /// （即范围请求的起点），顺序不限。分段重叠时（如重试后的范围请求）重叠部分必须一致；
+    /// 内容冲突或分段之间存在空缺时返回 `InvalidData` 读取错误。拼接结果按文件头声明的大小截断，
+    /// 超出部分被忽略，不足则返回 `DatabaseFileCorrupted`。
+    pub fn from_segments(segments: Vec<(u64, Vec<u8>)>, key: &str) -> Result<Self, CzError> {
+        Self::from_segments_with_options(segments, key, CzdbOptions::default())
+    }
+
+    /// Build from a segmented download with custom options.
+    ///
+    /// 使用自定义选项由分段下载的数据库构建。
+    pub fn from_segments_with_options(
+        segments: Vec<(u64, Vec<u8>)>,
+        key: &str,
+        options: CzdbOptions,
+    ) -> Result<Self, CzError> {
+        let mut data = reassemble(segments)?;
+        let key_bytes = decode_aes_key(key)?;
+        let header = read_hyper_header(
+            &mut Cursor::new(&data),
+            data.len() as u64,
             &key_bytes,
+            &options,
         )?;
-        let memory_index = build_memory_index(&data[data_offset..], &meta)?;
+        let data_offset = header.data_offset();
+        if data_offset >= data.len() as u64 {
+            return Err(CzError::NoDataSection);
+        }
+        let data_offset = data_offset as usize;
+        let file_size = data
+            .get(data_offset + 1..data_offset + 5)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+            .ok_or(CzError::DatabaseFileCorrupted)?;
+        let declared_len = data_offset + file_size;
+        if data.len() < declared_len {
+            return Err(CzError::DatabaseFileCorrupted);
+        }
+        data.truncate(declared_len);
+        Self::from_key_bytes(data, &key_bytes, options)
+    }
 
-        Ok(Self {
-            meta,
-            memory_index,
-        })
+    /// Re-open the file this searcher was opened from and replace its state.
+    ///
+    /// The same key and options are used and the label is kept. On failure the current
+    /// state is left untouched and keeps serving queries. Databases built from bytes have
+    /// no file to reload and return an `Unsupported` read error.
+    ///
+    /// 使用相同的密钥和选项重新打开原文件并替换内部状态，标签保持不变。
+    /// 失败时保留当前状态并继续提供查询。由字节构建的数据库没有可重新加载的文件，
+    /// 返回 `Unsupported` 读取错误。
+    pub fn reload(&mut self) -> Result<(), CzError> {
+        let source = self.source.as_ref().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                "database was not opened from a file",
+            )
+        })?;
+        let mut fresh = Self::open_with_key_bytes_and_options(
+            &source.path,
+            &source.key,
+            self.meta.options.clone(),
+        )?;
+        fresh.meta.label = self.meta.label.take();
+        *self = fresh;
+        Ok(())
+    }
+
+    /// Open the database at `db_path` with `key` and replace this searcher's state with it.
+    ///
+    /// The current options and label are kept, and later `reload` calls use the new file.
+    /// The new file is fully parsed before anything is replaced, so on failure the current
+    /// state is left untouched and keeps serving queries.
+    ///
+    /// 使用 `key` 打开 `db_path` 处的数据库并替换当前状态，保留现有选项和标签，
+    /// 之后的 `reload` 也将使用新文件。新文件完整解析后才会替换，失败时保留当前状态
+    /// 并继续提供查询。
+    pub fn reload_from(&mut self, db_path: &str, key: &str) -> Result<(), CzError> {
+        let mut fresh = Self::open_with_options(db_path, key, self.meta.options.clone())?;
+        fresh.meta.label = self.meta.label.take();
+        *self = fresh;
+        Ok(())
     }
 
     /// Search a single IP address.
     ///
-    /// 查询指定 IP 地址。
+    /// Consults the configured `ResultCache` first, if any. Regions listed in
+    /// `CzdbOptions::treat_as_miss` yield `None`.
+    ///
+    /// 查询指定 IP 地址；若配置了 `ResultCache`，会先查询缓存。
+    /// `CzdbOptions::treat_as_miss` 中列出的区域返回 `None`。
     pub fn search(&self, ip: IpAddr) -> Option<String> {
-        self.search_ref(ip).map(str::to_string)
+        if let Some(region) = self.meta.cached_region(ip) {
+            return Some(region);
+        }
+        let region = self.search_ref(ip).map(str::to_string);
+        self.meta.cache_region(ip, &region);
+        region
+    }
+
+    /// `search` with the same signature as `CzdbDisk::try_search` and `CzdbMmap::try_search`.
+    ///
+    /// Every region is decoded while the index is built, so corrupt records fail the open
+    /// instead and this never returns an error.
+    ///
+    /// 与 `CzdbDisk::try_search`、`CzdbMmap::try_search` 签名相同的 `search`。
+    /// 所有区域都在构建索引时解析，损坏的记录会在打开时报错，因此该方法不会返回错误。
+    pub fn try_search(&self, ip: IpAddr) -> Result<Option<String>, CzError> {
+        Ok(self.search(ip))
+    }
+
+    /// Search `ip` and split the region into named fields; see `Region`.
+    ///
+    /// The pool keeps only joined strings, so when the database selects geo-map columns
+    /// every region is split as if it led with them, including records stored inline.
+    ///
+    /// 查询 `ip` 并将区域拆分为具名字段，参见 `Region`。字符串池仅保存拼接后的字符串，
+    /// 因此数据库选择了地理映射列时，所有区域（包括内联记录）都按以这些列开头的格式拆分。
+    pub fn search_structured(&self, ip: IpAddr) -> Option<Region> {
+        let raw = self.search_ref(ip)?.to_string();
+        Some(Region::from_joined(raw, &self.meta.structured_columns()))
+    }
+
+    /// Search `ip` only if it is a public address, returning `None` immediately otherwise.
+    ///
+    /// Private, loopback, link-local and other reserved addresses skip the lookup; see
+    /// `is_geolocatable` for the exact ranges.
+    ///
+    /// 仅在 `ip` 为公网地址时查询，否则直接返回 `None`。私有、环回、链路本地等保留地址
+    /// 不执行查询，具体范围参见 `is_geolocatable`。
+    pub fn search_public_only(&self, ip: IpAddr) -> Option<String> {
+        if !is_geolocatable(ip) {
+            return None;
+        }
+        self.search(ip)
+    }
+
+    /// Search `ip` and report how many binary-search iterations the lookup took.
+    ///
+    /// The `ResultCache` is bypassed; `treat_as_miss` still applies. `search` does no
+    /// counting.
+    ///
+    /// 查询 `ip` 并返回查找所用的二分迭代次数。不使用 `ResultCache`，但仍应用
+    /// `treat_as_miss`。`search` 不做计数。
+    pub fn search_instrumented(&self, ip: IpAddr) -> (Option<String>, usize) {
+        let mut iterations = 0;
+        let ip = self.meta.normalize_ip(ip);
+        if !self.meta.db_type.compare(&ip) {
+            return (None, iterations);
+        }
+        let region_id = match ip {
+            IpAddr::V4(ip) => self
+                .find_v4_counted(ipv4_to_u32(ip), &mut iterations)
+                .map(|e| e.region_id),
+            IpAddr::V6(ip) => self
+                .find_v6_counted(&ip.octets(), &mut iterations)
+                .map(|e| e.region_id),
+        };
+        let region = region_id.map(|id| self.memory_index.regions.get(id));
+        (
+            self.meta.filter_miss(region).map(str::to_string),
+            iterations,
+        )
+    }
+
+    /// Search a single IP address given by reference.
+    ///
+    /// Convenience for generic code holding `&IpAddr`; equivalent to `search(*ip)`.
+    ///
+    /// 通过引用查询指定 IP，便于持有 `&IpAddr` 的泛型代码使用，等价于 `search(*ip)`。
+    pub fn search_by_ref(&self, ip: &IpAddr) -> Option<String> {
+        self.search(*ip)
+    }
+
+    /// Parse `ip` and search it, returning the parse error for malformed input.
+    ///
+    /// Lookups compare the parsed octets, so every spelling of an IPv6 address (letter case,
+    /// leading zeros, `::` compression) finds the same entry.
+    ///
+    /// 解析 `ip` 并查询；输入格式错误时返回解析错误。查询比较的是解析后的字节，因此同一 IPv6
+    /// 地址的任意写法（大小写、前导零、`::` 压缩）都会命中相同条目。
+    pub fn search_str(&self, ip: &str) -> Result<Option<String>, AddrParseError> {
+        Ok(self.search(ip.parse()?))
+    }
+
+    /// Search a single IP address, returning `default` when there is no usable region.
+    ///
+    /// Both a miss and a matched block whose region is empty after trimming yield
+    /// `default`; use `search` to tell them apart.
+    ///
+    /// 查询指定 IP，无可用区域时返回 `default`。未命中与命中但区域去除空白后为空
+    /// 两种情况均返回 `default`；如需区分请使用 `search`。
+    pub fn search_or(&self, ip: IpAddr, default: &str) -> String {
+        match self.search(ip) {
+            Some(region) if !region.trim().is_empty() => region,
+            _ => default.to_string(),
+        }
+    }
+
+    /// Look up `ip`, reporting why no region was returned.
+    ///
+    /// 查询 `ip`，并在未返回区域时说明原因。
+    pub fn query(&self, ip: IpAddr) -> QueryResult {
+        let ip = self.meta.normalize_ip(ip);
+        if !self.meta.db_type.compare(&ip) {
+            return QueryResult::WrongVersion;
+        }
+        match self.region_id(ip) {
+            Some(region_id) => QueryResult::covered(
+                self.meta
+                    .filter_miss(Some(self.memory_index.regions.get(region_id)))
+                    .map(str::to_string),
+            ),
+            None => QueryResult::NotCovered,
+        }
+    }
+
+    /// Search a single IP address and keep only its ASCII content.
+    ///
+    /// This is lossy: non-ASCII characters are dropped and columns left empty are removed,
+    /// so purely Chinese regions come back as an empty string. Intended for downstream
+    /// systems that cannot handle UTF-8.
+    ///
+    /// 查询指定 IP 并仅保留 ASCII 内容。该操作有损：非 ASCII 字符被删除，变为空的列被移除，
+    /// 纯中文区域将返回空字符串。适用于无法处理 UTF-8 的下游系统。
+    pub fn search_ascii(&self, ip: IpAddr) -> Option<String> {
+        self.search(ip).map(|region| ascii_only(&region))
     }
 
     /// Search a single IP address and return a borrowed string.
     ///
-    /// 查询指定 IP 并返回借用字符串。
+    /// Like `search`, regions listed in `CzdbOptions::treat_as_miss` yield `None`.
+    ///
+    /// 查询指定 IP 并返回借用字符串。与 `search` 相同，`CzdbOptions::treat_as_miss`
+    /// 中列出的区域返回 `None`。
     pub fn search_ref(&self, ip: IpAddr) -> Option<&str> {
+        let region_id = self.region_id(self.meta.normalize_ip(ip))?;
+        self.meta
+            .filter_miss(Some(self.memory_index.regions.get(region_id)))
+    }
+
+    /// Search a single IP address, returning the geo-map columns registered for `lang`.
+    ///
+    /// The regions of every language registered with `CzdbOptions::language_columns` are
+    /// decoded while the index is built, so each one adds a string pool. Languages without
+    /// a mapping fall back to the database's column selection, matching `search`.
+    ///
+    /// 查询指定 IP，返回为 `lang` 注册的地理映射列。通过 `CzdbOptions::language_columns`
+    /// 注册的每种语言都会在构建索引时解析，并各占用一个字符串池；未注册的语言使用数据库自带的
+    /// 列选择，与 `search` 结果一致。
+    pub fn search_lang(&self, ip: IpAddr, lang: &str) -> Option<String> {
+        let region_id = self.region_id(self.meta.normalize_ip(ip))?;
+        let pool = self
+            .memory_index
+            .lang_regions
+            .get(lang)
+            .unwrap_or(&self.memory_index.regions);
+        self.meta
+            .filter_miss(Some(pool.get(region_id)))
+            .map(str::to_string)
+    }
+
+    /// Pool id of the region covering the already normalized `ip`, before `treat_as_miss`.
+    fn region_id(&self, ip: IpAddr) -> Option<u32> {
+        if !self.meta.db_type.compare(&ip) {
+            return None;
+        }
+        match ip {
+            IpAddr::V4(ip) => Some(self.find_v4(ipv4_to_u32(ip))?.region_id),
+            IpAddr::V6(ip) => Some(self.find_v6(&ip.octets())?.region_id),
+        }
+    }
+
+    /// `search_ref` for an IPv4 database, skipping the `IpAddr` dispatch and version check.
+    ///
+    /// Meant for hot loops that only ever query IPv4. Debug builds assert the database is
+    /// IPv4; in release builds an IPv6 database simply yields `None`.
+    ///
+    /// 针对 IPv4 数据库的 `search_ref`，省去 `IpAddr` 分派与版本检查，适用于只查询 IPv4 的热点循环。
+    /// Debug 构建会断言数据库为 IPv4；Release 构建中 IPv6 数据库直接返回 `None`。
+    pub fn search_v4(&self, ip: Ipv4Addr) -> Option<&str> {
+        debug_assert_eq!(self.meta.db_type, DbType::Ipv4);
+        let entry = self.find_v4(ipv4_to_u32(ip))?;
+        self.meta
+            .filter_miss(Some(self.memory_index.regions.get(entry.region_id)))
+    }
+
+    /// `search_ref` for an IPv6 database, skipping the `IpAddr` dispatch and version check.
+    ///
+    /// IPv4-mapped addresses are looked up as given, not converted. Debug builds assert the
+    /// database is IPv6; in release builds an IPv4 database simply yields `None`.
+    ///
+    /// 针对 IPv6 数据库的 `search_ref`，省去 `IpAddr` 分派与版本检查；IPv4 映射地址按原样查询，
+    /// 不做转换。Debug 构建会断言数据库为 IPv6；Release 构建中 IPv4 数据库直接返回 `None`。
+    pub fn search_v6(&self, ip: Ipv6Addr) -> Option<&str> {
+        debug_assert_eq!(self.meta.db_type, DbType::Ipv6);
+        let entry = self.find_v6(&ip.octets())?;
+        self.meta
+            .filter_miss(Some(self.memory_index.regions.get(entry.region_id)))
+    }
+
+    /// Look up the entry covering `ip`, returning its range, region and database type.
+    ///
+    /// 查询覆盖 `ip` 的条目，返回其地址范围、区域及数据库类型。
+    pub fn lookup_entry(&self, ip: IpAddr) -> Option<MatchedEntry> {
+        let ip = self.meta.normalize_ip(ip);
         if !self.meta.db_type.compare(&ip) {
             return None;
         }
+        let (start, end, region_id) = match ip {
+            IpAddr::V4(ip) => {
+                let entry = self.find_v4(ipv4_to_u32(ip))?;
+                (
+                    IpAddr::V4(u32_to_ipv4(entry.start_ip)),
+                    IpAddr::V4(u32_to_ipv4(entry.end_ip)),
+                    entry.region_id,
+                )
+            }
+            IpAddr::V6(ip) => {
+                let entry = self.find_v6(&ip.octets())?;
+                (
+                    IpAddr::V6(bytes_to_ipv6(entry.start_ip)),
+                    IpAddr::V6(bytes_to_ipv6(entry.end_ip)),
+                    entry.region_id,
+                )
+            }
+        };
+        Some(MatchedEntry {
+            start,
+            end,
+            region: self
+                .meta
+                .filter_miss(Some(self.memory_index.regions.get(region_id)))?
+                .to_string(),
+            db_type: self.meta.db_type,
+        })
+    }
+
+    /// Entries on either side of `ip` as `(start, end, region)`: the last one ending before
+    /// it and the first one starting after it.
+    ///
+    /// Meant for investigating coverage gaps: when `search` misses, these are the ranges
+    /// bordering the gap. When `ip` is covered, they are the neighbours of its entry. Either
+    /// side is `None` past the first or last entry, and both are for the other IP version.
+    ///
+    /// 以 `(起始, 结束, 区域)` 返回 `ip` 两侧的条目：在其之前结束的最后一个条目，以及在其之后开始的
+    /// 第一个条目。用于排查覆盖空洞：`search` 未命中时，两者即为空洞两侧的范围；`ip` 已被覆盖时，
+    /// 则为其所在条目的相邻条目。超出首尾条目的一侧为 `None`，IP 版本不匹配时两侧均为 `None`。
+    #[allow(clippy::type_complexity)]
+    pub fn search_nearest(
+        &self,
+        ip: IpAddr,
+    ) -> (
+        Option<(IpAddr, IpAddr, String)>,
+        Option<(IpAddr, IpAddr, String)>,
+    ) {
+        let ip = self.meta.normalize_ip(ip);
+        if !self.meta.db_type.compare(&ip) {
+            return (None, None);
+        }
+        let index = &self.memory_index;
+        let entry = |start, end, region_id| (start, end, index.regions.get(region_id).to_string());
         match ip {
             IpAddr::V4(ip) => {
-                if self.memory_index.entries_v4.is_empty() {
-                    return None;
-                }
-                let ip_num = u32::from_be_bytes(ip.octets());
-                let mut l = 0usize;
-                let mut h = self.memory_index.entries_v4.len() - 1;
-                while l <= h {
-                    let m = (l + h) >> 1;
-                    let entry = &self.memory_index.entries_v4[m];
-                    if ip_num >= entry.start_ip && ip_num <= entry.end_ip {
-                        return Some(self.memory_index.regions.get(entry.region_id));
-                    } else if ip_num < entry.start_ip {
-                        if m == 0 {
-                            break;
-                        }
-                        h = m - 1;
-                    } else {
-                        l = m + 1;
-                    }
+                let ip = ipv4_to_u32(ip);
+                let entries = &index.entries_v4;
+                let matched = |e: &IndexEntryV4| {
+                    let (start, end) = (u32_to_ipv4(e.start_ip), u32_to_ipv4(e.end_ip));
+                    entry(start.into(), end.into(), e.region_id)
+                };
+                let before = entries.partition_point(|e| e.end_ip < ip);
+                let after = entries.partition_point(|e| e.start_ip <= ip);
+                (
+                    before.checked_sub(1).map(|i| matched(&entries[i])),
+                    entries.get(after).map(matched),
+                )
+            }
+            IpAddr::V6(ip) => {
+                let ip = ip.octets();
+                let entries = &index.entries_v6;
+                let matched = |e: &IndexEntryV6| {
+                    let (start, end) = (bytes_to_ipv6(e.start_ip), bytes_to_ipv6(e.end_ip));
+                    entry(start.into(), end.into(), e.region_id)
+                };
+                let before = entries.partition_point(|e| e.end_ip < ip);
+                let after = entries.partition_point(|e| e.start_ip <= ip);
+                (
+                    before.checked_sub(1).map(|i| matched(&entries[i])),
+                    entries.get(after).map(matched),
+                )
+            }
+        }
+    }
+
+    /// Search `ip`, returning the region and the prefix length of the smallest CIDR
+    /// network containing the matched range.
+    ///
+    /// For ranges that are not CIDR-aligned the containing network is wider than the
+    /// range itself; see `covering_prefix_len`.
+    ///
+    /// 查询 `ip`，返回区域及包含命中范围的最小 CIDR 网络前缀长度。范围未按 CIDR 对齐时，
+    /// 该网络会比范围本身更大，参见 `covering_prefix_len`。
+    pub fn search_with_prefix(&self, ip: IpAddr) -> Option<(String, u8)> {
+        let entry = self.lookup_entry(ip)?;
+        let prefix_len = entry.prefix_len();
+        Some((entry.region, prefix_len))
+    }
+
+    /// Returns true only if `ip` is matched by a single-address block (start == end == ip).
+    ///
+    /// 仅当 `ip` 命中的块为单地址块（起始 == 结束 == ip）时返回 true。
+    pub fn is_exact_match(&self, ip: IpAddr) -> bool {
+        let ip = self.meta.normalize_ip(ip);
+        if !self.meta.db_type.compare(&ip) {
+            return false;
+        }
+        match ip {
+            IpAddr::V4(ip) => {
+                let ip_num = ipv4_to_u32(ip);
+                self.find_v4(ip_num)
+                    .is_some_and(|e| e.start_ip == ip_num && e.end_ip == ip_num)
+            }
+            IpAddr::V6(ip) => {
+                let ip_bytes = ip.octets();
+                self.find_v6(&ip_bytes)
+                    .is_some_and(|e| e.start_ip == ip_bytes && e.end_ip == ip_bytes)
+            }
+        }
+    }
+
+    /// Report whether the IP is a start, end, or interior address of its covering block.
+    ///
+    /// Returns `None` if no block covers the IP.
+    ///
+    /// 判断 IP 是所在块的起始、结束还是内部地址；未覆盖时返回 `None`。
+    pub fn boundary_kind(&self, ip: IpAddr) -> Option<BoundaryKind> {
+        let ip = self.meta.normalize_ip(ip);
+        if !self.meta.db_type.compare(&ip) {
+            return None;
+        }
+        let kind = match ip {
+            IpAddr::V4(ip) => {
+                let ip_num = ipv4_to_u32(ip);
+                let entry = self.find_v4(ip_num)?;
+                if ip_num == entry.start_ip {
+                    BoundaryKind::Start
+                } else if ip_num == entry.end_ip {
+                    BoundaryKind::End
+                } else {
+                    BoundaryKind::Interior
                 }
-                None
             }
             IpAddr::V6(ip) => {
-                if self.memory_index.entries_v6.is_empty() {
-                    return None;
+                let ip_bytes = ip.octets();
+                let entry = self.find_v6(&ip_bytes)?;
+                if ip_bytes == entry.start_ip {
+                    BoundaryKind::Start
+                } else if ip_bytes == entry.end_ip {
+                    BoundaryKind::End
+                } else {
+                    BoundaryKind::Interior
                 }
-                let mut ip_bytes = [0u8; 16];
-                ip_bytes.copy_from_slice(&ip.octets());
-                let mut l = 0usize;
-                let mut h = self.memory_index.entries_v6.len() - 1;
-                while l <= h {
-                    let m = (l + h) >> 1;
-                    let entry = &self.memory_index.entries_v6[m];
-                    let cmp_start = compare_bytes(&ip_bytes, &entry.start_ip, 16);
-                    let cmp_end = compare_bytes(&ip_bytes, &entry.end_ip, 16);
-                    if cmp_start != std::cmp::Ordering::Less
-                        && cmp_end != std::cmp::Ordering::Greater
-                    {
-                        return Some(self.memory_index.regions.get(entry.region_id));
-                    } else if cmp_start == std::cmp::Ordering::Less {
-                        if m == 0 {
-                            break;
-                        }
-                        h = m - 1;
-                    } else {
-                        l = m + 1;
-                    }
+            }
+        };
+        Some(kind)
+    }
+
+    fn find_v4(&self, ip_num: u32) -> Option<&IndexEntryV4> {
+        self.find_v4_counted(ip_num, &mut ())
+    }
+
+    fn find_v4_counted(
+        &self,
+        ip_num: u32,
+        iterations: &mut impl Iterations,
+    ) -> Option<&IndexEntryV4> {
+        // Find the last entry starting at or before `ip_num`, then check its end.
+        let starts = &self.memory_index.starts_v4;
+        let (mut l, mut h) = (0usize, starts.len());
+        while l < h {
+            iterations.step();
+            let m = l + (h - l) / 2;
+            if starts[m] <= ip_num {
+                l = m + 1;
+            } else {
+                h = m;
+            }
+        }
+        let entry = self.memory_index.entries_v4.get(l.checked_sub(1)?)?;
+        (ip_num <= entry.end_ip).then_some(entry)
+    }
+
+    fn find_v6(&self, ip_bytes: &[u8; 16]) -> Option<&IndexEntryV6> {
+        self.find_v6_counted(ip_bytes, &mut ())
+    }
+
+    fn find_v6_counted(
+        &self,
+        ip_bytes: &[u8; 16],
+        iterations: &mut impl Iterations,
+    ) -> Option<&IndexEntryV6> {
+        let entries = &self.memory_index.entries_v6;
+        if entries.is_empty() {
+            return None;
+        }
+        let mut l = 0usize;
+        let mut h = entries.len() - 1;
+        while l <= h {
+            iterations.step();
+            let m = (l + h) >> 1;
+            let entry = &entries[m];
+            let cmp_start = compare_bytes(ip_bytes, &entry.start_ip, 16);
+            let cmp_end = compare_bytes(ip_bytes, &entry.end_ip, 16);
+            if cmp_start != std::cmp::Ordering::Less && cmp_end != std::cmp::Ordering::Greater {
+                return Some(entry);
+            } else if cmp_start == std::cmp::Ordering::Less {
+                if m == 0 {
+                    break;
                 }
-                None
+                h = m - 1;
+            } else {
+                l = m + 1;
             }
         }
+        None
     }
 
     /// Search a small batch of IP addresses.
@@ -175,6 +911,18 @@ impl CzdbMemory {
         ips.iter().map(|ip| self.search(*ip)).collect()
     }
 
+    /// Search a batch of IP addresses in parallel on the rayon thread pool.
+    ///
+    /// Results are in input order and equal to `search_many`.
+    ///
+    /// 在 rayon 线程池上并行批量查询 IP，结果按输入顺序排列，与 `search_many` 一致。
+    #[cfg(feature = "rayon")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "rayon")))]
+    pub fn search_many_par(&self, ips: &[IpAddr]) -> Vec<Option<String>> {
+        use rayon::prelude::*;
+        ips.par_iter().map(|ip| self.search(*ip)).collect()
+    }
+
     /// Search a batch of IP addresses and return borrowed strings.
     ///
     /// 批量查询 IP 并返回借用字符串。
@@ -182,6 +930,34 @@ impl CzdbMemory {
         ips.iter().map(|ip| self.search_ref(*ip)).collect()
     }
 
+    /// Search a batch of IP addresses, writing borrowed results into `out`.
+    ///
+    /// Lets callers reuse one output buffer across batches.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `out.len() != ips.len()`.
+    ///
+    /// 批量查询 IP 并将借用结果写入 `out`，便于复用输出缓冲区。`out` 与 `ips` 长度不同时 panic。
+    pub fn search_many_into<'a>(&'a self, ips: &[IpAddr], out: &mut [Option<&'a str>]) {
+        assert_eq!(out.len(), ips.len(), "output slice length must match input");
+        for (slot, ip) in out.iter_mut().zip(ips) {
+            *slot = self.search_ref(*ip);
+        }
+    }
+
+    /// Returns true if any IP in `ips` resolves to a region containing `region_needle`.
+    ///
+    /// Stops at the first match instead of resolving the whole batch.
+    ///
+    /// 判断 `ips` 中是否存在解析结果包含 `region_needle` 的 IP，命中第一个即返回。
+    pub fn any_in_region(&self, ips: &[IpAddr], region_needle: &str) -> bool {
+        ips.iter().any(|ip| {
+            self.search_ref(*ip)
+                .is_some_and(|region| region.contains(region_needle))
+        })
+    }
+
     /// Search a large batch by sorting and scanning.
     ///
     /// 对大批量 IP 进行排序后扫描查询。
@@ -189,9 +965,9 @@ impl CzdbMemory {
         let mut results = vec![None; ips.len()];
         let mut v4 = Vec::new();
         let mut v6 = Vec::new();
-        for (idx, ip) in ips.iter().copied().enumerate() {
-            match ip {
-                IpAddr::V4(ipv4) => v4.push((u32::from_be_bytes(ipv4.octets()), idx)),
+        for (idx, ip) in ips.iter().enumerate() {
+            match self.meta.normalize_ip(*ip) {
+                IpAddr::V4(ipv4) => v4.push((ipv4_to_u32(ipv4), idx)),
                 IpAddr::V6(ipv6) => v6.push((ipv6.octets(), idx)),
             }
         }
@@ -220,8 +996,11 @@ impl CzdbMemory {
             let mut entry_idx = 0usize;
             for (ip_bytes, original_idx) in v6 {
                 while entry_idx < self.memory_index.entries_v6.len()
-                    && compare_bytes(&self.memory_index.entries_v6[entry_idx].end_ip, &ip_bytes, 16)
-                        == std::cmp::Ordering::Less
+                    && compare_bytes(
+                        &self.memory_index.entries_v6[entry_idx].end_ip,
+                        &ip_bytes,
+                        16,
+                    ) == std::cmp::Ordering::Less
                 {
                     entry_idx += 1;
                 }
@@ -231,9 +1010,7 @@ impl CzdbMemory {
                 let entry = &self.memory_index.entries_v6[entry_idx];
                 let cmp_start = compare_bytes(&ip_bytes, &entry.start_ip, 16);
                 let cmp_end = compare_bytes(&ip_bytes, &entry.end_ip, 16);
-                if cmp_start != std::cmp::Ordering::Less
-                    && cmp_end != std::cmp::Ordering::Greater
-                {
+                if cmp_start != std::cmp::Ordering::Less && cmp_end != std::cmp::Ordering::Greater {
                     results[original_idx] = Some(self.memory_index.regions.get(entry.region_id));
                 }
             }
@@ -242,12 +1019,774 @@ impl CzdbMemory {
         results
     }
 
+    /// Search a batch and return `(ip, region)` pairs sorted ascending by IP.
+    ///
+    /// IPv4 addresses sort before IPv6 addresses. Duplicates are kept.
+    ///
+    /// 批量查询并返回按 IP 升序排列的 `(ip, region)` 对；IPv4 排在 IPv6 之前，保留重复项。
+    pub fn search_many_sorted(&self, ips: &[IpAddr]) -> Vec<(IpAddr, Option<String>)> {
+        let mut sorted = ips.to_vec();
+        sorted.sort_unstable();
+        let results = self.search_many_scan(&sorted);
+        sorted
+            .into_iter()
+            .zip(results)
+            .map(|(ip, region)| (ip, region.map(str::to_string)))
+            .collect()
+    }
+
+    /// Search a batch with `search_many_scan` and group the input positions by region.
+    ///
+    /// Returns each distinct region mapped to the indices into `ips` that resolved to it, in
+    /// ascending order, followed by the indices of the addresses that matched nothing.
+    ///
+    /// 使用 `search_many_scan` 批量查询，并按区域对输入位置分组。返回每个不同区域到 `ips`
+    /// 中解析为该区域的下标（升序）的映射，以及未命中任何区域的地址下标。
+    pub fn search_many_grouped(&self, ips: &[IpAddr]) -> (HashMap<String, Vec<usize>>, Vec<usize>) {
+        let mut groups = HashMap::<&str, Vec<usize>>::new();
+        let mut unmatched = Vec::new();
+        for (idx, region) in self.search_many_scan(ips).into_iter().enumerate() {
+            match region {
+                Some(region) => groups.entry(region).or_default().push(idx),
+                None => unmatched.push(idx),
+            }
+        }
+        let groups = groups
+            .into_iter()
+            .map(|(region, indices)| (region.to_string(), indices))
+            .collect();
+        (groups, unmatched)
+    }
+
+    /// Geo-map column mask; bit `n + 1` selects column `n`, bit 0 is reserved.
+    ///
+    /// 地理映射列掩码；第 `n + 1` 位选择第 `n` 列，第 0 位保留。
+    pub fn column_selection(&self) -> u64 {
+        self.meta.column_selection
+    }
+
+    /// Names of the selected geo-map columns (`country`, `province`, `city`, `district`,
+    /// `isp`), in the order `search` joins them. Columns the name table does not cover are
+    /// `"unknown"`; the record's inline data follows them in `search` results.
+    ///
+    /// 选中的地理映射列名称（`country`、`province`、`city`、`district`、`isp`），顺序与
+    /// `search` 拼接顺序一致；名称表未覆盖的列为 `"unknown"`。
+    /// `search` 结果中这些列之后为记录的内联数据。
+    pub fn selected_columns(&self) -> Vec<&'static str> {
+        self.meta.selected_columns()
+    }
+
+    /// Number of IP ranges (index blocks) in the database.
+    ///
+    /// 数据库中 IP 段（索引块）的数量。
+    pub fn entry_count(&self) -> usize {
+        match self.meta.db_type {
+            DbType::Ipv4 => self.memory_index.entries_v4.len(),
+            DbType::Ipv6 => self.memory_index.entries_v6.len(),
+        }
+    }
+
+    /// Returns the storage backend of this searcher.
+    ///
+    /// 返回该查询器的存储模式。
+    pub fn backend_kind(&self) -> BackendKind {
+        BackendKind::Memory
+    }
+
+    /// Attach a human-readable label, e.g. to identify this database in logs.
+    ///
+    /// 附加便于识别的标签，例如用于在日志中区分数据库。
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.meta.label = Some(label.into());
+        self
+    }
+
+    /// The label set with `with_label`, if any.
+    ///
+    /// 通过 `with_label` 设置的标签（若有）。
+    pub fn label(&self) -> Option<&str> {
+        self.meta.label.as_deref()
+    }
+
+    /// Inclusive address ranges covered by each header partition.
+    ///
+    /// 每个头部分区覆盖的地址闭区间。
+    pub fn header_partitions(&self) -> Vec<(IpAddr, IpAddr)> {
+        self.meta.header_partitions()
+    }
+
+    /// Returns the index entry at position `index`, in ascending address order.
+    ///
+    /// Returns `None` past the last entry.
+    ///
+    /// 按地址升序返回第 `index` 个索引条目；越界时返回 `None`。
+    pub fn entry_at(&self, index: usize) -> Option<(IpAddr, IpAddr, &str)> {
+        match self.meta.db_type {
+            DbType::Ipv4 => {
+                let entry = self.memory_index.entries_v4.get(index)?;
+                Some((
+                    IpAddr::V4(u32_to_ipv4(entry.start_ip)),
+                    IpAddr::V4(u32_to_ipv4(entry.end_ip)),
+                    self.memory_index.regions.get(entry.region_id),
+                ))
+            }
+            DbType::Ipv6 => {
+                let entry = self.memory_index.entries_v6.get(index)?;
+                Some((
+                    IpAddr::V6(bytes_to_ipv6(entry.start_ip)),
+                    IpAddr::V6(bytes_to_ipv6(entry.end_ip)),
+                    self.memory_index.regions.get(entry.region_id),
+                ))
+            }
+        }
+    }
+
+    /// Every entry overlapping `net`, as `(start, end, region)` in ascending address order.
+    ///
+    /// Ranges are returned whole, so the first and last may extend past `net`. A network of
+    /// the other address family yields nothing.
+    ///
+    /// 返回与 `net` 重叠的所有条目 `(start, end, region)`，按地址升序排列。范围按原样返回，
+    /// 首尾范围可能超出 `net`。地址族与数据库不一致时返回空。
+    #[cfg(feature = "ipnet")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "ipnet")))]
+    pub fn search_cidr(&self, net: ipnet::IpNet) -> Vec<(IpAddr, IpAddr, String)> {
+        let index = &self.memory_index;
+        let region = |id| index.regions.get(id).to_string();
+        match net {
+            ipnet::IpNet::V4(net) if self.meta.db_type == DbType::Ipv4 => {
+                let (first, last) = (ipv4_to_u32(net.network()), ipv4_to_u32(net.broadcast()));
+                let from = index.entries_v4.partition_point(|e| e.end_ip < first);
+                index.entries_v4[from..]
+                    .iter()
+                    .take_while(|e| e.start_ip <= last)
+                    .map(|e| {
+                        let start = IpAddr::V4(u32_to_ipv4(e.start_ip));
+                        (
+                            start,
+                            IpAddr::V4(u32_to_ipv4(e.end_ip)),
+                            region(e.region_id),
+                        )
+                    })
+                    .collect()
+            }
+            ipnet::IpNet::V6(net) if self.meta.db_type == DbType::Ipv6 => {
+                let (first, last) = (net.network().octets(), net.broadcast().octets());
+                let from = index.entries_v6.partition_point(|e| e.end_ip < first);
+                index.entries_v6[from..]
+                    .iter()
+                    .take_while(|e| e.start_ip <= last)
+                    .map(|e| {
+                        let start = IpAddr::V6(bytes_to_ipv6(e.start_ip));
+                        (
+                            start,
+                            IpAddr::V6(bytes_to_ipv6(e.end_ip)),
+                            region(e.region_id),
+                        )
+                    })
+                    .collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Iterate over every entry as `(start, end, region)`, in ascending address order.
+    ///
+    /// Use `entry_at` to walk the entries without allocating the region strings.
+    ///
+    /// 按地址升序遍历所有条目，产出 `(start, end, region)`；如需避免分配区域字符串，请使用 `entry_at`。
+    pub fn iter(&self) -> impl Iterator<Item = (IpAddr, IpAddr, String)> + '_ {
+        (0..self.entry_count())
+            .filter_map(|index| self.entry_at(index))
+            .map(|(start, end, region)| (start, end, region.to_string()))
+    }
+
+    /// Export every entry as newline-delimited JSON (`{"start","end","region"}` per line).
+    ///
+    /// 将全部条目导出为 JSONL，每行一个 `{"start","end","region"}` 对象。
+    pub fn export_jsonl<W: Write>(&self, w: &mut W) -> Result<(), CzError> {
+        for (start, end, region) in (0..).map_while(|index| self.entry_at(index)) {
+            write_jsonl_entry(w, start, end, region)?;
+        }
+        Ok(())
+    }
+
+    /// Export every entry as CSV, with a header row and one `start_ip,end_ip,...` row per entry.
+    ///
+    /// Without geo-map columns the third column is the whole `region`. Otherwise there is one
+    /// column per selected geo-map column, named as in `selected_columns`, followed by the
+    /// inline `other_data` as `isp`. Addresses use dotted IPv4 or canonical IPv6 notation.
+    ///
+    /// 将全部条目导出为 CSV：首行为表头，每个条目一行 `start_ip,end_ip,...`。无地理映射列时第三列为
+    /// 完整的 `region`；否则每个选中的地理映射列占一列，名称与 `selected_columns` 一致，
+    /// 最后一列 `isp` 为内联的 `other_data`。地址使用点分 IPv4 或规范 IPv6 格式。
+    pub fn export_csv<W: Write>(&self, mut out: W) -> io::Result<()> {
+        let columns = self.meta.structured_columns();
+        let mut header = vec!["start_ip", "end_ip"];
+        if columns.is_empty() {
+            header.push("region");
+        } else {
+            header.extend(self.meta.selected_columns());
+            header.push("isp");
+        }
+        write_csv_row(&mut out, &header)?;
+
+        for (start, end, region) in (0..).map_while(|index| self.entry_at(index)) {
+            let (start, end) = (start.to_string(), end.to_string());
+            if columns.is_empty() {
+                write_csv_row(&mut out, &[&start, &end, region])?;
+                continue;
+            }
+            let region = Region::from_joined(region.to_string(), &columns);
+            let mut extra = region.columns.iter().map(|(_, value)| value.as_str());
+            let mut row = vec![start.as_str(), end.as_str()];
+            row.extend(columns.iter().map(|column| {
+                let value = match column {
+                    0 => region.country.as_deref(),
+                    1 => region.province.as_deref(),
+                    2 => region.city.as_deref(),
+                    _ => extra.next(),
+                };
+                value.unwrap_or("")
+            }));
+            row.push(region.isp.as_deref().unwrap_or(""));
+            write_csv_row(&mut out, &row)?;
+        }
+        Ok(())
+    }
+
+    /// Iterate over the entries of header partition `partition`, in address order.
+    ///
+    /// Yields the entries whose index pointers fall in
+    /// `header_ptr[partition]..header_ptr[partition + 1]`; the last partition runs to the
+    /// end of the index. An out-of-range partition yields nothing.
+    ///
+    /// 按地址顺序遍历第 `partition` 个头部分区内的条目，即索引指针位于
+    /// `header_ptr[partition]..header_ptr[partition + 1]` 的条目；最后一个分区延伸到索引末尾。
+    /// 分区编号越界时不产生任何条目。
+    pub fn partition_entries(
+        &self,
+        partition: usize,
+    ) -> impl Iterator<Item = (IpAddr, IpAddr, &str)> + '_ {
+        let blen = self.meta.db_type.index_block_len();
+        let start_index = self.meta.start_index as usize;
+        let position = |ptr: u32| (ptr as usize).saturating_sub(start_index) / blen;
+        let range = match self.meta.header_ptr.get(partition) {
+            Some(&sptr) => {
+                let end = match self.meta.header_ptr.get(partition + 1) {
+                    Some(&eptr) => position(eptr),
+                    None => position(self.meta.end_index) + 1,
+                };
+                position(sptr)..end
+            }
+            None => 0..0,
+        };
+        range.map_while(|index| self.entry_at(index))
+    }
+
+    /// Lowest and highest addresses covered: the first entry's start and the last entry's end.
+    ///
+    /// Returns `None` for a database without entries.
+    ///
+    /// 返回覆盖的最低与最高地址，即首个条目的起始地址与最后一个条目的结束地址；无条目时返回 `None`。
+    pub fn address_bounds(&self) -> Option<(IpAddr, IpAddr)> {
+        let len = match self.meta.db_type {
+            DbType::Ipv4 => self.memory_index.entries_v4.len(),
+            DbType::Ipv6 => self.memory_index.entries_v6.len(),
+        };
+        let (first, _, _) = self.entry_at(0)?;
+        let (_, last, _) = self.entry_at(len.checked_sub(1)?)?;
+        Some((first, last))
+    }
+
+    /// Returns true if every range in `other` is fully covered by this database.
+    ///
+    /// 判断 `other` 中的所有区间是否都被当前数据库完整覆盖。
+    pub fn covers_all_of(&self, other: &CzdbMemory) -> bool {
+        self.uncovered_ranges(other).is_empty()
+    }
+
+    /// Returns the inclusive ranges of `other` that this database does not cover.
+    ///
+    /// Databases of different IP versions share no coverage, so every range of `other`
+    /// is returned in that case.
+    ///
+    /// 返回 `other` 中未被当前数据库覆盖的闭区间；IP 版本不同时返回 `other` 的全部区间。
+    pub fn uncovered_ranges(&self, other: &CzdbMemory) -> Vec<(IpAddr, IpAddr)> {
+        let db_type = other.meta.db_type;
+        let to_ip = |n: u128| numeric_to_ip(n, db_type);
+        let ours: Vec<(u128, u128)> = if self.meta.db_type == db_type {
+            self.numeric_ranges().collect()
+        } else {
+            Vec::new()
+        };
+
+        let mut gaps = Vec::new();
+        let mut idx = 0usize;
+        for (start, end) in other.numeric_ranges() {
+            let mut cursor = start;
+            loop {
+                while idx < ours.len() && ours[idx].1 < cursor {
+                    idx += 1;
+                }
+                match ours.get(idx) {
+                    Some(&(s, e)) if s <= cursor => {
+                        if e >= end {
+                            break;
+                        }
+                        cursor = e + 1;
+                    }
+                    Some(&(s, _)) if s <= end => {
+                        gaps.push((to_ip(cursor), to_ip(s - 1)));
+                        cursor = s;
+                    }
+                    _ => {
+                        gaps.push((to_ip(cursor), to_ip(end)));
+                        break;
+                    }
+                }
+            }
+        }
+        gaps
+    }
+
+    /// Reports adjacent entries whose ranges overlap, as `(start, end, next_start, next_end)`.
+    ///
+    /// Entries are expected to be sorted and disjoint; any pair where an entry ends at or
+    /// after the next one starts is returned.
+    ///
+    /// 报告地址范围重叠的相邻条目，格式为 `(start, end, next_start, next_end)`。
+    /// 条目应有序且互不相交，若某条目的结束地址不小于下一条目的起始地址则返回该对。
+    pub fn find_overlaps(&self) -> Vec<(IpAddr, IpAddr, IpAddr, IpAddr)> {
+        let db_type = self.meta.db_type;
+        let to_ip = |n: u128| numeric_to_ip(n, db_type);
+        let ranges: Vec<(u128, u128)> = self.numeric_ranges().collect();
+        ranges
+            .windows(2)
+            .filter(|pair| pair[0].1 >= pair[1].0)
+            .map(|pair| {
+                (
+                    to_ip(pair[0].0),
+                    to_ip(pair[0].1),
+                    to_ip(pair[1].0),
+                    to_ip(pair[1].1),
+                )
+            })
+            .collect()
+    }
+
+    fn numeric_ranges(&self) -> Box<dyn Iterator<Item = (u128, u128)> + '_> {
+        match self.meta.db_type {
+            DbType::Ipv4 => Box::new(
+                self.memory_index
+                    .entries_v4
+                    .iter()
+                    .map(|e| (e.start_ip as u128, e.end_ip as u128)),
+            ),
+            DbType::Ipv6 => Box::new(self.memory_index.entries_v6.iter().map(|e| {
+                (
+                    u128::from_be_bytes(e.start_ip),
+                    u128::from_be_bytes(e.end_ip),
+                )
+            })),
+        }
+    }
+
+    /// Returns the `n` regions referenced by the most index entries, with their counts.
+    ///
+    /// Ties are broken by the order in which regions first appear in the index.
+    ///
+    /// 返回被最多索引条目引用的前 `n` 个区域及其引用次数；次数相同时按首次出现顺序排列。
+    pub fn top_regions(&self, n: usize) -> Vec<(&str, usize)> {
+        let mut counts = vec![0usize; self.memory_index.regions.spans.len()];
+        let region_ids = self
+            .memory_index
+            .entries_v4
+            .iter()
+            .map(|e| e.region_id)
+            .chain(self.memory_index.entries_v6.iter().map(|e| e.region_id));
+        for region_id in region_ids {
+            counts[region_id as usize] += 1;
+        }
+
+        let mut ranked: Vec<(u32, usize)> = (0u32..).zip(counts).collect();
+        ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked
+            .into_iter()
+            .take(n)
+            .map(|(region_id, count)| (self.memory_index.regions.get(region_id), count))
+            .collect()
+    }
+
+    /// Check the built index for consistency without querying every address.
+    ///
+    /// Verifies that every entry has `start <= end` and lies strictly after the previous one
+    /// (`CzError::IndexOutOfOrder` naming the entry otherwise), that every region id
+    /// resolves to a span inside the string pool, that the entry count matches
+    /// `start_index`/`end_index`, and that the IPv4 start-address array mirrors the entries
+    /// (`CzError::DatabaseFileCorrupted` otherwise).
+    ///
+    /// 在不逐个查询地址的情况下检查已构建索引的一致性：每个条目须满足 `start <= end`
+    /// 且严格位于前一条目之后，否则返回指明条目位置的 `CzError::IndexOutOfOrder`；
+    /// 每个区域 ID 须指向字符串池内的有效区间，条目数量须与 `start_index`/`end_index` 一致，
+    /// 且 IPv4 起始地址数组须与条目一一对应，否则返回 `CzError::DatabaseFileCorrupted`。
+    pub fn validate(&self) -> Result<(), CzError> {
+        let index = &self.memory_index;
+        let blen = self.meta.db_type.index_block_len();
+        let (start, end) = (self.meta.start_index as usize, self.meta.end_index as usize);
+        if end < start || !(end - start).is_multiple_of(blen) {
+            return Err(CzError::DatabaseFileCorrupted);
+        }
+        let (count, other_family) = match self.meta.db_type {
+            DbType::Ipv4 => (index.entries_v4.len(), index.entries_v6.len()),
+            DbType::Ipv6 => (index.entries_v6.len(), index.entries_v4.len()),
+        };
+        if count != self.meta.entry_count() || other_family != 0 {
+            return Err(CzError::DatabaseFileCorrupted);
+        }
+        if index.starts_v4.len() != index.entries_v4.len()
+            || index
+                .starts_v4
+                .iter()
+                .zip(&index.entries_v4)
+                .any(|(&start, entry)| start != entry.start_ip)
+        {
+            return Err(CzError::DatabaseFileCorrupted);
+        }
+
+        let ranges: Vec<(u128, u128, u32)> = match self.meta.db_type {
+            DbType::Ipv4 => index
+                .entries_v4
+                .iter()
+                .map(|e| (e.start_ip.into(), e.end_ip.into(), e.region_id))
+                .collect(),
+            DbType::Ipv6 => index
+                .entries_v6
+                .iter()
+                .map(|e| {
+                    let (start, end) = (
+                        u128::from_be_bytes(e.start_ip),
+                        u128::from_be_bytes(e.end_ip),
+                    );
+                    (start, end, e.region_id)
+                })
+                .collect(),
+        };
+        let mut previous_end = None;
+        for (at, &(start, end, region_id)) in ranges.iter().enumerate() {
+            if start > end || previous_end.is_some_and(|previous| previous >= start) {
+                return Err(CzError::IndexOutOfOrder { at });
+            }
+            previous_end = Some(end);
+            let span = index
+                .regions
+                .spans
+                .get(region_id as usize)
+                .ok_or(CzError::DatabaseFileCorrupted)?;
+            if index
+                .regions
+                .data
+                .get(span.start..span.start + span.len)
+                .is_none()
+            {
+                return Err(CzError::DatabaseFileCorrupted);
+            }
+        }
+        Ok(())
+    }
+
+    /// Approximate heap bytes held by the in-memory index: the v4 start-address array, the
+    /// v4 and v6 entry vectors and the region string pools, including those built for
+    /// `search_lang`, with their spans.
+    ///
+    /// Counts allocated capacity, not just the used length. Allocator overhead, the header
+    /// index and the geo map kept in the metadata are not included.
+    ///
+    /// 内存索引占用的堆内存估算值（字节）：v4 起始地址数组、v4 与 v6 条目数组、
+    /// 区域字符串池（包括为 `search_lang` 构建的字符串池）及其区间数组。
+    /// 按已分配容量计算；不包含分配器开销、头部索引以及元数据中保留的地理映射。
+    pub fn memory_usage(&self) -> usize {
+        let index = &self.memory_index;
+        index.starts_v4.capacity() * size_of::<u32>()
+            + index.entries_v4.capacity() * size_of::<IndexEntryV4>()
+            + index.entries_v6.capacity() * size_of::<IndexEntryV6>()
+            + [&index.regions]
+                .into_iter()
+                .chain(index.lang_regions.values())
+                .map(|pool| pool.data.len() + pool.spans.capacity() * size_of::<RegionSpan>())
+                .sum::<usize>()
+    }
+
+    /// Length statistics over the distinct regions held in the string pool.
+    ///
+    /// `max_len` is the largest buffer any `search` result can need.
+    ///
+    /// 字符串池中不同区域的长度统计；`max_len` 即任意 `search` 结果所需的最大缓冲区大小。
+    pub fn region_length_stats(&self) -> RegionStats {
+        let regions = &self.memory_index.regions;
+        let spans = &regions.spans;
+        if spans.is_empty() {
+            return RegionStats::default();
+        }
+        let total: usize = spans.iter().map(|span| span.len).sum();
+        RegionStats {
+            distinct: spans.len(),
+            min_len: spans.iter().map(|span| span.len).min().unwrap_or(0),
+            max_len: spans.iter().map(|span| span.len).max().unwrap_or(0),
+            mean_len: total as f64 / spans.len() as f64,
+            total_bytes: regions.data.len(),
+        }
+    }
+
+    /// Number of region strings decoded so far that were not valid UTF-8.
+    ///
+    /// 目前为止解析到的非法 UTF-8 区域字符串数量。
+    pub fn invalid_utf8_count(&self) -> usize {
+        self.meta.invalid_utf8_count()
+    }
+
+    /// Returns the decrypted geo-map bytes, if the database has one.
+    ///
+    /// 返回解密后的地理映射数据（若存在）。
+    pub fn raw_geo_map(&self) -> Option<&[u8]> {
+        self.meta.raw_geo_map()
+    }
+
     /// Returns the database IP version.
     ///
     /// 返回数据库类型（IPv4 或 IPv6）。
     pub fn db_type(&self) -> DbType {
         self.meta.db_type
     }
+
+    /// Whether the database can answer queries for `ip`'s address family.
+    ///
+    /// 判断数据库能否查询 `ip` 所属的地址族。
+    pub fn supports(&self, ip: IpAddr) -> bool {
+        self.meta.db_type.compare(&self.meta.normalize_ip(ip))
+    }
+
+    /// The IP version this database answers for.
+    ///
+    /// 数据库支持查询的 IP 版本。
+    pub fn supported_version(&self) -> DbType {
+        self.meta.db_type
+    }
+
+    /// Format version, client id and expiry date read from the hyper header.
+    ///
+    /// 从超头读取的格式版本、客户端 ID 与到期日期。
+    pub fn info(&self) -> DbInfo {
+        self.meta.info()
+    }
+
+    /// Whether the database was past its expiry date when opened.
+    ///
+    /// Only ever true when opened with `CzdbOptions::allow_expired`.
+    ///
+    /// 打开时数据库是否已过期；仅在使用 `CzdbOptions::allow_expired` 打开时可能为 true。
+    pub fn is_expired(&self) -> bool {
+        self.meta.expired
+    }
+
+    /// Header geometry as `(padding_size, encrypted_block_size)`.
+    ///
+    /// The data section starts at `12 + padding_size + encrypted_block_size`.
+    ///
+    /// 返回头部布局 `(padding_size, encrypted_block_size)`；
+    /// 数据区起始于 `12 + padding_size + encrypted_block_size`。
+    pub fn header_geometry(&self) -> (u32, u32) {
+        self.meta.header_geometry()
+    }
+
+    /// Length in bytes of the data section, everything after the hyper header and padding.
+    ///
+    /// 数据区（超头与填充之后的全部内容）的字节长度。
+    pub fn data_section_len(&self) -> usize {
+        self.meta.file_size as usize
+    }
+}
+
+/// Decrypt the hyper header and parse the metadata, returning it with the data offset.
+fn load_meta(
+    data: &[u8],
+    key_bytes: &[u8],
+    options: CzdbOptions,
+) -> Result<(DbMeta, usize), CzError> {
+    let mut cursor = Cursor::new(data);
+    let header = read_hyper_header(&mut cursor, data.len() as u64, key_bytes, &options)?;
+    let data_offset = header.data_offset();
+    if data_offset >= data.len() as u64 {
+        return Err(CzError::NoDataSection);
+    }
+    let data_offset = data_offset as usize;
+    let meta = parse_meta_from_bytes(
+        &data[data_offset..],
+        data.len() as u64,
+        &header,
+        key_bytes,
+        options,
+    )?;
+    Ok((meta, data_offset))
+}
+
+/// Rebuild a file from `(offset, bytes)` segments, merging overlaps whose bytes agree.
+fn reassemble(mut segments: Vec<(u64, Vec<u8>)>) -> io::Result<Vec<u8>> {
+    segments.sort_by_key(|(offset, _)| *offset);
+    let mut data = Vec::new();
+    for (offset, bytes) in segments {
+        let invalid = |msg: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("segment at offset {offset} {msg}"),
+            )
+        };
+        let start = usize::try_from(offset).map_err(|_| invalid("is out of range"))?;
+        if start > data.len() {
+            return Err(invalid("leaves a gap after the previous segment"));
+        }
+        let overlap = (data.len() - start).min(bytes.len());
+        if data[start..start + overlap] != bytes[..overlap] {
+            return Err(invalid("conflicts with the overlapping segment"));
+        }
+        data.extend_from_slice(&bytes[overlap..]);
+    }
+    Ok(data)
+}
+
+/// Magic bytes opening an index sidecar written by `CzdbMemory::save_index`.
+const SIDECAR_MAGIC: &[u8; 8] = b"CZDBIDX1";
+
+/// Decoding options that change the contents of a built index, packed into one byte.
+fn sidecar_options_tag(meta: &DbMeta) -> u8 {
+    let options = &meta.options;
+    let byte_order = match options.ipv4_byte_order {
+        Ipv4ByteOrder::BigEndian => 0,
+        Ipv4ByteOrder::LittleEndian => 1,
+        Ipv4ByteOrder::Detect => 2,
+    };
+    options.normalize_whitespace as u8
+        | (options.lossy_utf8 as u8) << 1
+        | byte_order << 2
+        | (options.region_decoder.is_some() as u8) << 4
+}
+
+/// Write `index` as a little-endian sidecar keyed by the database content hash.
+fn write_index_sidecar<W: Write>(
+    w: &mut W,
+    index: &MemoryIndex,
+    meta: &DbMeta,
+    content_hash: u64,
+) -> io::Result<()> {
+    w.write_all(SIDECAR_MAGIC)?;
+    w.write_u64::<LittleEndian>(content_hash)?;
+    w.write_u8(sidecar_options_tag(meta))?;
+    w.write_u64::<LittleEndian>(index.entries_v4.len() as u64)?;
+    w.write_u64::<LittleEndian>(index.entries_v6.len() as u64)?;
+    w.write_u64::<LittleEndian>(index.regions.spans.len() as u64)?;
+    w.write_u64::<LittleEndian>(index.regions.data.len() as u64)?;
+    for entry in &index.entries_v4 {
+        w.write_u32::<LittleEndian>(entry.start_ip)?;
+        w.write_u32::<LittleEndian>(entry.end_ip)?;
+        w.write_u32::<LittleEndian>(entry.region_id)?;
+    }
+    for entry in &index.entries_v6 {
+        w.write_all(&entry.start_ip)?;
+        w.write_all(&entry.end_ip)?;
+        w.write_u32::<LittleEndian>(entry.region_id)?;
+    }
+    for span in &index.regions.spans {
+        w.write_u64::<LittleEndian>(span.start as u64)?;
+        w.write_u64::<LittleEndian>(span.len as u64)?;
+    }
+    w.write_all(index.regions.data.as_bytes())
+}
+
+/// Read the sidecar at `path`, returning `None` if it is missing, malformed or was saved
+/// for a different database file or different decoding options.
+///
+/// Per-language pools are not saved, so the sidecar is never used when languages are
+/// registered.
+fn read_index_sidecar(path: &str, meta: &DbMeta, content_hash: u64) -> Option<MemoryIndex> {
+    if !meta.options.language_columns.is_empty() {
+        return None;
+    }
+    let bytes = std::fs::read(path).ok()?;
+    let mut r = Cursor::new(bytes.as_slice());
+    let mut magic = [0u8; 8];
+    r.read_exact(&mut magic).ok()?;
+    if &magic != SIDECAR_MAGIC
+        || r.read_u64::<LittleEndian>().ok()? != content_hash
+        || r.read_u8().ok()? != sidecar_options_tag(meta)
+    {
+        return None;
+    }
+    let v4_count = r.read_u64::<LittleEndian>().ok()? as usize;
+    let v6_count = r.read_u64::<LittleEndian>().ok()? as usize;
+    let span_count = r.read_u64::<LittleEndian>().ok()? as usize;
+    let data_len = r.read_u64::<LittleEndian>().ok()? as usize;
+    let expected = v4_count
+        .checked_mul(12)?
+        .checked_add(v6_count.checked_mul(36)?)?
+        .checked_add(span_count.checked_mul(16)?)?
+        .checked_add(data_len)?;
+    if bytes.len() - r.position() as usize != expected {
+        return None;
+    }
+
+    let mut entries_v4 = Vec::with_capacity(v4_count);
+    for _ in 0..v4_count {
+        entries_v4.push(IndexEntryV4 {
+            start_ip: r.read_u32::<LittleEndian>().ok()?,
+            end_ip: r.read_u32::<LittleEndian>().ok()?,
+            region_id: r.read_u32::<LittleEndian>().ok()?,
+        });
+    }
+    let mut entries_v6 = Vec::with_capacity(v6_count);
+    for _ in 0..v6_count {
+        let mut start_ip = [0u8; 16];
+        let mut end_ip = [0u8; 16];
+        r.read_exact(&mut start_ip).ok()?;
+        r.read_exact(&mut end_ip).ok()?;
+        entries_v6.push(IndexEntryV6 {
+            start_ip,
+            end_ip,
+            region_id: r.read_u32::<LittleEndian>().ok()?,
+        });
+    }
+    let mut spans = Vec::with_capacity(span_count);
+    for _ in 0..span_count {
+        spans.push(RegionSpan {
+            start: r.read_u64::<LittleEndian>().ok()? as usize,
+            len: r.read_u64::<LittleEndian>().ok()? as usize,
+        });
+    }
+    let data = std::str::from_utf8(&bytes[r.position() as usize..]).ok()?;
+
+    let spans_valid = spans.iter().all(|span| {
+        span.start
+            .checked_add(span.len)
+            .is_some_and(|end| data.get(span.start..end).is_some())
+    });
+    let ids_valid = entries_v4
+        .iter()
+        .map(|e| e.region_id)
+        .chain(entries_v6.iter().map(|e| e.region_id))
+        .all(|region_id| (region_id as usize) < spans.len());
+    if !spans_valid || !ids_valid {
+        return None;
+    }
+
+    Some(MemoryIndex {
+        starts_v4: entries_v4.iter().map(|e| e.start_ip).collect(),
+        entries_v4,
+        entries_v6,
+        regions: RegionPool {
+            data: data.into(),
+            spans,
+        },
+        lang_regions: HashMap::new(),
+    })
 }
 
 fn build_memory_index(bindata: &[u8], meta: &DbMeta) -> Result<MemoryIndex, CzError> {
@@ -266,9 +1805,14 @@ fn build_memory_index(bindata: &[u8], meta: &DbMeta) -> Result<MemoryIndex, CzEr
     let total_blocks = (end - start) / blen + 1;
     let mut entries_v4 = Vec::with_capacity(total_blocks);
     let mut entries_v6 = Vec::with_capacity(total_blocks);
-    let mut regions = Vec::<RegionSpan>::new();
-    let mut region_text = String::new();
-    let mut region_cache = HashMap::<(usize, usize), usize>::new();
+    let mut regions = RegionPoolBuilder::default();
+    let mut lang_regions: Vec<_> = meta
+        .options
+        .language_columns
+        .iter()
+        .map(|(lang, &mask)| (lang, mask, RegionPoolBuilder::default()))
+        .collect();
+    let mut region_cache = HashMap::<(usize, usize), u32>::new();
 
     let mut p = start;
     while p <= end {
@@ -293,19 +1837,23 @@ fn build_memory_index(bindata: &[u8], meta: &DbMeta) -> Result<MemoryIndex, CzEr
                 if data_ptr + data_len > bindata.len() {
                     return Err(CzError::DatabaseFileCorrupted);
                 }
-                let region = decode_region_from_bytes(
-                    &bindata[data_ptr..data_ptr + data_len],
-                    meta,
-                )
-                .ok_or(CzError::DatabaseFileCorrupted)?;
-                let start_offset = region_text.len();
-                region_text.push_str(&region);
-                let len = region.len();
-                let id = regions.len();
-                regions.push(RegionSpan {
-                    start: start_offset,
-                    len,
-                });
+                let site = RecordSite {
+                    ip: None,
+                    data_ptr,
+                    data_len,
+                };
+                let region_bytes = &bindata[data_ptr..data_ptr + data_len];
+                let region = decode_region_from_bytes(region_bytes, meta, site)
+                    .ok_or(CzError::DatabaseFileCorrupted)?;
+                // Region ids are stored as `u32` to keep index entries compact.
+                let id = u32::try_from(regions.spans.len())
+                    .map_err(|_| CzError::DatabaseFileCorrupted)?;
+                regions.push(&region);
+                for (_, mask, pool) in &mut lang_regions {
+                    let region = decode_region_with_columns(region_bytes, meta, *mask, site)
+                        .ok_or(CzError::DatabaseFileCorrupted)?;
+                    pool.push(&region);
+                }
                 region_cache.insert((data_ptr, data_len), id);
                 id
             }
@@ -330,21 +1878,85 @@ fn build_memory_index(bindata: &[u8], meta: &DbMeta) -> Result<MemoryIndex, CzEr
         p += blen;
     }
 
+    if use_little_endian_v4(&entries_v4, meta.options.ipv4_byte_order) {
+        for entry in &mut entries_v4 {
+            entry.start_ip = entry.start_ip.swap_bytes();
+            entry.end_ip = entry.end_ip.swap_bytes();
+        }
+    }
+
     Ok(MemoryIndex {
+        starts_v4: entries_v4.iter().map(|e| e.start_ip).collect(),
         entries_v4,
         entries_v6,
-        regions: RegionPool {
-            data: region_text.into_boxed_str(),
-            spans: regions,
-        },
+        regions: regions.finish(),
+        lang_regions: lang_regions
+            .into_iter()
+            .map(|(lang, _, pool)| (lang.clone(), pool.finish()))
+            .collect(),
     })
 }
 
+/// Number of leading entries inspected when detecting the IPv4 byte order.
+const BYTE_ORDER_PROBE: usize = 16;
+
+/// Decide whether big-endian decoded IPv4 entries should be reinterpreted as little-endian.
+fn use_little_endian_v4(entries: &[IndexEntryV4], order: Ipv4ByteOrder) -> bool {
+    let is_monotonic = |swap: bool| {
+        let ip = |n: u32| if swap { n.swap_bytes() } else { n };
+        let probe = &entries[..entries.len().min(BYTE_ORDER_PROBE)];
+        probe.iter().all(|e| ip(e.start_ip) <= ip(e.end_ip))
+            && probe
+                .windows(2)
+                .all(|w| ip(w[0].end_ip) < ip(w[1].start_ip))
+    };
+    match order {
+        Ipv4ByteOrder::BigEndian => false,
+        Ipv4ByteOrder::LittleEndian => true,
+        Ipv4ByteOrder::Detect => !is_monotonic(false) && is_monotonic(true),
+    }
+}
+
+impl Searcher for CzdbMemory {
+    fn lookup(&self, ip: IpAddr) -> Option<String> {
+        self.search(ip)
+    }
+
+    fn db_type(&self) -> DbType {
+        self.supported_version()
+    }
+
+    fn backend_kind(&self) -> BackendKind {
+        CzdbMemory::backend_kind(self)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{CacheKey, ResultCache};
+    use crate::{
+        Clock,
+        fixture::{FixtureBuilder, TempDb, geo_columns, region_payload, test_key},
+    };
+    use chrono::NaiveDate;
     use rmpv::{Value, encode::write_value};
-    use std::net::Ipv4Addr;
+    use std::{
+        net::Ipv4Addr,
+        sync::{
+            Arc, Mutex,
+            atomic::{AtomicUsize, Ordering},
+        },
+    };
+
+    #[derive(Debug)]
+    struct FixedClock(NaiveDate);
+
+    impl Clock for FixedClock {
+        fn today(&self) -> NaiveDate {
+            self.0
+        }
+    }
 
     fn build_test_db() -> CzdbMemory {
         let block_len = DbType::Ipv4.index_block_len();
@@ -396,12 +2008,25 @@ mod tests {
             geo_map_data: None,
             start_index: first_offset as u32,
             end_index: offset as u32,
+            options: CzdbOptions::default(),
+            invalid_utf8: Default::default(),
+            padding_size: 0,
+            encrypted_block_size: 0,
+            file_size: 0,
+            label: None,
+            fingerprint: 0,
+            info: DbInfo::default(),
+            expired: false,
         };
 
         let memory_index = build_memory_index(&bindata, &meta).unwrap();
 
         let _ = bindata;
-        CzdbMemory { meta, memory_index }
+        CzdbMemory {
+            meta,
+            memory_index,
+            source: None,
+        }
     }
 
     #[test]
@@ -413,6 +2038,937 @@ mod tests {
         );
     }
 
+    #[test]
+    fn search_v4_checks_both_ends_and_gaps() {
+        let db = build_test_db();
+        let search = |a, b, c, d| db.search_ref(IpAddr::V4(Ipv4Addr::new(a, b, c, d)));
+        assert_eq!(search(0, 0, 0, 0), None);
+        assert_eq!(search(1, 1, 0, 255), None);
+        assert_eq!(search(1, 1, 1, 255), Some("region1"));
+        assert_eq!(search(1, 1, 2, 0), None);
+        assert_eq!(search(2, 2, 2, 0), Some("region2"));
+        assert_eq!(search(2, 2, 2, 255), Some("region2"));
+        assert_eq!(search(255, 255, 255, 255), None);
+    }
+
+    #[test]
+    fn normalize_whitespace_collapses_other_data() {
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.255.255.255", "  中国\t 电信   AS4134 ")
+            .build();
+        let ip = IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4));
+
+        let raw = CzdbMemory::from_bytes(bytes.clone(), &test_key()).unwrap();
+        assert_eq!(raw.search(ip).as_deref(), Some("  中国\t 电信   AS4134 "));
+
+        let options = CzdbOptions::new().normalize_whitespace(true);
+        let normalized = CzdbMemory::from_bytes_with_options(bytes, &test_key(), options).unwrap();
+        assert_eq!(normalized.search(ip).as_deref(), Some("中国 电信 AS4134"));
+    }
+
+    #[test]
+    fn boundary_kind_classifies_block_positions() {
+        let db = build_test_db();
+        let kind = |ip: [u8; 4]| db.boundary_kind(IpAddr::V4(Ipv4Addr::from(ip)));
+        assert_eq!(kind([1, 1, 1, 0]), Some(BoundaryKind::Start));
+        assert_eq!(kind([1, 1, 1, 255]), Some(BoundaryKind::End));
+        assert_eq!(kind([2, 2, 2, 7]), Some(BoundaryKind::Interior));
+        assert_eq!(kind([3, 3, 3, 3]), None);
+    }
+
+    #[test]
+    fn header_partitions_span_consecutive_header_ips() {
+        let db = build_test_db();
+        let v4 = |a, b, c, d| IpAddr::V4(Ipv4Addr::new(a, b, c, d));
+        assert_eq!(
+            db.header_partitions(),
+            vec![
+                (v4(1, 1, 1, 0), v4(2, 2, 1, 255)),
+                (v4(2, 2, 2, 0), v4(255, 255, 255, 255)),
+            ]
+        );
+    }
+
+    #[test]
+    fn uncovered_ranges_reports_lost_coverage() {
+        let old = CzdbMemory::from_bytes(
+            FixtureBuilder::new(DbType::Ipv4)
+                .block("1.0.0.0", "1.0.0.255", "a")
+                .block("1.0.1.0", "1.0.3.255", "b")
+                .build(),
+            &test_key(),
+        )
+        .unwrap();
+        let new = CzdbMemory::from_bytes(
+            FixtureBuilder::new(DbType::Ipv4)
+                .block("1.0.0.0", "1.0.1.127", "a")
+                .block("1.0.2.0", "1.0.4.255", "b")
+                .build(),
+            &test_key(),
+        )
+        .unwrap();
+        let v4 = |a, b, c, d| IpAddr::V4(Ipv4Addr::new(a, b, c, d));
+
+        assert!(!new.covers_all_of(&old));
+        assert_eq!(
+            new.uncovered_ranges(&old),
+            vec![(v4(1, 0, 1, 128), v4(1, 0, 1, 255))]
+        );
+        assert_eq!(
+            old.uncovered_ranges(&new),
+            vec![(v4(1, 0, 4, 0), v4(1, 0, 4, 255))]
+        );
+        assert!(old.covers_all_of(&old));
+    }
+
+    #[test]
+    fn find_overlaps_reports_overlapping_neighbours() {
+        let db = CzdbMemory::from_bytes(
+            FixtureBuilder::new(DbType::Ipv4)
+                .block("1.0.0.0", "1.0.1.10", "a")
+                .block("1.0.1.0", "1.0.1.255", "b")
+                .block("1.0.2.0", "1.0.2.255", "c")
+                .build(),
+            &test_key(),
+        )
+        .unwrap();
+        let v4 = |a, b, c, d| IpAddr::V4(Ipv4Addr::new(a, b, c, d));
+        assert_eq!(
+            db.find_overlaps(),
+            vec![(
+                v4(1, 0, 0, 0),
+                v4(1, 0, 1, 10),
+                v4(1, 0, 1, 0),
+                v4(1, 0, 1, 255)
+            )]
+        );
+        assert!(build_test_db().find_overlaps().is_empty());
+    }
+
+    #[test]
+    fn detect_byte_order_handles_both_ipv4_layouts() {
+        let fixture = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.0.0.255", "a")
+            .block("1.0.1.0", "1.0.1.255", "b")
+            .block("1.0.2.0", "1.0.3.255", "c");
+        let detect = CzdbOptions::new().ipv4_byte_order(Ipv4ByteOrder::Detect);
+        let ip = IpAddr::V4(Ipv4Addr::new(1, 0, 0, 5));
+
+        let big = CzdbMemory::from_bytes_with_options(fixture.build(), &test_key(), detect.clone())
+            .unwrap();
+        assert_eq!(big.search_ref(ip), Some("a"));
+
+        let mut little_bytes = fixture.build();
+        for n in 0..3 {
+            let pos = fixture.block_pos(n);
+            little_bytes[pos..pos + 4].reverse();
+            little_bytes[pos + 4..pos + 8].reverse();
+        }
+        let misread = CzdbMemory::from_bytes(little_bytes.clone(), &test_key()).unwrap();
+        assert_ne!(misread.search_ref(ip), Some("a"));
+        let little =
+            CzdbMemory::from_bytes_with_options(little_bytes, &test_key(), detect).unwrap();
+        assert_eq!(little.search_ref(ip), Some("a"));
+        assert_eq!(
+            little.search_ref(IpAddr::V4(Ipv4Addr::new(1, 0, 3, 255))),
+            Some("c")
+        );
+    }
+
+    #[test]
+    fn top_regions_ranks_by_entry_count() {
+        let db = CzdbMemory::from_bytes(
+            FixtureBuilder::new(DbType::Ipv4)
+                .block("1.0.0.0", "1.0.0.255", "a")
+                .block("1.0.1.0", "1.0.1.255", "b")
+                .block("1.0.2.0", "1.0.2.255", "b")
+                .block("1.0.3.0", "1.0.3.255", "c")
+                .build(),
+            &test_key(),
+        )
+        .unwrap();
+        assert_eq!(db.top_regions(2), vec![("b", 2), ("a", 1)]);
+        assert_eq!(db.top_regions(10).len(), 3);
+    }
+
+    #[test]
+    fn ipv4_entries_pack_without_padding() {
+        assert_eq!(std::mem::size_of::<IndexEntryV4>(), 12);
+        assert_eq!(std::mem::size_of::<IndexEntryV6>(), 36);
+    }
+
+    #[test]
+    fn entry_at_returns_entries_by_position() {
+        let db = build_test_db();
+        let v4 = |a, b, c, d| IpAddr::V4(Ipv4Addr::new(a, b, c, d));
+        assert_eq!(
+            db.entry_at(1),
+            Some((v4(2, 2, 2, 0), v4(2, 2, 2, 255), "region2"))
+        );
+        assert_eq!(db.entry_at(2), None);
+    }
+
+    #[test]
+    fn search_many_into_reuses_output_buffer() {
+        let db = build_test_db();
+        let ips = [
+            IpAddr::V4(Ipv4Addr::new(2, 2, 2, 2)),
+            IpAddr::V4(Ipv4Addr::new(3, 3, 3, 3)),
+        ];
+        let mut out = [Some("stale"); 2];
+        db.search_many_into(&ips, &mut out);
+        assert_eq!(out, [Some("region2"), None]);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn search_many_par_matches_search_many_on_shuffled_input() {
+        let db = build_test_db();
+        let mut ips: Vec<IpAddr> = (0..=255u8)
+            .flat_map(|b| {
+                [
+                    IpAddr::V4(Ipv4Addr::new(1, 1, 1, b)),
+                    IpAddr::V4(Ipv4Addr::new(2, 2, 2, b)),
+                    IpAddr::V4(Ipv4Addr::new(3, b, 3, 3)),
+                ]
+            })
+            .collect();
+        let mut state = 0x9e37_79b9_u32;
+        for i in (1..ips.len()).rev() {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            ips.swap(i, state as usize % (i + 1));
+        }
+        let par = db.search_many_par(&ips);
+        assert_eq!(par, db.search_many(&ips));
+        assert!(par.iter().any(Option::is_some) && par.iter().any(Option::is_none));
+    }
+
+    #[test]
+    fn search_lang_selects_registered_columns() {
+        let mut geo_map = Vec::new();
+        let mix = geo_columns(&mut geo_map, &["中国", "北京", "China", "Beijing"]);
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block_payload("1.0.0.0", "1.255.255.255", region_payload(mix, "ISP"))
+            .block("2.0.0.0", "2.255.255.255", "inline")
+            .geo_map(0b110, geo_map)
+            .build();
+        let options = CzdbOptions::new()
+            .language_columns("zh", &[0, 1])
+            .language_columns("en", &[2, 3]);
+        let db = CzdbMemory::from_bytes_with_options(bytes.clone(), &test_key(), options).unwrap();
+        let target: IpAddr = "1.0.0.1".parse().unwrap();
+        assert_eq!(
+            db.search_lang(target, "en").as_deref(),
+            Some("China\tBeijing\tISP")
+        );
+        assert_eq!(
+            db.search_lang(target, "zh").as_deref(),
+            Some("中国\t北京\tISP")
+        );
+        assert_eq!(db.search_lang(target, "fr"), db.search(target));
+        let inline: IpAddr = "2.0.0.1".parse().unwrap();
+        assert_eq!(db.search_lang(inline, "en").as_deref(), Some("inline"));
+
+        let plain = CzdbMemory::from_bytes(bytes, &test_key()).unwrap();
+        assert!(db.memory_usage() > plain.memory_usage());
+    }
+
+    #[test]
+    fn from_segments_merges_overlaps_and_trims_trailing_slop() {
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.255.255.255", "a")
+            .block("2.0.0.0", "2.255.255.255", "b")
+            .build();
+        let mid = bytes.len() / 2;
+        let head = (0, bytes[..mid].to_vec());
+        // A retried range that re-sends 5 bytes before the boundary, plus trailing slop.
+        let mut retried = bytes[mid - 5..].to_vec();
+        retried.extend_from_slice(&[0xaa; 7]);
+        let tail = ((mid - 5) as u64, retried);
+
+        let segments = vec![tail.clone(), head.clone()];
+        let db = CzdbMemory::from_segments(segments, &test_key()).unwrap();
+        assert_eq!(db.search_ref("2.0.0.1".parse().unwrap()), Some("b"));
+
+        let mut conflicting = tail.clone();
+        conflicting.1[0] ^= 0xff;
+        let gap = ((mid + 1) as u64, bytes[mid + 1..].to_vec());
+        for segments in [vec![head.clone(), conflicting], vec![head.clone(), gap]] {
+            assert!(matches!(
+                CzdbMemory::from_segments(segments, &test_key()),
+                Err(CzError::DatabaseFileReadError(e)) if e.kind() == io::ErrorKind::InvalidData
+            ));
+        }
+
+        let short = vec![head, (mid as u64, bytes[mid..bytes.len() - 1].to_vec())];
+        assert!(matches!(
+            CzdbMemory::from_segments(short, &test_key()),
+            Err(CzError::DatabaseFileCorrupted)
+        ));
+    }
+
+    #[test]
+    fn lookup_entry_reports_range_region_and_type() {
+        let db = build_test_db();
+        let entry = db
+            .lookup_entry(IpAddr::V4(Ipv4Addr::new(2, 2, 2, 9)))
+            .unwrap();
+        assert_eq!(
+            entry,
+            MatchedEntry {
+                start: IpAddr::V4(Ipv4Addr::new(2, 2, 2, 0)),
+                end: IpAddr::V4(Ipv4Addr::new(2, 2, 2, 255)),
+                region: "region2".to_string(),
+                db_type: DbType::Ipv4,
+            }
+        );
+        assert_eq!(db.lookup_entry("::1".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn search_with_prefix_reports_covering_network() {
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.255.255.255", "a")
+            .block("2.0.0.0", "2.0.0.255", "b")
+            .block("2.0.1.0", "2.0.4.255", "c")
+            .build();
+        let db = CzdbMemory::from_bytes(bytes, &test_key()).unwrap();
+        let search = |ip: &str| db.search_with_prefix(ip.parse().unwrap());
+        assert_eq!(search("1.2.3.4"), Some(("a".to_string(), 8)));
+        assert_eq!(search("2.0.0.9"), Some(("b".to_string(), 24)));
+        assert_eq!(search("2.0.2.9"), Some(("c".to_string(), 21)));
+        assert_eq!(search("9.9.9.9"), None);
+    }
+
+    #[test]
+    fn result_cache_is_shared_and_keyed_by_database() {
+        #[derive(Debug, Default)]
+        struct MapCache(Mutex<HashMap<CacheKey, String>>, AtomicUsize);
+        impl ResultCache for MapCache {
+            fn get(&self, key: &CacheKey) -> Option<String> {
+                let hit = self.0.lock().unwrap().get(key).cloned();
+                if hit.is_some() {
+                    self.1.fetch_add(1, Ordering::Relaxed);
+                }
+                hit
+            }
+            fn put(&self, key: CacheKey, region: String) {
+                self.0.lock().unwrap().insert(key, region);
+            }
+        }
+
+        let cache = Arc::new(MapCache::default());
+        let options = CzdbOptions::new().result_cache(cache.clone());
+        let bytes = |region| {
+            FixtureBuilder::new(DbType::Ipv4)
+                .block("1.0.0.0", "1.255.255.255", region)
+                .block("2.0.0.0", "2.255.255.255", "b")
+                .build()
+        };
+        let open = |region| {
+            CzdbMemory::from_bytes_with_options(bytes(region), &test_key(), options.clone())
+                .unwrap()
+        };
+        // "y" only differs from "a" in region contents, not in size or layout.
+        let (first, second, third) = (open("a"), open("zz"), open("y"));
+        let ip = "1.2.3.4".parse().unwrap();
+
+        assert_eq!(first.search(ip).as_deref(), Some("a"));
+        assert_eq!(first.search(ip).as_deref(), Some("a"));
+        assert_eq!(second.search(ip).as_deref(), Some("zz"));
+        assert_eq!(third.search(ip).as_deref(), Some("y"));
+        assert_eq!(first.search("9.9.9.9".parse().unwrap()), None);
+        assert_eq!(cache.1.load(Ordering::Relaxed), 1);
+        assert_eq!(cache.0.lock().unwrap().len(), 3);
+
+        // Another backend on the same file shares the keys.
+        let tmp = TempDb::new(&bytes("a"));
+        let disk =
+            crate::CzdbDisk::open_with_options(tmp.path_str(), &test_key(), options).unwrap();
+        assert_eq!(disk.search(ip).as_deref(), Some("a"));
+        assert_eq!(cache.1.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn region_length_stats_summarize_pool() {
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.255.255.255", "ab")
+            .block("2.0.0.0", "2.255.255.255", "abcdef")
+            .block("3.0.0.0", "3.255.255.255", "ab")
+            .build();
+        let db = CzdbMemory::from_bytes(bytes, &test_key()).unwrap();
+        let stats = db.region_length_stats();
+        assert_eq!(stats.distinct, 2);
+        assert_eq!((stats.min_len, stats.max_len), (2, 6));
+        assert_eq!(stats.mean_len, 4.0);
+        assert_eq!(stats.total_bytes, 8);
+    }
+
+    #[test]
+    fn treat_as_miss_filters_placeholder_regions() {
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.255.255.255", "保留地址")
+            .block("2.0.0.0", "2.255.255.255", "保留地址 extra")
+            .build();
+        let options = CzdbOptions::new().treat_as_miss(["保留地址", "reserved"]);
+        let db = CzdbMemory::from_bytes_with_options(bytes, &test_key(), options).unwrap();
+        let ip = "1.2.3.4".parse().unwrap();
+        assert_eq!(db.search(ip), None);
+        assert_eq!(db.search_ref(ip), None);
+        assert_eq!(db.search_or(ip, "unknown"), "unknown");
+        assert_eq!(
+            db.search("2.2.2.2".parse().unwrap()).as_deref(),
+            Some("保留地址 extra")
+        );
+        assert!(db.lookup_entry(ip).is_none());
+    }
+
+    #[test]
+    fn search_instrumented_counts_iterations() {
+        // Seven entries: the IPv4 search narrows `starts_v4` to one slot in exactly three
+        // steps, while the IPv6 search stops as soon as it probes the covering entry.
+        let db = |db_type, block: fn(u8) -> (String, String)| {
+            let fixture = (1..=7).fold(FixtureBuilder::new(db_type), |fixture, n| {
+                let (start, end) = block(n);
+                fixture.block(&start, &end, "r")
+            });
+            CzdbMemory::from_bytes(fixture.build(), &test_key()).unwrap()
+        };
+        let v4 = db(DbType::Ipv4, |n| {
+            (format!("{n}.0.0.0"), format!("{n}.255.255.255"))
+        });
+        for addr in ["1.0.0.0", "4.1.2.3", "7.255.255.255"] {
+            assert_eq!(
+                v4.search_instrumented(addr.parse().unwrap()),
+                (Some("r".to_string()), 3),
+                "{addr}"
+            );
+        }
+        assert_eq!(
+            v4.search_instrumented("9.9.9.9".parse().unwrap()),
+            (None, 3)
+        );
+        assert_eq!(v4.search_instrumented("::1".parse().unwrap()), (None, 0));
+
+        let v6 = db(DbType::Ipv6, |n| (format!("{n}::"), format!("{n}::ffff")));
+        for (addr, expected) in [("4::1", 1), ("2::1", 2), ("6::1", 2), ("1::1", 3)] {
+            let (region, iterations) = v6.search_instrumented(addr.parse().unwrap());
+            assert_eq!(region.as_deref(), Some("r"));
+            assert_eq!(iterations, expected, "{addr}");
+        }
+    }
+
+    #[test]
+    fn index_sidecar_is_reused_only_for_matching_database() {
+        let build = |region| {
+            FixtureBuilder::new(DbType::Ipv4)
+                .block("1.0.0.0", "1.255.255.255", region)
+                .block("2.0.0.0", "2.255.255.255", "b")
+                .build()
+        };
+        let tmp = TempDb::new(&build("beijing"));
+        let other = TempDb::new(&build("beijinx"));
+        let sidecar = TempDb::new(&[]);
+        let ip = "1.2.3.4".parse().unwrap();
+
+        let db = CzdbMemory::open(tmp.path_str(), &test_key()).unwrap();
+        db.save_index(sidecar.path_str()).unwrap();
+        assert!(matches!(
+            CzdbMemory::from_bytes(build("beijing"), &test_key())
+                .unwrap()
+                .save_index(sidecar.path_str()),
+            Err(CzError::DatabaseFileReadError(err)) if err.kind() == io::ErrorKind::Unsupported
+        ));
+        let loaded =
+            CzdbMemory::load_with_index(tmp.path_str(), &test_key(), sidecar.path_str()).unwrap();
+        assert_eq!(loaded.search(ip).as_deref(), Some("beijing"));
+        assert_eq!(loaded.region_length_stats(), db.region_length_stats());
+
+        let rebuilt =
+            CzdbMemory::load_with_index(other.path_str(), &test_key(), sidecar.path_str()).unwrap();
+        assert_eq!(rebuilt.search(ip).as_deref(), Some("beijinx"));
+
+        let normalized = CzdbMemory::load_with_index_with_options(
+            tmp.path_str(),
+            &test_key(),
+            sidecar.path_str(),
+            CzdbOptions::new().normalize_whitespace(true),
+        )
+        .unwrap();
+        assert_eq!(normalized.search(ip).as_deref(), Some("beijing"));
+
+        let mut truncated = std::fs::read(sidecar.path_str()).unwrap();
+        truncated.pop();
+        std::fs::write(sidecar.path_str(), truncated).unwrap();
+        let fallback =
+            CzdbMemory::load_with_index(tmp.path_str(), &test_key(), sidecar.path_str()).unwrap();
+        assert_eq!(fallback.search(ip).as_deref(), Some("beijing"));
+    }
+
+    #[test]
+    fn search_structured_splits_pooled_region() {
+        let mut geo_map = Vec::new();
+        let mix = geo_columns(&mut geo_map, &["中国", "北京"]);
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block_payload("1.0.0.0", "1.255.255.255", region_payload(mix, "联通"))
+            .block("2.0.0.0", "2.255.255.255", "b")
+            .geo_map(0b110, geo_map)
+            .build();
+        let db = CzdbMemory::from_bytes(bytes, &test_key()).unwrap();
+        let region = db.search_structured("1.2.3.4".parse().unwrap()).unwrap();
+        assert_eq!(region.country.as_deref(), Some("中国"));
+        assert_eq!(region.province.as_deref(), Some("北京"));
+        assert_eq!(region.city, None);
+        assert_eq!(region.isp.as_deref(), Some("联通"));
+        assert_eq!(region.raw, "中国\t北京\t联通");
+    }
+
+    #[test]
+    fn export_csv_splits_geo_columns() {
+        let mut out = Vec::new();
+        build_test_db().export_csv(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            concat!(
+                "start_ip,end_ip,region\r\n",
+                "1.1.1.0,1.1.1.255,region1\r\n",
+                "2.2.2.0,2.2.2.255,region2\r\n",
+            )
+        );
+
+        let mut geo_map = Vec::new();
+        let mix = geo_columns(&mut geo_map, &["中国", "北京"]);
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block_payload("1.0.0.0", "1.255.255.255", region_payload(mix, "联通"))
+            .block("2.0.0.0", "2.255.255.255", "b")
+            .geo_map(0b110, geo_map)
+            .build();
+        let db = CzdbMemory::from_bytes(bytes, &test_key()).unwrap();
+        let mut out = Vec::new();
+        db.export_csv(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            concat!(
+                "start_ip,end_ip,country,province,isp\r\n",
+                "1.0.0.0,1.255.255.255,中国,北京,联通\r\n",
+                "2.0.0.0,2.255.255.255,b,,\r\n",
+            )
+        );
+
+        // Mask 0b1010 selects columns 0 and 2; the header must not call the city a province.
+        let mut geo_map = Vec::new();
+        let mix = geo_columns(&mut geo_map, &["中国", "广东", "深圳"]);
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block_payload("1.0.0.0", "1.255.255.255", region_payload(mix, "电信"))
+            .geo_map(0b1010, geo_map)
+            .build();
+        let db = CzdbMemory::from_bytes(bytes, &test_key()).unwrap();
+        let mut out = Vec::new();
+        db.export_csv(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            concat!(
+                "start_ip,end_ip,country,city,isp\r\n",
+                "1.0.0.0,1.255.255.255,中国,深圳,电信\r\n",
+            )
+        );
+    }
+
+    #[test]
+    fn selected_columns_names_the_column_mask() {
+        assert!(build_test_db().selected_columns().is_empty());
+
+        let mut geo_map = Vec::new();
+        let mix = geo_columns(&mut geo_map, &["中国", "北京", "海淀"]);
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block_payload("1.0.0.0", "1.255.255.255", region_payload(mix, "联通"))
+            .block("2.0.0.0", "2.255.255.255", "b")
+            .geo_map(0b1011, geo_map)
+            .build();
+        let db = CzdbMemory::from_bytes(bytes, &test_key()).unwrap();
+        assert_eq!(db.column_selection(), 0b1011);
+        assert_eq!(db.selected_columns(), ["country", "city"]);
+    }
+
+    #[test]
+    fn info_reports_hyper_header_fields() {
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.255.255.255", "a")
+            .block("2.0.0.0", "2.255.255.255", "b")
+            .expiry(250423)
+            .build();
+        let clock = FixedClock(NaiveDate::from_ymd_opt(2025, 1, 1).unwrap());
+        let options = CzdbOptions::new().clock(clock);
+        let db = CzdbMemory::from_bytes_with_options(bytes, &test_key(), options).unwrap();
+        assert_eq!(
+            db.info(),
+            DbInfo {
+                version: 1,
+                client_id: 42,
+                expiry_date: NaiveDate::from_ymd_opt(2025, 4, 23),
+            }
+        );
+    }
+
+    #[test]
+    fn expiry_check_uses_configured_clock() {
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.255.255.255", "a")
+            .block("2.0.0.0", "2.255.255.255", "b")
+            .expiry(250101)
+            .build();
+        let open = |y, m, d| {
+            let clock = FixedClock(NaiveDate::from_ymd_opt(y, m, d).unwrap());
+            let options = CzdbOptions::new().clock(clock);
+            CzdbMemory::from_bytes_with_options(bytes.clone(), &test_key(), options)
+        };
+        assert!(open(2025, 1, 1).is_ok());
+        assert!(matches!(open(2025, 1, 2), Err(CzError::DatabaseExpired)));
+    }
+
+    #[test]
+    fn allow_expired_loads_and_flags_expired_database() {
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.255.255.255", "a")
+            .block("2.0.0.0", "2.255.255.255", "b")
+            .expiry(250101)
+            .build();
+        let open = |y, m, d| {
+            let options = CzdbOptions::new()
+                .clock(NaiveDate::from_ymd_opt(y, m, d).unwrap())
+                .allow_expired(true);
+            CzdbMemory::from_bytes_with_options(bytes.clone(), &test_key(), options).unwrap()
+        };
+        assert!(!open(2025, 1, 1).is_expired());
+        let db = open(2025, 1, 2);
+        assert!(db.is_expired());
+        assert_eq!(db.search_ref("2.0.0.1".parse().unwrap()), Some("b"));
+    }
+
+    #[test]
+    fn supports_matches_database_address_family() {
+        let db = build_test_db();
+        assert_eq!(db.supported_version(), DbType::Ipv4);
+        assert!(db.supports(IpAddr::V4(Ipv4Addr::LOCALHOST)));
+        assert!(!db.supports("::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn export_jsonl_writes_one_object_per_entry() {
+        let db = build_test_db();
+        let mut out = Vec::new();
+        db.export_jsonl(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            concat!(
+                "{\"start\":\"1.1.1.0\",\"end\":\"1.1.1.255\",\"region\":\"region1\"}\n",
+                "{\"start\":\"2.2.2.0\",\"end\":\"2.2.2.255\",\"region\":\"region2\"}\n",
+            )
+        );
+    }
+
+    #[test]
+    fn search_str_parses_before_searching() {
+        let db = build_test_db();
+        assert_eq!(db.search_str("2.2.2.2"), Ok(Some("region2".to_string())));
+        assert_eq!(db.search_str("9.9.9.9"), Ok(None));
+        assert!(db.search_str("2.2.2").is_err());
+    }
+
+    #[test]
+    fn ipv4_mapped_addresses_search_ipv4_database() {
+        let db = build_test_db();
+        for ip in ["::ffff:2.2.2.2", "::2.2.2.2"] {
+            assert_eq!(db.search_str(ip), Ok(Some("region2".to_string())));
+        }
+        assert!(db.supports("::ffff:1.1.1.1".parse().unwrap()));
+        assert_eq!(db.search_str("::1"), Ok(None));
+        assert_eq!(
+            db.search_many_scan(&["::ffff:1.1.1.1".parse().unwrap()]),
+            vec![Some("region1")]
+        );
+
+        let mut strict = build_test_db();
+        strict.meta.options = CzdbOptions::new().strict_ip_version(true);
+        assert_eq!(strict.search_str("::ffff:2.2.2.2"), Ok(None));
+    }
+
+    #[test]
+    fn memory_usage_covers_entries_and_region_pool() {
+        let db = build_test_db();
+        let entries = 2 * (size_of::<u32>() + size_of::<IndexEntryV4>());
+        let pool = "region1region2".len() + 2 * size_of::<RegionSpan>();
+        assert!(db.memory_usage() >= entries + pool);
+    }
+
+    #[cfg(feature = "ipnet")]
+    #[test]
+    fn search_cidr_returns_overlapping_ranges() {
+        let db = build_test_db();
+        let v4 = |a, b, c, d| IpAddr::V4(Ipv4Addr::new(a, b, c, d));
+        let cidr = |s: &str| db.search_cidr(s.parse().unwrap());
+        assert_eq!(
+            cidr("0.0.0.0/0"),
+            vec![
+                (v4(1, 1, 1, 0), v4(1, 1, 1, 255), "region1".to_string()),
+                (v4(2, 2, 2, 0), v4(2, 2, 2, 255), "region2".to_string()),
+            ]
+        );
+        assert_eq!(
+            cidr("2.2.2.128/30"),
+            vec![(v4(2, 2, 2, 0), v4(2, 2, 2, 255), "region2".to_string())]
+        );
+        assert!(cidr("1.1.2.0/24").is_empty());
+        assert!(cidr("::/0").is_empty());
+    }
+
+    #[test]
+    fn validate_reports_first_anomaly() {
+        let mut db = build_test_db();
+        db.validate().unwrap();
+
+        db.memory_index.entries_v4[1].start_ip = db.memory_index.entries_v4[0].end_ip;
+        db.memory_index.starts_v4[1] = db.memory_index.entries_v4[0].end_ip;
+        assert!(matches!(
+            db.validate(),
+            Err(CzError::IndexOutOfOrder { at: 1 })
+        ));
+
+        let mut db = build_test_db();
+        db.memory_index.entries_v4[0].region_id = 7;
+        assert!(matches!(db.validate(), Err(CzError::DatabaseFileCorrupted)));
+
+        let mut db = build_test_db();
+        db.memory_index.entries_v4.pop();
+        assert!(matches!(db.validate(), Err(CzError::DatabaseFileCorrupted)));
+
+        let mut db = build_test_db();
+        db.memory_index.starts_v4[1] += 1;
+        assert!(matches!(db.validate(), Err(CzError::DatabaseFileCorrupted)));
+    }
+
+    #[test]
+    fn iter_yields_every_entry_in_order() {
+        let db = build_test_db();
+        let v4 = |a, b, c, d| IpAddr::V4(Ipv4Addr::new(a, b, c, d));
+        assert_eq!(
+            db.iter().collect::<Vec<_>>(),
+            vec![
+                (v4(1, 1, 1, 0), v4(1, 1, 1, 255), "region1".to_string()),
+                (v4(2, 2, 2, 0), v4(2, 2, 2, 255), "region2".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn any_in_region_matches_substring() {
+        let db = build_test_db();
+        let ips = [
+            IpAddr::V4(Ipv4Addr::new(9, 9, 9, 9)),
+            IpAddr::V4(Ipv4Addr::new(2, 2, 2, 2)),
+        ];
+        assert!(db.any_in_region(&ips, "gion2"));
+        assert!(!db.any_in_region(&ips, "region1"));
+    }
+
+    #[test]
+    fn search_or_falls_back_on_miss() {
+        let db = build_test_db();
+        let ip = |d| IpAddr::V4(Ipv4Addr::new(2, 2, 2, d));
+        assert_eq!(db.search_or(ip(1), "unknown"), "region2");
+        assert_eq!(
+            db.search_or(IpAddr::V4(Ipv4Addr::new(9, 9, 9, 9)), "unknown"),
+            "unknown"
+        );
+    }
+
+    #[test]
+    fn is_exact_match_requires_single_address_block() {
+        let db = CzdbMemory::from_bytes(
+            FixtureBuilder::new(DbType::Ipv4)
+                .block("1.0.0.0", "1.0.0.9", "range")
+                .block("1.0.0.10", "1.0.0.10", "host")
+                .build(),
+            &test_key(),
+        )
+        .unwrap();
+        assert!(db.is_exact_match(IpAddr::V4(Ipv4Addr::new(1, 0, 0, 10))));
+        assert!(!db.is_exact_match(IpAddr::V4(Ipv4Addr::new(1, 0, 0, 0))));
+    }
+
+    #[test]
+    fn partition_entries_yields_one_partition() {
+        let db = CzdbMemory::from_bytes(
+            FixtureBuilder::new(DbType::Ipv4)
+                .block("1.0.0.0", "1.255.255.255", "a")
+                .block("2.0.0.0", "2.255.255.255", "b")
+                .block("3.0.0.0", "3.255.255.255", "c")
+                .header_every(2)
+                .build(),
+            &test_key(),
+        )
+        .unwrap();
+        let regions = |p| db.partition_entries(p).map(|e| e.2).collect::<Vec<_>>();
+        assert_eq!(regions(0), ["a", "b"]);
+        assert_eq!(regions(1), ["c"]);
+        assert!(regions(2).is_empty());
+    }
+
+    #[test]
+    fn search_many_sorted_orders_by_ip() {
+        let db = build_test_db();
+        let v4 = |a, b, c, d| IpAddr::V4(Ipv4Addr::new(a, b, c, d));
+        let ips = [v4(9, 9, 9, 9), v4(2, 2, 2, 2), v4(1, 1, 1, 1)];
+        assert_eq!(
+            db.search_many_sorted(&ips),
+            vec![
+                (v4(1, 1, 1, 1), Some("region1".to_string())),
+                (v4(2, 2, 2, 2), Some("region2".to_string())),
+                (v4(9, 9, 9, 9), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn search_many_grouped_collects_indices_per_region() {
+        let db = build_test_db();
+        let v4 = |a, b, c, d| IpAddr::V4(Ipv4Addr::new(a, b, c, d));
+        let ips = [
+            v4(2, 2, 2, 2),
+            v4(1, 1, 1, 1),
+            v4(9, 9, 9, 9),
+            v4(1, 1, 1, 200),
+        ];
+        let (groups, unmatched) = db.search_many_grouped(&ips);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups["region1"], [1, 3]);
+        assert_eq!(groups["region2"], [0]);
+        assert_eq!(unmatched, [2]);
+    }
+
+    #[test]
+    fn search_nearest_reports_the_entries_around_a_gap() {
+        let db = build_test_db();
+        let v4 = |a, b, c, d| IpAddr::V4(Ipv4Addr::new(a, b, c, d));
+        let regions = |ip| {
+            let (before, after) = db.search_nearest(ip);
+            (before.map(|e| e.2), after.map(|e| e.2))
+        };
+        let named = |s: &str| Some(s.to_string());
+
+        assert_eq!(
+            regions(v4(1, 200, 0, 0)),
+            (named("region1"), named("region2"))
+        );
+        assert_eq!(regions(v4(0, 0, 0, 1)), (None, named("region1")));
+        assert_eq!(regions(v4(9, 9, 9, 9)), (named("region2"), None));
+        assert_eq!(regions(v4(1, 1, 1, 5)), (None, named("region2")));
+        let (before, _) = db.search_nearest(v4(1, 200, 0, 0));
+        assert_eq!(before.unwrap().1, v4(1, 1, 1, 255));
+        assert_eq!(regions("::1".parse().unwrap()), (None, None));
+    }
+
+    #[test]
+    fn typed_searches_match_search_ref() {
+        let db = build_test_db();
+        for ip in [[1, 1, 1, 7], [2, 2, 2, 255], [1, 1, 2, 0]] {
+            let ip = Ipv4Addr::from(ip);
+            assert_eq!(db.search_v4(ip), db.search_ref(IpAddr::V4(ip)));
+        }
+
+        let bytes = FixtureBuilder::new(DbType::Ipv6)
+            .block("2001:db8::", "2001:db8::ffff", "doc")
+            .build();
+        let db = CzdbMemory::from_bytes(bytes, &test_key()).unwrap();
+        assert_eq!(db.search_v6("2001:db8::1".parse().unwrap()), Some("doc"));
+        assert_eq!(db.search_v6("2001:db9::".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn address_bounds_span_first_to_last_entry() {
+        let v4 = |a, b, c, d| IpAddr::V4(Ipv4Addr::new(a, b, c, d));
+        assert_eq!(
+            build_test_db().address_bounds(),
+            Some((v4(1, 1, 1, 0), v4(2, 2, 2, 255)))
+        );
+    }
+
+    #[test]
+    fn label_is_unset_until_attached() {
+        let db = build_test_db();
+        assert_eq!(db.label(), None);
+        assert_eq!(db.with_label("tenant-a").label(), Some("tenant-a"));
+    }
+
+    #[test]
+    fn header_only_file_reports_missing_data_section() {
+        let fixture = FixtureBuilder::new(DbType::Ipv4).block("1.0.0.0", "1.0.0.255", "a");
+        let mut bytes = fixture.build();
+        bytes.truncate(fixture.data_offset());
+        assert!(matches!(
+            CzdbMemory::from_bytes(bytes.clone(), &test_key()),
+            Err(CzError::NoDataSection)
+        ));
+        assert!(matches!(
+            CzdbMemory::from_segments(vec![(0, bytes)], &test_key()),
+            Err(CzError::NoDataSection)
+        ));
+    }
+
+    #[test]
+    fn strict_rejects_inverted_index_block() {
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.255.255.255", "a")
+            .block("3.0.0.0", "2.0.0.0", "b")
+            .build();
+        assert!(CzdbMemory::from_bytes(bytes.clone(), &test_key()).is_ok());
+        assert!(matches!(
+            CzdbMemory::from_bytes_with_options(
+                bytes,
+                &test_key(),
+                CzdbOptions::new().strict(true)
+            ),
+            Err(CzError::DatabaseFileCorrupted)
+        ));
+    }
+
+    #[test]
+    fn reload_from_swaps_only_after_a_successful_parse() {
+        let old = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.255.255.255", "old")
+            .build();
+        let new = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.255.255.255", "new")
+            .build();
+        let (old, new) = (TempDb::new(&old), TempDb::new(&new));
+        let mut db = CzdbMemory::open(old.path_str(), &test_key()).unwrap();
+        let one = IpAddr::V4(Ipv4Addr::new(1, 0, 0, 1));
+
+        assert!(db.reload_from(new.path_str(), "not base64!").is_err());
+        assert!(db.reload_from("/nonexistent/czdb", &test_key()).is_err());
+        assert_eq!(db.search_ref(one), Some("old"));
+
+        db.reload_from(new.path_str(), &test_key()).unwrap();
+        assert_eq!(db.search_ref(one), Some("new"));
+        db.reload().unwrap();
+        assert_eq!(db.search_ref(one), Some("new"));
+    }
+
+    #[test]
+    fn reload_requires_a_source_file() {
+        let mut db = build_test_db();
+        assert!(matches!(
+            db.reload(),
+            Err(CzError::DatabaseFileReadError(e)) if e.kind() == io::ErrorKind::Unsupported
+        ));
+        assert_eq!(
+            db.search_ref(IpAddr::V4(Ipv4Addr::new(2, 2, 2, 2))),
+            Some("region2")
+        );
+    }
+
     #[test]
     fn search_returns_expected_results() {
         let db = build_test_db();
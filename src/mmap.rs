@@ -1,14 +1,24 @@
 use crate::{
-    CzError,
+    CzError, CzdbOptions, borrowed,
     common::{
-        DbMeta, decode_aes_key, decode_region_from_bytes, parse_meta_from_bytes, read_hyper_header,
-        compare_bytes,
+        BackendKind, BoundaryKind, DbInfo, DbMeta, DbType, IndexBlock, Iterations, MatchedEntry,
+        QueryResult, RecordSite, Region, Searcher, Source, ascii_only, bytes_to_ip,
+        check_aes_key_len, decode_aes_key, decode_other_data, decode_region_at,
+        decode_region_from_bytes, decode_region_ref, decode_region_value,
+        decode_region_with_columns, ip_to_bytes, is_geolocatable, key_from_env, key_from_file,
+        open_with_first_key, read_geo_map_ref, read_other_data_bytes, write_jsonl_entry,
     },
 };
+use chrono::NaiveDate;
+#[cfg(unix)]
+use memmap2::Advice;
 use memmap2::{Mmap, MmapOptions};
+use rmpv::Value;
 use std::{
+    borrow::Cow,
     fs::File,
-    net::IpAddr,
+    io::Write,
+    net::{AddrParseError, IpAddr},
 };
 
 #[derive(Debug)]
@@ -23,13 +33,68 @@ impl MmapBytes {
     }
 }
 
+/// Paging hint applied to the mapping when a `CzdbMmap` is opened.
+///
+/// Pages are otherwise faulted in on first touch, which shows up as latency spikes on the
+/// first queries after opening. The hints trade startup cost for that latency. `Sequential`
+/// and `WillNeed` use `madvise` and are ignored on platforms without it; `Populate` pre-faults
+/// the whole mapping on Linux and behaves like `Lazy` elsewhere.
+///
+/// 打开 `CzdbMmap` 时应用于映射的分页提示。默认情况下页面在首次访问时才载入，
+/// 表现为打开后最初几次查询的延迟抖动；这些提示以启动开销换取查询延迟。
+/// `Sequential` 与 `WillNeed` 通过 `madvise` 实现，在不支持的平台上被忽略；
+/// `Populate` 在 Linux 上预先载入整个映射，其他平台上等同于 `Lazy`。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MmapAdvice {
+    /// Fault pages in on first touch.
+    ///
+    /// 首次访问时载入页面。
+    #[default]
+    Lazy,
+    /// Hint sequential access so the kernel reads ahead aggressively.
+    ///
+    /// 提示顺序访问，使内核积极预读。
+    Sequential,
+    /// Hint that the whole file will be needed soon so the kernel starts reading it in.
+    ///
+    /// 提示即将需要整个文件，使内核提前开始读取。
+    WillNeed,
+    /// Pre-fault the whole mapping before `open` returns.
+    ///
+    /// 在 `open` 返回前预先载入整个映射。
+    Populate,
+}
+
+impl MmapAdvice {
+    fn map(self, file: &File) -> std::io::Result<Mmap> {
+        let mut options = MmapOptions::new();
+        if self == Self::Populate {
+            options.populate();
+        }
+        let mmap = unsafe { options.map(file)? };
+        #[cfg(unix)]
+        match self {
+            Self::Sequential => mmap.advise(Advice::Sequential)?,
+            Self::WillNeed => mmap.advise(Advice::WillNeed)?,
+            Self::Lazy | Self::Populate => {}
+        }
+        Ok(mmap)
+    }
+}
+
 /// Mmap-backed CZDB searcher.
 ///
-/// 基于 mmap 的 CZDB 查询器。
+/// `CzdbMmap` is `Send + Sync` and every query method takes `&self`, so an
+/// `Arc<CzdbMmap>` can be queried from many threads at once without locking.
+///
+/// 基于 mmap 的 CZDB 查询器。`CzdbMmap` 实现了 `Send + Sync`，且所有查询方法都只需 `&self`，
+/// 因此 `Arc<CzdbMmap>` 可在多个线程中无锁并发查询。
 #[derive(Debug)]
 pub struct CzdbMmap {
     bindata: MmapBytes,
     meta: DbMeta,
+    source: Source,
+    advice: MmapAdvice,
 }
 
 impl CzdbMmap {
@@ -37,92 +102,441 @@ impl CzdbMmap {
     ///
     /// 使用内存映射打开数据库文件。
     pub fn open(db_path: &str, key: &str) -> Result<Self, CzError> {
-        let key_bytes = decode_aes_key(key)?;
-        let mut file = File::open(db_path)?;
-        let header = read_hyper_header(&mut file, &key_bytes)?;
-        let data_offset = (12 + header.padding_size + header.encrypted_block_size) as usize;
-        let mmap = unsafe { MmapOptions::new().map(&file)? };
-        if data_offset > mmap.len() {
-            return Err(CzError::DatabaseFileCorrupted);
-        }
-        let file_size_total = file.metadata()?.len();
-        let bindata = MmapBytes { mmap, offset: data_offset };
-        let meta = parse_meta_from_bytes(
-            bindata.as_slice(),
-            file_size_total,
-            header.padding_size,
-            header.encrypted_block_size,
-            &key_bytes,
-        )?;
+        Self::open_with_options(db_path, key, CzdbOptions::default())
+    }
+
+    /// Open a database file using memory mapping with a raw 16-byte AES key instead of a
+    /// base64 string.
+    ///
+    /// Returns `InvalidAesKeyLength` if `key` is not 16 bytes long.
+    ///
+    /// 使用内存映射打开数据库文件，直接使用 16 字节原始 AES 密钥而非 base64 字符串；长度不是 16 字节时返回
+    /// `InvalidAesKeyLength`。
+    pub fn open_with_key_bytes(db_path: &str, key: &[u8]) -> Result<Self, CzError> {
+        Self::open_with_key_bytes_and_options(db_path, key, CzdbOptions::default())
+    }
+
+    /// Open a database file, reading the base64 key from the environment variable `var_name`.
+    ///
+    /// Surrounding whitespace in the key is ignored. Returns `KeySourceError` if the variable
+    /// is not set.
+    ///
+    /// 打开数据库文件，从环境变量 `var_name` 读取 base64 密钥，忽略首尾空白；
+    /// 变量未设置时返回 `KeySourceError`。
+    pub fn open_with_key_env(db_path: &str, var_name: &str) -> Result<Self, CzError> {
+        Self::open(db_path, &key_from_env(var_name)?)
+    }
 
-        Ok(Self { bindata, meta })
+    /// Open a database file, reading the base64 key from the file at `key_path`.
+    ///
+    /// Surrounding whitespace and newlines in the file are ignored. Returns `KeySourceError`
+    /// if the file cannot be read.
+    ///
+    /// 打开数据库文件，从 `key_path` 处的文件读取 base64 密钥，忽略首尾空白与换行；
+    /// 文件无法读取时返回 `KeySourceError`。
+    pub fn open_with_key_file(db_path: &str, key_path: &str) -> Result<Self, CzError> {
+        Self::open(db_path, &key_from_file(key_path)?)
+    }
+
+    /// Open a database file with the first of `keys` that decrypts it, e.g. while keys are
+    /// being rotated.
+    ///
+    /// A key that fails anywhere in the header parse is skipped. If none of them fits, returns
+    /// `NoMatchingKey` with the number of keys tried and the last error that was not a plain
+    /// decryption failure, such as `DatabaseFileCorrupted` for a damaged file.
+    ///
+    /// 使用 `keys` 中第一个能解密的密钥打开数据库文件，适用于密钥轮换期间；头部解析任一环节
+    /// 失败的密钥会被跳过。全部不适用时返回 `NoMatchingKey`，其中包含尝试的密钥数量，以及最后一个
+    /// 并非单纯解密失败的错误（如文件损坏时的 `DatabaseFileCorrupted`）。
+    pub fn open_with_keys(db_path: &str, keys: &[&str]) -> Result<Self, CzError> {
+        open_with_first_key(keys, |key| Self::open(db_path, key))
+    }
+
+    /// Open a database file, checking expiry against `today` instead of the host clock.
+    ///
+    /// Equivalent to `open_with_options` with `CzdbOptions::clock(today)`.
+    ///
+    /// 使用内存映射打开数据库文件，以 `today` 而非主机时钟判断是否过期；
+    /// 等价于使用 `CzdbOptions::clock(today)` 的 `open_with_options`。
+    pub fn open_with_reference_date(
+        db_path: &str,
+        key: &str,
+        today: NaiveDate,
+    ) -> Result<Self, CzError> {
+        Self::open_with_options(db_path, key, CzdbOptions::new().clock(today))
+    }
+
+    /// Open a database file, applying `advice` to the mapping; see `MmapAdvice`.
+    ///
+    /// `reload` applies the same advice to the re-opened file.
+    ///
+    /// 打开数据库文件并对映射应用 `advice`，参见 `MmapAdvice`；`reload` 会对重新打开的文件
+    /// 应用相同的提示。
+    pub fn open_with_advice(db_path: &str, key: &str, advice: MmapAdvice) -> Result<Self, CzError> {
+        Self::open_mapped(
+            db_path,
+            &decode_aes_key(key)?,
+            CzdbOptions::default(),
+            advice,
+        )
+    }
+
+    /// Open a database file using memory mapping with custom options.
+    ///
+    /// 使用自定义选项通过内存映射打开数据库文件。
+    pub fn open_with_options(
+        db_path: &str,
+        key: &str,
+        options: CzdbOptions,
+    ) -> Result<Self, CzError> {
+        Self::open_with_key_bytes_and_options(db_path, &decode_aes_key(key)?, options)
+    }
+
+    /// `open_with_key_bytes` with custom options.
+    ///
+    /// 使用自定义选项的 `open_with_key_bytes`。
+    pub fn open_with_key_bytes_and_options(
+        db_path: &str,
+        key_bytes: &[u8],
+        options: CzdbOptions,
+    ) -> Result<Self, CzError> {
+        Self::open_mapped(db_path, key_bytes, options, MmapAdvice::default())
+    }
+
+    fn open_mapped(
+        db_path: &str,
+        key_bytes: &[u8],
+        options: CzdbOptions,
+        advice: MmapAdvice,
+    ) -> Result<Self, CzError> {
+        check_aes_key_len(key_bytes)?;
+        let mmap = advice.map(&File::open(db_path)?)?;
+        let (meta, offset) = borrowed::load_meta(&mmap, key_bytes, options)?;
+        let bindata = MmapBytes { mmap, offset };
+
+        Ok(Self {
+            bindata,
+            meta,
+            source: Source {
+                path: db_path.to_string(),
+                key: key_bytes.to_vec(),
+            },
+            advice,
+        })
+    }
+
+    /// Re-open the file this searcher was opened from and replace its state.
+    ///
+    /// The same key and options are used and the label is kept. On failure the current
+    /// state is left untouched and keeps serving queries. Replace the file by renaming a new
+    /// one over it: the current state still reads the old file until the reload succeeds.
+    ///
+    /// 使用相同的密钥和选项重新打开原文件并替换内部状态，标签保持不变。
+    /// 失败时保留当前状态并继续提供查询。请通过重命名新文件覆盖原文件来更新，
+    /// 重新加载成功前当前状态仍会读取旧文件。
+    pub fn reload(&mut self) -> Result<(), CzError> {
+        let mut fresh = Self::open_mapped(
+            &self.source.path,
+            &self.source.key,
+            self.meta.options.clone(),
+            self.advice,
+        )?;
+        fresh.meta.label = self.meta.label.take();
+        *self = fresh;
+        Ok(())
     }
 
     /// Search a single IP address.
     ///
-    /// 查询指定 IP 地址。
+    /// Consults the configured `ResultCache` first, if any. Regions listed in
+    /// `CzdbOptions::treat_as_miss` yield `None`.
+    ///
+    /// 查询指定 IP 地址；若配置了 `ResultCache`，会先查询缓存。
+    /// `CzdbOptions::treat_as_miss` 中列出的区域返回 `None`。
     pub fn search(&self, ip: IpAddr) -> Option<String> {
-        if !self.meta.db_type.compare(&ip) {
-            return None;
-        }
-        let mut ip_bytes = [0u8; 16];
-        match ip {
-            IpAddr::V4(ip) => ip_bytes[..4].copy_from_slice(&ip.octets()),
-            IpAddr::V6(ip) => ip_bytes.copy_from_slice(&ip.octets()),
-        }
+        self.try_search(ip).ok().flatten()
+    }
+
+    /// Search a single IP address, reporting corrupt records as errors.
+    ///
+    /// `Ok(None)` is a clean miss. Index or region bytes lying past the end of the file yield
+    /// `Err(CzError::DatabaseFileCorrupted)` and regions that fail to decode
+    /// `Err(CzError::CorruptRegion)`. `search` maps every error to `None`.
+    ///
+    /// 查询指定 IP 地址，并将已损坏的记录作为错误返回。`Ok(None)` 表示正常未命中；
+    /// 索引或区域数据超出文件末尾时返回 `Err(CzError::DatabaseFileCorrupted)`，
+    /// 区域解析失败返回 `Err(CzError::CorruptRegion)`。`search` 会将所有错误映射为 `None`。
+    pub fn try_search(&self, ip: IpAddr) -> Result<Option<String>, CzError> {
+        borrowed::try_search(self.bindata.as_slice(), &self.meta, ip)
+    }
 
-        let (sptr, eptr) = self.meta.search_in_header(&ip_bytes)?;
-        let sptr = sptr as usize;
-        let eptr = eptr as usize;
-        if eptr < sptr {
+    /// Search `ip` and split the region into named fields; see `Region`.
+    ///
+    /// 查询 `ip` 并将区域拆分为具名字段，参见 `Region`。
+    pub fn search_structured(&self, ip: IpAddr) -> Option<Region> {
+        let block = self.locate(ip)?;
+        let region_bytes = self.region_bytes(&block)?;
+        let raw =
+            decode_region_from_bytes(region_bytes, &self.meta, RecordSite::lookup(ip, &block))?;
+        self.meta.filter_miss(Some(raw.as_str()))?;
+        let geo_mapped = read_geo_map_ref(region_bytes).is_some();
+        Some(self.meta.structured_region(raw, geo_mapped))
+    }
+
+    /// Search `ip` only if it is a public address, returning `None` immediately otherwise.
+    ///
+    /// Private, loopback, link-local and other reserved addresses skip the lookup; see
+    /// `is_geolocatable` for the exact ranges.
+    ///
+    /// 仅在 `ip` 为公网地址时查询，否则直接返回 `None`。私有、环回、链路本地等保留地址
+    /// 不执行查询，具体范围参见 `is_geolocatable`。
+    pub fn search_public_only(&self, ip: IpAddr) -> Option<String> {
+        if !is_geolocatable(ip) {
             return None;
         }
+        self.search(ip)
+    }
 
-        let bindata = self.bindata.as_slice();
-        let ip_len = self.meta.db_type.bytes_len();
-        let blen = self.meta.db_type.index_block_len();
-        let block_len = eptr - sptr;
-        let max_len = sptr.saturating_add(block_len).saturating_add(blen);
-        if max_len > bindata.len() {
-            return None;
+    /// Search `ip` and report how many binary-search iterations the lookup took.
+    ///
+    /// The count covers both the header search and the search within the located index
+    /// partition, so an oversized partition shows up as a high count. The `ResultCache` is
+    /// bypassed; `treat_as_miss` still applies. `search` does no counting.
+    ///
+    /// 查询 `ip` 并返回查找所用的二分迭代次数。次数包含头部索引查找与所定位索引分区内的查找，
+    /// 过大的分区会表现为较高的次数。不使用 `ResultCache`，但仍应用 `treat_as_miss`。
+    /// `search` 不做计数。
+    pub fn search_instrumented(&self, ip: IpAddr) -> (Option<String>, usize) {
+        let mut iterations = 0;
+        let region = self.locate_counted(ip, &mut iterations).and_then(|block| {
+            let site = RecordSite::lookup(ip, &block);
+            decode_region_from_bytes(self.region_bytes(&block)?, &self.meta, site)
+        });
+        (self.meta.filter_miss(region), iterations)
+    }
+
+    /// Search a single IP address given by reference.
+    ///
+    /// Convenience for generic code holding `&IpAddr`; equivalent to `search(*ip)`.
+    ///
+    /// 通过引用查询指定 IP，便于持有 `&IpAddr` 的泛型代码使用，等价于 `search(*ip)`。
+    pub fn search_by_ref(&self, ip: &IpAddr) -> Option<String> {
+        self.search(*ip)
+    }
+
+    /// Parse `ip` and search it, returning the parse error for malformed input.
+    ///
+    /// Lookups compare the parsed octets, so every spelling of an IPv6 address (letter case,
+    /// leading zeros, `::` compression) finds the same entry.
+    ///
+    /// 解析 `ip` 并查询；输入格式错误时返回解析错误。查询比较的是解析后的字节，因此同一 IPv6
+    /// 地址的任意写法（大小写、前导零、`::` 压缩）都会命中相同条目。
+    pub fn search_str(&self, ip: &str) -> Result<Option<String>, AddrParseError> {
+        Ok(self.search(ip.parse()?))
+    }
+
+    /// Search a single IP address, returning `default` when there is no usable region.
+    ///
+    /// Both a miss and a matched block whose region is empty after trimming yield
+    /// `default`; use `search` to tell them apart.
+    ///
+    /// 查询指定 IP，无可用区域时返回 `default`。未命中与命中但区域去除空白后为空
+    /// 两种情况均返回 `default`；如需区分请使用 `search`。
+    pub fn search_or(&self, ip: IpAddr, default: &str) -> String {
+        match self.search(ip) {
+            Some(region) if !region.trim().is_empty() => region,
+            _ => default.to_string(),
         }
+    }
 
-        let mut l = 0usize;
-        let mut h = block_len / blen;
-        while l <= h {
-            let m = (l + h) >> 1;
-            let p = sptr + m * blen;
-            let start_ip = &bindata[p..p + ip_len];
-            let end_ip = &bindata[p + ip_len..p + ip_len * 2];
-            let cmp_start = compare_bytes(&ip_bytes, start_ip, ip_len);
-            let cmp_end = compare_bytes(&ip_bytes, end_ip, ip_len);
-
-            if cmp_start != std::cmp::Ordering::Less && cmp_end != std::cmp::Ordering::Greater {
-                let data_ptr = u32::from_le_bytes([
-                    bindata[p + ip_len * 2],
-                    bindata[p + ip_len * 2 + 1],
-                    bindata[p + ip_len * 2 + 2],
-                    bindata[p + ip_len * 2 + 3],
-                ]) as usize;
-                let data_len = bindata[p + ip_len * 2 + 4] as usize;
-                if data_ptr + data_len > bindata.len() {
-                    return None;
-                }
-                return decode_region_from_bytes(
-                    &bindata[data_ptr..data_ptr + data_len],
-                    &self.meta,
-                );
-            } else if cmp_start == std::cmp::Ordering::Less {
-                if m == 0 {
-                    break;
-                }
-                h = m - 1;
-            } else {
-                l = m + 1;
-            }
+    /// Look up `ip`, reporting why no region was returned.
+    ///
+    /// 查询 `ip`，并在未返回区域时说明原因。
+    pub fn query(&self, ip: IpAddr) -> QueryResult {
+        let ip = self.meta.normalize_ip(ip);
+        if !self.meta.db_type.compare(&ip) {
+            return QueryResult::WrongVersion;
         }
+        let Some(block) = self.locate(ip) else {
+            return QueryResult::NotCovered;
+        };
+        QueryResult::covered(
+            self.meta
+                .filter_miss(self.region_bytes(&block).and_then(|bytes| {
+                    decode_region_from_bytes(bytes, &self.meta, RecordSite::lookup(ip, &block))
+                })),
+        )
+    }
+
+    /// Search a single IP address and keep only its ASCII content.
+    ///
+    /// This is lossy: non-ASCII characters are dropped and columns left empty are removed,
+    /// so purely Chinese regions come back as an empty string. Intended for downstream
+    /// systems that cannot handle UTF-8.
+    ///
+    /// 查询指定 IP 并仅保留 ASCII 内容。该操作有损：非 ASCII 字符被删除，变为空的列被移除，
+    /// 纯中文区域将返回空字符串。适用于无法处理 UTF-8 的下游系统。
+    pub fn search_ascii(&self, ip: IpAddr) -> Option<String> {
+        self.search(ip).map(|region| ascii_only(&region))
+    }
+
+    /// Search a single IP address, borrowing from the mapping for inline regions.
+    ///
+    /// Regions built from geo-map columns are allocated.
+    ///
+    /// 查询指定 IP；内联区域直接借用映射数据，由地理映射列组成的区域会分配新字符串。
+    pub fn search_ref(&self, ip: IpAddr) -> Option<Cow<'_, str>> {
+        let block = self.locate(ip)?;
+        let region = decode_region_ref(
+            self.region_bytes(&block)?,
+            &self.meta,
+            RecordSite::lookup(ip, &block),
+        );
+        self.meta.filter_miss(region)
+    }
+
+    /// Returns only the inline `other_data` of the record matching `ip`.
+    ///
+    /// Geo-map columns are never decoded. Whitespace and UTF-8 options apply as in `search`.
+    ///
+    /// 仅返回匹配记录的内联 `other_data`，不解析地理映射列；空白与 UTF-8 选项与 `search` 一致。
+    pub fn search_other(&self, ip: IpAddr) -> Option<String> {
+        let block = self.locate(ip)?;
+        decode_other_data(self.region_bytes(&block)?, &self.meta)
+    }
+
+    /// Returns the undecoded region of the record matching `ip` as a MessagePack array.
+    ///
+    /// The array holds every geo-map column, including unselected ones, followed by
+    /// `other_data`; inline regions have `other_data` only. Text options are not applied.
+    ///
+    /// 以 MessagePack 数组返回匹配记录未经格式化的区域：包含全部地理映射列（含未选中的列），
+    /// 最后为 `other_data`；内联区域仅含 `other_data`。不应用文本选项。
+    pub fn search_raw(&self, ip: IpAddr) -> Option<Value> {
+        let block = self.locate(ip)?;
+        decode_region_value(self.region_bytes(&block)?, &self.meta)
+    }
+
+    /// Returns the raw `other_data` bytes of the record matching `ip`, without UTF-8 validation.
+    ///
+    /// 返回匹配记录 `other_data` 的原始字节，不做 UTF-8 校验。
+    pub fn search_raw_bytes(&self, ip: IpAddr) -> Option<Vec<u8>> {
+        let block = self.locate(ip)?;
+        read_other_data_bytes(self.region_bytes(&block)?)
+    }
 
-        None
+    /// Returns the geo-map `(data_ptr, data_len)` referenced by the record matching `ip`.
+    ///
+    /// Returns `None` if the IP is not covered or its region is stored inline.
+    ///
+    /// 返回匹配记录引用的地理映射 `(data_ptr, data_len)`；未覆盖或内联区域时返回 `None`。
+    pub fn geo_map_ref(&self, ip: IpAddr) -> Option<(usize, usize)> {
+        let block = self.locate(ip)?;
+        read_geo_map_ref(self.region_bytes(&block)?)
+    }
+
+    /// Number of region strings decoded so far that were not valid UTF-8.
+    ///
+    /// 目前为止解析到的非法 UTF-8 区域字符串数量。
+    pub fn invalid_utf8_count(&self) -> usize {
+        self.meta.invalid_utf8_count()
+    }
+
+    /// Returns the decrypted geo-map bytes, if the database has one.
+    ///
+    /// 返回解密后的地理映射数据（若存在）。
+    pub fn raw_geo_map(&self) -> Option<&[u8]> {
+        self.meta.raw_geo_map()
+    }
+
+    fn region_bytes(&self, block: &IndexBlock) -> Option<&[u8]> {
+        borrowed::region_bytes(self.bindata.as_slice(), block)
+    }
+
+    /// Search a single IP address, returning the geo-map columns registered for `lang`.
+    ///
+    /// Languages without a mapping (see `CzdbOptions::language_columns`) fall back to
+    /// the database's column selection, matching `search`.
+    ///
+    /// 查询指定 IP，返回为 `lang` 注册的地理映射列；未注册的语言使用数据库自带的列选择，
+    /// 与 `search` 结果一致。
+    pub fn search_lang(&self, ip: IpAddr, lang: &str) -> Option<String> {
+        let block = self.locate(ip)?;
+        let region = decode_region_with_columns(
+            self.region_bytes(&block)?,
+            &self.meta,
+            self.meta.language_mask(lang),
+            RecordSite::lookup(ip, &block),
+        );
+        self.meta.filter_miss(region)
+    }
+
+    /// Look up the entry covering `ip`, returning its range, region and database type.
+    ///
+    /// 查询覆盖 `ip` 的条目，返回其地址范围、区域及数据库类型。
+    pub fn lookup_entry(&self, ip: IpAddr) -> Option<MatchedEntry> {
+        let block = self.locate(ip)?;
+        let db_type = self.meta.db_type;
+        Some(MatchedEntry {
+            start: bytes_to_ip(&block.start_ip, db_type),
+            end: bytes_to_ip(&block.end_ip, db_type),
+            region: self.meta.filter_miss(decode_region_from_bytes(
+                self.region_bytes(&block)?,
+                &self.meta,
+                RecordSite::lookup(ip, &block),
+            ))?,
+            db_type,
+        })
+    }
+
+    /// Search `ip`, returning the region and the prefix length of the smallest CIDR
+    /// network containing the matched range.
+    ///
+    /// For ranges that are not CIDR-aligned the containing network is wider than the
+    /// range itself; see `covering_prefix_len`.
+    ///
+    /// 查询 `ip`，返回区域及包含命中范围的最小 CIDR 网络前缀长度。范围未按 CIDR 对齐时，
+    /// 该网络会比范围本身更大，参见 `covering_prefix_len`。
+    pub fn search_with_prefix(&self, ip: IpAddr) -> Option<(String, u8)> {
+        let entry = self.lookup_entry(ip)?;
+        let prefix_len = entry.prefix_len();
+        Some((entry.region, prefix_len))
+    }
+
+    /// Returns true only if `ip` is matched by a single-address block (start == end == ip).
+    ///
+    /// 仅当 `ip` 命中的块为单地址块（起始 == 结束 == ip）时返回 true。
+    pub fn is_exact_match(&self, ip: IpAddr) -> bool {
+        let ip = self.meta.normalize_ip(ip);
+        self.locate(ip)
+            .is_some_and(|block| block.is_exact(&ip_to_bytes(ip), self.meta.db_type))
+    }
+
+    /// Report whether the IP is a start, end, or interior address of its covering block.
+    ///
+    /// Returns `None` if no block covers the IP.
+    ///
+    /// 判断 IP 是所在块的起始、结束还是内部地址；未覆盖时返回 `None`。
+    pub fn boundary_kind(&self, ip: IpAddr) -> Option<BoundaryKind> {
+        let ip = self.meta.normalize_ip(ip);
+        let block = self.locate(ip)?;
+        Some(block.boundary_kind(&ip_to_bytes(ip), self.meta.db_type))
+    }
+
+    fn locate(&self, ip: IpAddr) -> Option<IndexBlock> {
+        self.locate_counted(ip, &mut ())
+    }
+
+    fn locate_counted(&self, ip: IpAddr, iterations: &mut impl Iterations) -> Option<IndexBlock> {
+        self.try_locate(ip, iterations).ok().flatten()
+    }
+
+    fn try_locate(
+        &self,
+        ip: IpAddr,
+        iterations: &mut impl Iterations,
+    ) -> Result<Option<IndexBlock>, CzError> {
+        borrowed::locate(self.bindata.as_slice(), &self.meta, ip, iterations)
     }
 
     /// Search a small batch of IP addresses.
@@ -131,4 +545,313 @@ impl CzdbMmap {
     pub fn search_many(&self, ips: &[IpAddr]) -> Vec<Option<String>> {
         ips.iter().map(|ip| self.search(*ip)).collect()
     }
+
+    /// Search a batch of IP addresses, borrowing from the mapping for inline regions.
+    ///
+    /// 批量查询 IP；内联区域直接借用映射数据。
+    pub fn search_many_ref(&self, ips: &[IpAddr]) -> Vec<Option<Cow<'_, str>>> {
+        ips.iter().map(|ip| self.search_ref(*ip)).collect()
+    }
+
+    /// Iterate over every index block, decoding each record independently.
+    ///
+    /// Records whose region pointer falls outside the mapping yield
+    /// `Err(CzError::DatabaseFileCorrupted)`; records that fail to decode yield
+    /// `Err(CzError::CorruptRegion)` naming the data pointer and the malformed part.
+    /// Iteration continues with the next record either way.
+    ///
+    /// 遍历所有索引块并逐条解析。区域指针越界的记录返回 `Err(CzError::DatabaseFileCorrupted)`；
+    /// 无法解析的记录返回 `Err(CzError::CorruptRegion)`，包含数据指针及出错部分。
+    /// 两种情况都会继续处理下一条。
+    pub fn iter_checked(
+        &self,
+    ) -> impl Iterator<Item = Result<(IpAddr, IpAddr, String), CzError>> + '_ {
+        let db_type = self.meta.db_type;
+        let blen = db_type.index_block_len();
+        let start = self.meta.start_index as usize;
+        let end = self.meta.end_index as usize;
+        let bindata = self.bindata.as_slice();
+        (start..=end).step_by(blen).map(move |p| {
+            if p + blen > bindata.len() {
+                return Err(CzError::DatabaseFileCorrupted);
+            }
+            let block = IndexBlock::read(bindata, p, db_type);
+            if block.data_ptr + block.data_len > bindata.len() {
+                return Err(CzError::DatabaseFileCorrupted);
+            }
+            let region = decode_region_at(
+                &bindata[block.data_ptr..block.data_ptr + block.data_len],
+                &self.meta,
+                self.meta.column_selection,
+                RecordSite::scan(&block),
+            )?;
+            Ok((
+                bytes_to_ip(&block.start_ip, db_type),
+                bytes_to_ip(&block.end_ip, db_type),
+                region,
+            ))
+        })
+    }
+
+    /// Export every entry as newline-delimited JSON (`{"start","end","region"}` per line).
+    ///
+    /// Stops at the first record that fails to decode.
+    ///
+    /// 将全部条目导出为 JSONL，每行一个 `{"start","end","region"}` 对象；
+    /// 遇到无法解析的记录时停止。
+    pub fn export_jsonl<W: Write>(&self, w: &mut W) -> Result<(), CzError> {
+        for entry in self.iter_checked() {
+            let (start, end, region) = entry?;
+            write_jsonl_entry(w, start, end, &region)?;
+        }
+        Ok(())
+    }
+
+    /// Lowest and highest addresses covered: the first block's start and the last block's end.
+    ///
+    /// 返回覆盖的最低与最高地址，即首个索引块的起始地址与最后一个索引块的结束地址。
+    pub fn address_bounds(&self) -> Option<(IpAddr, IpAddr)> {
+        let db_type = self.meta.db_type;
+        let blen = db_type.index_block_len();
+        let bindata = self.bindata.as_slice();
+        let (start, end) = (self.meta.start_index as usize, self.meta.end_index as usize);
+        if end < start || end + blen > bindata.len() {
+            return None;
+        }
+        let first = IndexBlock::read(bindata, start, db_type);
+        let last = IndexBlock::read(bindata, end, db_type);
+        Some((
+            bytes_to_ip(&first.start_ip, db_type),
+            bytes_to_ip(&last.end_ip, db_type),
+        ))
+    }
+
+    /// Geo-map column mask; bit `n + 1` selects column `n`, bit 0 is reserved.
+    ///
+    /// 地理映射列掩码；第 `n + 1` 位选择第 `n` 列，第 0 位保留。
+    pub fn column_selection(&self) -> u64 {
+        self.meta.column_selection
+    }
+
+    /// Names of the selected geo-map columns (`country`, `province`, `city`, `district`,
+    /// `isp`), in the order `search` joins them. Columns the name table does not cover are
+    /// `"unknown"`; the record's inline data follows them in `search` results.
+    ///
+    /// 选中的地理映射列名称（`country`、`province`、`city`、`district`、`isp`），顺序与
+    /// `search` 拼接顺序一致；名称表未覆盖的列为 `"unknown"`。
+    /// `search` 结果中这些列之后为记录的内联数据。
+    pub fn selected_columns(&self) -> Vec<&'static str> {
+        self.meta.selected_columns()
+    }
+
+    /// Number of IP ranges (index blocks) in the database.
+    ///
+    /// 数据库中 IP 段（索引块）的数量。
+    pub fn entry_count(&self) -> usize {
+        self.meta.entry_count()
+    }
+
+    /// Returns the storage backend of this searcher.
+    ///
+    /// 返回该查询器的存储模式。
+    pub fn backend_kind(&self) -> BackendKind {
+        BackendKind::Mmap
+    }
+
+    /// Attach a human-readable label, e.g. to identify this database in logs.
+    ///
+    /// 附加便于识别的标签，例如用于在日志中区分数据库。
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.meta.label = Some(label.into());
+        self
+    }
+
+    /// The label set with `with_label`, if any.
+    ///
+    /// 通过 `with_label` 设置的标签（若有）。
+    pub fn label(&self) -> Option<&str> {
+        self.meta.label.as_deref()
+    }
+
+    /// Inclusive address ranges covered by each header partition.
+    ///
+    /// 每个头部分区覆盖的地址闭区间。
+    pub fn header_partitions(&self) -> Vec<(IpAddr, IpAddr)> {
+        self.meta.header_partitions()
+    }
+
+    /// Whether the database can answer queries for `ip`'s address family.
+    ///
+    /// 判断数据库能否查询 `ip` 所属的地址族。
+    pub fn supports(&self, ip: IpAddr) -> bool {
+        self.meta.db_type.compare(&self.meta.normalize_ip(ip))
+    }
+
+    /// The IP version this database answers for.
+    ///
+    /// 数据库支持查询的 IP 版本。
+    pub fn supported_version(&self) -> DbType {
+        self.meta.db_type
+    }
+
+    /// Format version, client id and expiry date read from the hyper header.
+    ///
+    /// 从超头读取的格式版本、客户端 ID 与到期日期。
+    pub fn info(&self) -> DbInfo {
+        self.meta.info()
+    }
+
+    /// Whether the database was past its expiry date when opened.
+    ///
+    /// Only ever true when opened with `CzdbOptions::allow_expired`.
+    ///
+    /// 打开时数据库是否已过期；仅在使用 `CzdbOptions::allow_expired` 打开时可能为 true。
+    pub fn is_expired(&self) -> bool {
+        self.meta.expired
+    }
+
+    /// Header geometry as `(padding_size, encrypted_block_size)`.
+    ///
+    /// The data section starts at `12 + padding_size + encrypted_block_size`.
+    ///
+    /// 返回头部布局 `(padding_size, encrypted_block_size)`；
+    /// 数据区起始于 `12 + padding_size + encrypted_block_size`。
+    pub fn header_geometry(&self) -> (u32, u32) {
+        self.meta.header_geometry()
+    }
+
+    /// Length in bytes of the data section, everything after the hyper header and padding.
+    ///
+    /// 数据区（超头与填充之后的全部内容）的字节长度。
+    pub fn data_section_len(&self) -> usize {
+        self.meta.file_size as usize
+    }
+}
+
+impl Searcher for CzdbMmap {
+    fn lookup(&self, ip: IpAddr) -> Option<String> {
+        self.search(ip)
+    }
+
+    fn db_type(&self) -> DbType {
+        self.supported_version()
+    }
+
+    fn backend_kind(&self) -> BackendKind {
+        CzdbMmap::backend_kind(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::{FixtureBuilder, TempDb, geo_columns, region_payload, test_key};
+    use std::sync::Arc;
+
+    #[test]
+    fn search_many_ref_borrows_inline_regions() {
+        let mut geo_map = Vec::new();
+        let mix = geo_columns(&mut geo_map, &["中国", "北京"]);
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.255.255.255", "inline")
+            .block_payload("2.0.0.0", "2.255.255.255", region_payload(mix, "联通"))
+            .geo_map(0b110, geo_map)
+            .build();
+        let tmp = TempDb::new(&bytes);
+        let db = CzdbMmap::open(tmp.path_str(), &test_key()).unwrap();
+        let ips: Vec<IpAddr> = ["1.1.1.1", "2.2.2.2", "3.3.3.3"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let results = db.search_many_ref(&ips);
+        assert!(matches!(results[0], Some(Cow::Borrowed("inline"))));
+        assert!(matches!(&results[1], Some(Cow::Owned(s)) if s == "中国\t北京\t联通"));
+        assert!(results[2].is_none());
+    }
+
+    #[test]
+    fn every_advice_opens_and_survives_reload() {
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.255.255.255", "beijing")
+            .build();
+        let tmp = TempDb::new(&bytes);
+        for advice in [
+            MmapAdvice::Lazy,
+            MmapAdvice::Sequential,
+            MmapAdvice::WillNeed,
+            MmapAdvice::Populate,
+        ] {
+            let mut db = CzdbMmap::open_with_advice(tmp.path_str(), &test_key(), advice).unwrap();
+            db.reload().unwrap();
+            assert_eq!(db.advice, advice);
+            assert_eq!(
+                db.search("1.2.3.4".parse().unwrap()).as_deref(),
+                Some("beijing")
+            );
+        }
+    }
+
+    #[test]
+    fn shared_arc_is_queried_concurrently() {
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.255.255.255", "beijing")
+            .block("2.0.0.0", "2.255.255.255", "shanghai")
+            .build();
+        let tmp = TempDb::new(&bytes);
+        let db = Arc::new(CzdbMmap::open(tmp.path_str(), &test_key()).unwrap());
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let db = Arc::clone(&db);
+                std::thread::spawn(move || {
+                    let (ip, expected) = if i % 2 == 0 {
+                        ("1.2.3.4", "beijing")
+                    } else {
+                        ("2.3.4.5", "shanghai")
+                    };
+                    for _ in 0..100 {
+                        assert_eq!(db.search(ip.parse().unwrap()).as_deref(), Some(expected));
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn data_section_len_matches_mapping() {
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.255.255.255", "a")
+            .block("2.0.0.0", "2.255.255.255", "b")
+            .build();
+        let tmp = TempDb::new(&bytes);
+        let db = CzdbMmap::open(tmp.path_str(), &test_key()).unwrap();
+        assert_eq!(db.data_section_len(), db.bindata.as_slice().len());
+    }
+
+    #[test]
+    fn search_rejects_misaligned_header_range() {
+        let fixture = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.127.255.255", "A")
+            .block("1.128.0.0", "1.199.255.255", "B")
+            .block("1.200.0.0", "1.255.255.255", "C")
+            .header_every(2);
+        let mut bytes = fixture.build();
+        let tmp = TempDb::new(&bytes);
+        let db = CzdbMmap::open(tmp.path_str(), &test_key()).unwrap();
+        assert_eq!(
+            db.search("1.199.236.9".parse().unwrap()),
+            Some("B".to_string())
+        );
+        drop(db);
+
+        let misaligned = (fixture.block_pos(1) - fixture.data_offset() + 5) as u32;
+        let pos = fixture.header_ptr_pos(1);
+        bytes[pos..pos + 4].copy_from_slice(&misaligned.to_le_bytes());
+        let tmp = TempDb::new(&bytes);
+        let db = CzdbMmap::open(tmp.path_str(), &test_key()).unwrap();
+        assert_eq!(db.search("1.199.236.9".parse().unwrap()), None);
+        assert_eq!(db.search("1.204.105.161".parse().unwrap()), None);
+    }
 }
@@ -0,0 +1,242 @@
+use crate::{Clock, RegionDecoder, ResultCache, SystemClock};
+use std::{collections::HashMap, sync::Arc};
+
+/// Byte order of the IPv4 addresses stored in the index section.
+///
+/// 索引区中 IPv4 地址的字节序。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Ipv4ByteOrder {
+    /// Network byte order, as written by the official tooling.
+    ///
+    /// 网络字节序（官方工具生成的格式）。
+    #[default]
+    BigEndian,
+    /// Little-endian addresses.
+    ///
+    /// 小端字节序。
+    LittleEndian,
+    /// Pick little-endian only when the leading entries are ordered as little-endian
+    /// but not as big-endian.
+    ///
+    /// 仅当前若干条目按小端有序而按大端无序时使用小端。
+    Detect,
+}
+
+/// Options controlling how a database is opened and how regions are decoded.
+///
+/// 控制数据库打开方式与区域解析行为的选项。
+#[derive(Debug, Clone, Default)]
+pub struct CzdbOptions {
+    pub(crate) normalize_whitespace: bool,
+    pub(crate) readahead_kib: usize,
+    #[cfg(feature = "cache")]
+    pub(crate) region_cache: usize,
+    pub(crate) lazy_geo_map: bool,
+    pub(crate) ipv4_byte_order: Ipv4ByteOrder,
+    pub(crate) lossy_utf8: bool,
+    pub(crate) language_columns: HashMap<String, u64>,
+    pub(crate) clock: Option<Arc<dyn Clock>>,
+    pub(crate) region_decoder: Option<Arc<dyn RegionDecoder>>,
+    pub(crate) result_cache: Option<Arc<dyn ResultCache>>,
+    pub(crate) treat_as_miss: Vec<String>,
+    pub(crate) allow_expired: bool,
+    pub(crate) strict_ip_version: bool,
+    pub(crate) strict: bool,
+}
+
+impl CzdbOptions {
+    /// Create options with default behavior.
+    ///
+    /// 使用默认行为创建选项。
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trim `other_data` and collapse internal whitespace runs into a single space.
+    ///
+    /// This matches the output of the pre-0.2 `Czdb` API. Disabled by default.
+    ///
+    /// 去除 `other_data` 首尾空白并将内部连续空白合并为单个空格，
+    /// 与 0.2 之前的 `Czdb` 接口输出一致。默认关闭。
+    pub fn normalize_whitespace(mut self, enabled: bool) -> Self {
+        self.normalize_whitespace = enabled;
+        self
+    }
+
+    /// Size in KiB of the read-ahead window used by `CzdbDisk`.
+    ///
+    /// `0` (the default) keeps the minimal per-query reads. Ignored by other backends.
+    ///
+    /// `CzdbDisk` 预读窗口大小（KiB）。默认 `0` 表示按需最小读取；其他模式忽略该选项。
+    pub fn readahead_kib(mut self, kib: usize) -> Self {
+        self.readahead_kib = kib;
+        self
+    }
+
+    /// Number of index partitions, and of decoded regions, `CzdbDisk` keeps in LRU caches.
+    ///
+    /// Lookups landing in a cached partition skip the index read, and blocks sharing a cached
+    /// region skip the region read and decode, so a repeat lookup reads nothing from the file.
+    /// `0` (the default) disables the cache. Ignored by other backends.
+    ///
+    /// `CzdbDisk` 在 LRU 缓存中保留的索引分区数量与已解析区域数量。落在已缓存分区内的查询跳过索引读取，
+    /// 共享已缓存区域的块跳过区域的读取与解析，因此重复查询不会读取文件。默认 `0` 表示关闭缓存；
+    /// 其他模式忽略该选项。
+    #[cfg(feature = "cache")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "cache")))]
+    pub fn region_cache(mut self, capacity: usize) -> Self {
+        self.region_cache = capacity;
+        self
+    }
+
+    /// Defer geo-map decryption until the first lookup that needs it.
+    ///
+    /// The encrypted bytes are kept and decrypted once, then cached. `CzdbMemory` decodes
+    /// every region while building its index, so this only helps `CzdbDisk` and `CzdbMmap`.
+    /// Disabled by default.
+    ///
+    /// 延迟到首次需要时再解密地理映射数据，之后缓存结果。`CzdbMemory`
+    /// 在构建索引时会解析全部区域，因此该选项仅对 `CzdbDisk` 和 `CzdbMmap` 有效。默认关闭。
+    pub fn lazy_geo_map(mut self, enabled: bool) -> Self {
+        self.lazy_geo_map = enabled;
+        self
+    }
+
+    /// Byte order used to interpret IPv4 index entries in `CzdbMemory`.
+    ///
+    /// Defaults to big-endian. A wrong byte order does not fail to load; it silently
+    /// returns wrong regions, so use `Ipv4ByteOrder::Detect` for files of unknown origin.
+    ///
+    /// `CzdbMemory` 解析 IPv4 索引条目时使用的字节序，默认大端。字节序错误不会导致加载失败，
+    /// 但会返回错误结果；来源不明的文件可使用 `Ipv4ByteOrder::Detect`。
+    pub fn ipv4_byte_order(mut self, order: Ipv4ByteOrder) -> Self {
+        self.ipv4_byte_order = order;
+        self
+    }
+
+    /// Decode region strings that are not valid UTF-8 lossily instead of as `"null"`.
+    ///
+    /// Invalid strings are counted either way; see `invalid_utf8_count` on each searcher.
+    /// Disabled by default.
+    ///
+    /// 对非法 UTF-8 的区域字符串进行有损解码，而不是替换为 `"null"`。无论是否开启都会计数，
+    /// 可通过各查询器的 `invalid_utf8_count` 获取。默认关闭。
+    pub fn lossy_utf8(mut self, enabled: bool) -> Self {
+        self.lossy_utf8 = enabled;
+        self
+    }
+
+    /// Register the geo-map columns holding the variant for language `lang`.
+    ///
+    /// `columns` are zero-based positions in the geo-map column array. `search_lang` then
+    /// returns those columns for `lang` in place of the database's column selection.
+    /// `CzdbMemory` builds an extra string pool for each registered language.
+    ///
+    /// 为语言 `lang` 注册对应的地理映射列。`columns` 为地理映射列数组中从 0 开始的位置；
+    /// 之后 `search_lang` 会用这些列替代数据库自带的列选择。`CzdbMemory` 会为每种已注册语言
+    /// 额外构建一个字符串池。
+    ///
+    /// ```rust,ignore
+    /// let options = CzdbOptions::new()
+    ///     .language_columns("zh", &[0, 1, 2])
+    ///     .language_columns("en", &[5, 6, 7]);
+    /// ```
+    pub fn language_columns(mut self, lang: impl Into<String>, columns: &[usize]) -> Self {
+        let mask = columns
+            .iter()
+            .filter(|&&column| column < 63)
+            .fold(0u64, |mask, &column| mask | (1 << (column + 1)));
+        self.language_columns.insert(lang.into(), mask);
+        self
+    }
+
+    /// Date source used by the expiry check. Defaults to `SystemClock`.
+    ///
+    /// 过期校验使用的日期来源，默认 `SystemClock`。
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    /// Open databases past their expiry date instead of failing with `DatabaseExpired`.
+    ///
+    /// The data stays usable; check `is_expired` on the searcher to report the stale
+    /// database. Disabled by default.
+    ///
+    /// 允许打开已过期的数据库，而不是返回 `DatabaseExpired` 错误。数据仍可正常查询，
+    /// 可通过查询器的 `is_expired` 判断并提示数据库已过期。默认关闭。
+    pub fn allow_expired(mut self, enabled: bool) -> Self {
+        self.allow_expired = enabled;
+        self
+    }
+
+    /// Stop unwrapping IPv4-mapped and IPv4-compatible IPv6 addresses for IPv4 databases.
+    ///
+    /// By default `::ffff:8.8.8.8` and `::8.8.8.8` are looked up as `8.8.8.8` in an IPv4
+    /// database. With this enabled they are treated as IPv6 and miss. Disabled by default.
+    ///
+    /// 不再为 IPv4 数据库解包 IPv4 映射及 IPv4 兼容的 IPv6 地址。默认情况下，`::ffff:8.8.8.8`
+    /// 与 `::8.8.8.8` 在 IPv4 数据库中按 `8.8.8.8` 查询；启用后它们被视为 IPv6 地址而无法命中。默认关闭。
+    pub fn strict_ip_version(mut self, enabled: bool) -> Self {
+        self.strict_ip_version = enabled;
+        self
+    }
+
+    /// Verify on open that every index block has `start <= end` and that blocks are strictly
+    /// ascending and disjoint, failing with `DatabaseFileCorrupted` otherwise.
+    ///
+    /// Lookups binary-search the index and silently return wrong answers when it is out of
+    /// order. The check scans the whole index, so it is disabled by default.
+    ///
+    /// 打开时校验每个索引块满足 `start <= end`，且各块严格递增、互不重叠，否则返回
+    /// `DatabaseFileCorrupted`。查询基于二分查找，索引无序时会静默返回错误结果。
+    /// 该校验需扫描整个索引，默认关闭。
+    pub fn strict(mut self, enabled: bool) -> Self {
+        self.strict = enabled;
+        self
+    }
+
+    /// Replace the built-in geo-map column handling with `decoder`.
+    ///
+    /// 使用 `decoder` 替代内置的地理映射列处理逻辑。
+    pub fn region_decoder(mut self, decoder: impl RegionDecoder + 'static) -> Self {
+        self.region_decoder = Some(Arc::new(decoder));
+        self
+    }
+
+    /// Cache consulted by `search` before each lookup. No cache is used by default.
+    ///
+    /// Pass clones of the same `Arc` to several searchers to share one cache between them.
+    ///
+    /// `search` 每次查询前使用的结果缓存，默认不使用缓存。
+    /// 将同一个 `Arc` 的克隆传给多个查询器即可共享缓存。
+    pub fn result_cache(mut self, cache: Arc<dyn ResultCache>) -> Self {
+        self.result_cache = Some(cache);
+        self
+    }
+
+    /// Placeholder regions that lookups report as a miss.
+    ///
+    /// A matched region exactly equal to one of `regions` after decoding (for example a
+    /// vendor's "reserved" marker) makes `search` and every other region lookup (`search_ref`,
+    /// `search_structured`, `search_lang`, `lookup_entry`, ...) return `None` on every backend,
+    /// and `query` report `QueryResult::CoveredEmpty`. Raw accessors such as `search_raw` are
+    /// unaffected. Empty by default.
+    ///
+    /// 查询时视为未命中的占位区域。解析后的区域与 `regions` 中任一项完全相同时
+    /// （例如厂商的"保留地址"标记），所有后端的 `search` 及其他区域查询（`search_ref`、
+    /// `search_structured`、`search_lang`、`lookup_entry` 等）均返回 `None`，`query` 返回
+    /// `QueryResult::CoveredEmpty`。`search_raw` 等原始数据接口不受影响。默认为空。
+    pub fn treat_as_miss<I, S>(mut self, regions: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.treat_as_miss = regions.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub(crate) fn clock_ref(&self) -> &dyn Clock {
+        self.clock.as_deref().unwrap_or(&SystemClock)
+    }
+}
@@ -0,0 +1,491 @@
+//! Parsing and search over an already-decrypted data section.
+//!
+//! Everything here uses only `core`: no file access, no clock and no allocation. The hyper
+//! header, its expiry check and the geo-map decryption are left to the caller, so the lookup
+//! logic can run where `std::fs` or a system clock is unavailable. The std backends build on
+//! the same index primitives.
+//!
+//! 对已解密数据区的解析与查询。此模块仅使用 `core`：不访问文件、不读取时钟、不分配内存。
+//! 超头解析、过期检查与地理映射解密均由调用方负责，因此查询逻辑可在没有 `std::fs`
+//! 或系统时钟的环境中运行。基于 std 的各后端共用相同的索引原语。
+
+use core::{
+    cmp::Ordering,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+};
+
+pub(crate) const SUPER_PART_LENGTH: usize = 17;
+
+/// Database IP version type.
+///
+/// 数据库 IP 版本类型。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum DbType {
+    Ipv4,
+    Ipv6,
+}
+
+impl DbType {
+    /// Decode the type byte at the start of the super part; only its lowest bit is used.
+    pub(crate) fn from_type_byte(byte: u8) -> Self {
+        if byte & 1 == 0 {
+            DbType::Ipv4
+        } else {
+            DbType::Ipv6
+        }
+    }
+
+    /// Returns true if the IP matches this database type.
+    ///
+    /// 判断给定 IP 是否与数据库类型一致。
+    pub fn compare(&self, ip: &IpAddr) -> bool {
+        match self {
+            DbType::Ipv4 => ip.is_ipv4(),
+            DbType::Ipv6 => ip.is_ipv6(),
+        }
+    }
+
+    /// Returns the index block length for this database type.
+    ///
+    /// 返回该数据库类型的索引块长度。
+    pub fn index_block_len(&self) -> usize {
+        match self {
+            DbType::Ipv4 => 13,
+            DbType::Ipv6 => 37,
+        }
+    }
+
+    /// Returns the IP byte length for this database type.
+    ///
+    /// 返回该数据库类型的 IP 字节长度。
+    pub fn bytes_len(&self) -> usize {
+        match self {
+            DbType::Ipv4 => 4,
+            DbType::Ipv6 => 16,
+        }
+    }
+}
+
+/// A single index block decoded from the index section.
+///
+/// 从索引区解析出的单个索引块。
+#[derive(Debug, Clone, Copy)]
+pub struct IndexBlock {
+    pub start_ip: [u8; 16],
+    pub end_ip: [u8; 16],
+    pub data_ptr: usize,
+    pub data_len: usize,
+}
+
+impl IndexBlock {
+    /// Decode the index block at offset `p` of `index`.
+    ///
+    /// 解析 `index` 中偏移 `p` 处的索引块。
+    pub fn read(index: &[u8], p: usize, db_type: DbType) -> Self {
+        let ip_len = db_type.bytes_len();
+        let mut start_ip = [0u8; 16];
+        let mut end_ip = [0u8; 16];
+        start_ip[..ip_len].copy_from_slice(&index[p..p + ip_len]);
+        end_ip[..ip_len].copy_from_slice(&index[p + ip_len..p + ip_len * 2]);
+        let data_ptr = u32::from_le_bytes([
+            index[p + ip_len * 2],
+            index[p + ip_len * 2 + 1],
+            index[p + ip_len * 2 + 2],
+            index[p + ip_len * 2 + 3],
+        ]) as usize;
+        let data_len = index[p + ip_len * 2 + 4] as usize;
+        Self {
+            start_ip,
+            end_ip,
+            data_ptr,
+            data_len,
+        }
+    }
+
+    /// Classify the given IP bytes against this block's boundaries.
+    ///
+    /// 判断给定 IP 字节相对于该块边界的位置。
+    #[cfg(feature = "std")]
+    pub fn boundary_kind(&self, ip_bytes: &[u8; 16], db_type: DbType) -> BoundaryKind {
+        BoundaryKind::classify(ip_bytes, &self.start_ip, &self.end_ip, db_type.bytes_len())
+    }
+
+    /// Whether this block covers exactly the single address `ip_bytes`.
+    ///
+    /// 判断该块是否仅覆盖单个地址 `ip_bytes`。
+    #[cfg(feature = "std")]
+    pub fn is_exact(&self, ip_bytes: &[u8; 16], db_type: DbType) -> bool {
+        let len = db_type.bytes_len();
+        self.start_ip[..len] == ip_bytes[..len] && self.end_ip[..len] == ip_bytes[..len]
+    }
+}
+
+/// Position of a queried address relative to the block that covers it.
+///
+/// 查询地址相对于其所在索引块的位置。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum BoundaryKind {
+    /// The address equals the block start (also used for single-address blocks).
+    ///
+    /// 地址等于块起始地址（单地址块同样返回此值）。
+    Start,
+    /// The address equals the block end.
+    ///
+    /// 地址等于块结束地址。
+    End,
+    /// The address lies strictly inside the block.
+    ///
+    /// 地址位于块内部。
+    Interior,
+}
+
+#[cfg(feature = "std")]
+impl BoundaryKind {
+    pub(crate) fn classify(ip_bytes: &[u8], start_ip: &[u8], end_ip: &[u8], length: usize) -> Self {
+        if compare_bytes(ip_bytes, start_ip, length) == Ordering::Equal {
+            BoundaryKind::Start
+        } else if compare_bytes(ip_bytes, end_ip, length) == Ordering::Equal {
+            BoundaryKind::End
+        } else {
+            BoundaryKind::Interior
+        }
+    }
+}
+
+/// Sink for binary-search steps.
+///
+/// `usize` counts them for the instrumented searches; `()` ignores them, so every other
+/// lookup is monomorphized to the plain search with no counter in the loop.
+pub trait Iterations {
+    /// Record one binary-search step.
+    fn step(&mut self);
+}
+
+impl Iterations for () {
+    #[inline(always)]
+    fn step(&mut self) {}
+}
+
+impl Iterations for usize {
+    #[inline(always)]
+    fn step(&mut self) {
+        *self += 1;
+    }
+}
+
+/// Binary search a contiguous run of index blocks for the one covering `ip_bytes`.
+///
+/// Returns the offset of the matching block within `index`. Each binary-search step is
+/// recorded in `iterations`.
+///
+/// 在连续的索引块中二分查找覆盖 `ip_bytes` 的块，返回其在 `index` 中的偏移；
+/// 每步二分查找都会记录到 `iterations`。
+pub fn find_index_block(
+    index: &[u8],
+    ip_bytes: &[u8; 16],
+    db_type: DbType,
+    iterations: &mut impl Iterations,
+) -> Option<usize> {
+    let ip_len = db_type.bytes_len();
+    let blen = db_type.index_block_len();
+    if index.len() < blen {
+        return None;
+    }
+
+    let mut l = 0usize;
+    let mut h = index.len() / blen - 1;
+    while l <= h {
+        iterations.step();
+        let m = (l + h) >> 1;
+        let p = m * blen;
+        let start_ip = &index[p..p + ip_len];
+        let end_ip = &index[p + ip_len..p + ip_len * 2];
+        let cmp_start = compare_bytes(ip_bytes, start_ip, ip_len);
+        let cmp_end = compare_bytes(ip_bytes, end_ip, ip_len);
+
+        if cmp_start != Ordering::Less && cmp_end != Ordering::Greater {
+            return Some(p);
+        } else if cmp_start == Ordering::Less {
+            if m == 0 {
+                break;
+            }
+            h = m - 1;
+        } else {
+            l = m + 1;
+        }
+    }
+
+    None
+}
+
+/// Copy an IP address into the 16-byte buffer layout used by the index.
+///
+/// 将 IP 地址复制为索引使用的 16 字节布局。
+pub fn ip_to_bytes(ip: IpAddr) -> [u8; 16] {
+    let mut ip_bytes = [0u8; 16];
+    match ip {
+        IpAddr::V4(ip) => ip_bytes[..4].copy_from_slice(&ip.octets()),
+        IpAddr::V6(ip) => ip_bytes.copy_from_slice(&ip.octets()),
+    }
+    ip_bytes
+}
+
+/// Convert index IP bytes back into an address of the given database type.
+///
+/// 将索引中的 IP 字节转换回对应数据库类型的地址。
+pub fn bytes_to_ip(ip_bytes: &[u8; 16], db_type: DbType) -> IpAddr {
+    match db_type {
+        DbType::Ipv4 => IpAddr::V4(Ipv4Addr::new(
+            ip_bytes[0],
+            ip_bytes[1],
+            ip_bytes[2],
+            ip_bytes[3],
+        )),
+        DbType::Ipv6 => IpAddr::V6(Ipv6Addr::from(*ip_bytes)),
+    }
+}
+
+/// Compare two byte slices for the first `length` bytes.
+///
+/// 比较两个字节切片的前 `length` 字节。
+pub fn compare_bytes(a: &[u8], b: &[u8], length: usize) -> Ordering {
+    for i in 0..length {
+        if a[i] < b[i] {
+            return Ordering::Less;
+        } else if a[i] > b[i] {
+            return Ordering::Greater;
+        }
+    }
+    Ordering::Equal
+}
+
+/// Split a packed `geo_pos_mix_size` into the geo-map `(data_ptr, data_len)`.
+///
+/// 将 `geo_pos_mix_size` 拆分为地理映射中的 `(data_ptr, data_len)`。
+pub fn split_geo_pos_mix(geo_pos_mix_size: u64) -> (usize, usize) {
+    let data_len = ((geo_pos_mix_size >> 24) & 0xff) as usize;
+    let data_ptr = (geo_pos_mix_size & 0x00ffffff) as usize;
+    (data_ptr, data_len)
+}
+
+/// Data section of a CZDB file, parsed in place from a decrypted buffer.
+///
+/// `data` starts after the hyper header and its padding, i.e. at offset
+/// `12 + padding + encrypted_block_size` of the file, and runs to the end of the file. Lookups
+/// binary-search the whole index directly instead of going through the header index.
+///
+/// 从已解密缓冲区就地解析的 CZDB 数据区。`data` 从超头及其填充之后开始，即文件中
+/// `12 + padding + encrypted_block_size` 偏移处，直到文件末尾。查询直接对整个索引二分查找，
+/// 不经过头部索引。
+#[derive(Debug, Clone, Copy)]
+pub struct DataSection<'a> {
+    db_type: DbType,
+    data: &'a [u8],
+    index: &'a [u8],
+    column_selection: u32,
+    geo_map: &'a [u8],
+}
+
+impl<'a> DataSection<'a> {
+    /// Parse the super part and locate the index and geo map, returning `None` when
+    /// `data` is truncated or its offsets are inconsistent.
+    ///
+    /// 解析超级块并定位索引与地理映射；`data` 被截断或偏移不一致时返回 `None`。
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        let u32_at = |pos: usize| {
+            let bytes = data.get(pos..pos.checked_add(4)?)?;
+            Some(u32::from_le_bytes(bytes.try_into().ok()?) as usize)
+        };
+        let db_type = DbType::from_type_byte(*data.first()?);
+        if u32_at(1)? != data.len() || data.len() < SUPER_PART_LENGTH {
+            return None;
+        }
+        let (start_index, end_index) = (u32_at(5)?, u32_at(13)?);
+        let blen = db_type.index_block_len();
+        if end_index < start_index || !(end_index - start_index).is_multiple_of(blen) {
+            return None;
+        }
+        let index_end = end_index.checked_add(blen)?;
+        let index = data.get(start_index..index_end)?;
+        let column_selection = u32_at(index_end)? as u32;
+        let geo_map = if column_selection == 0 {
+            &[][..]
+        } else {
+            let geo_map_start = index_end + 8;
+            data.get(geo_map_start..geo_map_start.checked_add(u32_at(index_end + 4)?)?)?
+        };
+        Some(Self {
+            db_type,
+            data,
+            index,
+            column_selection,
+            geo_map,
+        })
+    }
+
+    /// IP version of the database.
+    ///
+    /// 数据库的 IP 版本。
+    pub fn db_type(&self) -> DbType {
+        self.db_type
+    }
+
+    /// Number of index entries.
+    ///
+    /// 索引条目数量。
+    pub fn entry_count(&self) -> usize {
+        self.index.len() / self.db_type.index_block_len()
+    }
+
+    /// Geo-map column mask; bit `n + 1` selects column `n`.
+    ///
+    /// 地理映射列掩码；第 `n + 1` 位选择第 `n` 列。
+    pub fn column_selection(&self) -> u32 {
+        self.column_selection
+    }
+
+    /// The geo map as stored, still encrypted; decrypt a copy with `decrypt_geo_map`.
+    ///
+    /// 按原样存储、仍处于加密状态的地理映射；可用 `decrypt_geo_map` 解密其副本。
+    pub fn geo_map(&self) -> &'a [u8] {
+        self.geo_map
+    }
+
+    /// Find the entry covering `ip`, or `None` on a miss or an IP version mismatch.
+    ///
+    /// As with the std backends' default, IPv4-mapped and IPv4-compatible IPv6 addresses are
+    /// looked up as IPv4 in an IPv4 database.
+    ///
+    /// 查找覆盖 `ip` 的条目；未命中或 IP 版本不一致时返回 `None`。与 std 后端的默认行为一致，
+    /// 在 IPv4 数据库中 IPv4 映射与 IPv4 兼容的 IPv6 地址按 IPv4 查询。
+    pub fn find(&self, ip: IpAddr) -> Option<SectionEntry<'a>> {
+        let ip = normalize_ip(ip, self.db_type);
+        if !self.db_type.compare(&ip) {
+            return None;
+        }
+        let p = find_index_block(self.index, &ip_to_bytes(ip), self.db_type, &mut ())?;
+        let block = IndexBlock::read(self.index, p, self.db_type);
+        let region = self
+            .data
+            .get(block.data_ptr..block.data_ptr.checked_add(block.data_len)?)?;
+        Some(SectionEntry {
+            start_ip: bytes_to_ip(&block.start_ip, self.db_type),
+            end_ip: bytes_to_ip(&block.end_ip, self.db_type),
+            region,
+        })
+    }
+}
+
+/// Index entry found by `DataSection::find`, borrowing its region record.
+///
+/// 由 `DataSection::find` 找到的索引条目，借用其区域记录。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SectionEntry<'a> {
+    /// First address of the range.
+    ///
+    /// 范围的起始地址。
+    pub start_ip: IpAddr,
+    /// Last address of the range.
+    ///
+    /// 范围的结束地址。
+    pub end_ip: IpAddr,
+    /// The MessagePack region record: a geo position integer followed by `other_data`.
+    ///
+    /// MessagePack 区域记录：地理位置整数，其后为 `other_data`。
+    pub region: &'a [u8],
+}
+
+impl<'a> SectionEntry<'a> {
+    /// The `other_data` string of the region record, or `None` if it is malformed.
+    ///
+    /// 区域记录中的 `other_data` 字符串；格式错误时返回 `None`。
+    pub fn other_data(&self) -> Option<&'a str> {
+        let mut rest = self.region;
+        rmp::decode::read_int::<u64, _>(&mut rest).ok()?;
+        rmp::decode::read_str_from_slice(rest)
+            .ok()
+            .map(|(other_data, _)| other_data)
+    }
+
+    /// The MessagePack column array this record references in the decrypted `geo_map`.
+    ///
+    /// Returns `None` for inline records and references outside `geo_map`.
+    ///
+    /// 该记录在已解密的 `geo_map` 中引用的 MessagePack 列数组；内联记录或引用越界时返回 `None`。
+    pub fn geo_columns<'g>(&self, geo_map: &'g [u8]) -> Option<&'g [u8]> {
+        let geo_pos_mix_size = rmp::decode::read_int::<u64, _>(&mut &self.region[..]).ok()?;
+        if geo_pos_mix_size == 0 {
+            return None;
+        }
+        let (data_ptr, data_len) = split_geo_pos_mix(geo_pos_mix_size);
+        geo_map.get(data_ptr..data_ptr + data_len)
+    }
+}
+
+/// Address to look up for `ip` in a `db_type` database: IPv4-mapped (`::ffff:a.b.c.d`) and
+/// IPv4-compatible (`::a.b.c.d`) IPv6 addresses become IPv4 for IPv4 databases. `::` and
+/// `::1` are left alone.
+///
+/// 返回在 `db_type` 数据库中实际查询的地址：对 IPv4 数据库，IPv4 映射与 IPv4 兼容的 IPv6
+/// 地址转换为 IPv4。`::` 与 `::1` 保持不变。
+pub(crate) fn normalize_ip(ip: IpAddr, db_type: DbType) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) if db_type == DbType::Ipv4 && !v6.is_unspecified() && !v6.is_loopback() => {
+            v6.to_ipv4().map_or(ip, IpAddr::V4)
+        }
+        _ => ip,
+    }
+}
+
+/// Decrypt geo-map bytes in place with the 16-byte database key.
+///
+/// 使用 16 字节数据库密钥就地解密地理映射数据。
+pub fn decrypt_geo_map(geo_map: &mut [u8], key: &[u8; 16]) {
+    for (i, byte) in geo_map.iter_mut().enumerate() {
+        *byte ^= key[i % key.len()];
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::fixture::{FixtureBuilder, TEST_KEY_BYTES, geo_columns, region_payload};
+
+    #[test]
+    fn data_section_finds_entries_without_std_io() {
+        let mut geo_map = Vec::new();
+        let mix = geo_columns(&mut geo_map, &["中国", "广东"]);
+        let fixture = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.255.255.255", "inline")
+            .block_payload("2.0.0.0", "2.255.255.255", region_payload(mix, "电信"))
+            .geo_map(0b110, geo_map.clone());
+        let bytes = fixture.build();
+        let section = DataSection::parse(&bytes[fixture.data_offset()..]).unwrap();
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+        assert_eq!(section.db_type(), DbType::Ipv4);
+        assert_eq!(section.entry_count(), 2);
+        assert_eq!(section.column_selection(), 0b110);
+
+        let inline = section.find(ip("1.2.3.4")).unwrap();
+        assert_eq!(inline.start_ip, ip("1.0.0.0"));
+        assert_eq!(inline.end_ip, ip("1.255.255.255"));
+        assert_eq!(inline.other_data(), Some("inline"));
+        assert_eq!(section.find(ip("::ffff:1.2.3.4")), Some(inline));
+
+        let mut decrypted = section.geo_map().to_vec();
+        decrypt_geo_map(&mut decrypted, &TEST_KEY_BYTES);
+        assert_eq!(decrypted, geo_map);
+        let mapped = section.find(ip("2.0.0.1")).unwrap();
+        assert_eq!(mapped.other_data(), Some("电信"));
+        assert_eq!(mapped.geo_columns(&decrypted), Some(&geo_map[..]));
+        assert_eq!(inline.geo_columns(&decrypted), None);
+
+        assert_eq!(section.find(ip("3.0.0.1")), None);
+        assert_eq!(section.find(ip("::1")), None);
+        assert!(DataSection::parse(&bytes[fixture.data_offset()..bytes.len() - 1]).is_none());
+    }
+}
@@ -0,0 +1,80 @@
+use crate::CzdbMmap;
+use std::{
+    convert::Infallible,
+    future::{Ready, ready},
+    net::IpAddr,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tower_service::Service;
+
+/// `tower::Service` answering IP lookups from a shared `CzdbMmap`.
+///
+/// The lookup is synchronous and CPU-only, so it runs inline in `call` and the returned
+/// future is already complete. The service is always ready and never fails; clones share
+/// the same database.
+///
+/// 基于共享 `CzdbMmap` 响应 IP 查询的 `tower::Service`。查询是同步且仅占用 CPU 的，
+/// 因此直接在 `call` 中执行，返回的 future 已完成。该服务始终就绪且不会失败，克隆共享同一个数据库。
+#[derive(Debug, Clone)]
+pub struct CzdbService {
+    db: Arc<CzdbMmap>,
+}
+
+impl CzdbService {
+    /// Wrap a shared database.
+    ///
+    /// 包装共享的数据库。
+    pub fn new(db: Arc<CzdbMmap>) -> Self {
+        Self { db }
+    }
+
+    /// The wrapped database.
+    ///
+    /// 被包装的数据库。
+    pub fn database(&self) -> &Arc<CzdbMmap> {
+        &self.db
+    }
+}
+
+impl Service<IpAddr> for CzdbService {
+    type Response = Option<String>;
+    type Error = Infallible;
+    type Future = Ready<Result<Option<String>, Infallible>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, ip: IpAddr) -> Self::Future {
+        ready(Ok(self.db.search(ip)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        DbType,
+        fixture::{FixtureBuilder, TempDb, test_key},
+    };
+    use std::task::Waker;
+
+    #[test]
+    fn service_answers_lookups_inline() {
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.255.255.255", "beijing")
+            .block("2.0.0.0", "2.255.255.255", "shanghai")
+            .build();
+        let tmp = TempDb::new(&bytes);
+        let db = Arc::new(CzdbMmap::open(tmp.path_str(), &test_key()).unwrap());
+        let mut service = CzdbService::new(db);
+
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(matches!(service.poll_ready(&mut cx), Poll::Ready(Ok(()))));
+        let response = service.call("2.3.4.5".parse().unwrap()).into_inner();
+        assert_eq!(response.unwrap().as_deref(), Some("shanghai"));
+        let response = service.call("9.9.9.9".parse().unwrap()).into_inner();
+        assert_eq!(response.unwrap(), None);
+    }
+}
@@ -0,0 +1,71 @@
+use crate::{CzdbDisk, CzdbMemory};
+use std::net::IpAddr;
+
+/// Assert that every available backend returns the same `search` result for each of `ips`.
+///
+/// Opens `db_path` with `CzdbDisk`, `CzdbMemory` and (with the `mmap` feature) `CzdbMmap`.
+/// Panics if any backend fails to open, or on the first address whose results differ,
+/// listing what each backend returned.
+///
+/// 断言所有可用后端对 `ips` 中每个地址的 `search` 结果一致。使用 `CzdbDisk`、`CzdbMemory`
+/// 以及（启用 `mmap` feature 时）`CzdbMmap` 打开 `db_path`；任一后端打开失败，
+/// 或遇到首个结果不一致的地址时 panic，并列出各后端的返回值。
+pub fn assert_backends_agree(db_path: &str, key: &str, ips: &[IpAddr]) {
+    let disk = CzdbDisk::open(db_path, key)
+        .unwrap_or_else(|e| panic!("CzdbDisk failed to open {db_path}: {e}"));
+    let memory = CzdbMemory::open(db_path, key)
+        .unwrap_or_else(|e| panic!("CzdbMemory failed to open {db_path}: {e}"));
+    #[cfg(feature = "mmap")]
+    let mmap = crate::CzdbMmap::open(db_path, key)
+        .unwrap_or_else(|e| panic!("CzdbMmap failed to open {db_path}: {e}"));
+
+    for &ip in ips {
+        #[cfg(feature = "mmap")]
+        let mmap_result = Some(("mmap", mmap.search(ip)));
+        #[cfg(not(feature = "mmap"))]
+        let mmap_result = None;
+        let results: Vec<(&str, Option<String>)> =
+            [("disk", disk.search(ip)), ("memory", memory.search(ip))]
+                .into_iter()
+                .chain(mmap_result)
+                .collect();
+
+        if results.iter().any(|(_, region)| *region != results[0].1) {
+            let detail: Vec<String> = results
+                .iter()
+                .map(|(backend, region)| format!("  {backend}: {region:?}"))
+                .collect();
+            panic!(
+                "backends disagree for {ip} in {db_path}:\n{}",
+                detail.join("\n")
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        DbType,
+        fixture::{FixtureBuilder, TempDb, geo_columns, region_payload, test_key},
+    };
+
+    #[test]
+    fn backends_agree_on_fixture() {
+        let mut geo_map = Vec::new();
+        let mix = geo_columns(&mut geo_map, &["中国", "北京"]);
+        let bytes = FixtureBuilder::new(DbType::Ipv4)
+            .block("1.0.0.0", "1.255.255.255", "inline")
+            .block_payload("2.0.0.0", "2.255.255.255", region_payload(mix, "联通"))
+            .block("3.0.0.0", "3.255.255.255", "beijing")
+            .geo_map(0b110, geo_map)
+            .build();
+        let tmp = TempDb::new(&bytes);
+        let ips: Vec<IpAddr> = ["0.0.0.1", "1.1.1.1", "2.2.2.2", "3.3.3.3", "9.9.9.9", "::1"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        assert_backends_agree(tmp.path_str(), &test_key(), &ips);
+    }
+}